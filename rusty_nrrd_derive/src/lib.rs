@@ -0,0 +1,340 @@
+//! Derive macro for `rusty_nrrd::PixelValue`, re-exported by `rusty_nrrd` with the `derive` feature.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Group, TokenStream as TokenStream2};
+use quote::quote;
+use syn::{
+    parse_macro_input, parse_quote, spanned::Spanned, token::Paren, Data, DeriveInput, Error,
+    Fields, LitStr, Member, Type,
+};
+
+const NUMERIC_TYPES: [&str; 10] = [
+    "i8", "u8", "i16", "u16", "i32", "u32", "i64", "u64", "f32", "f64",
+];
+
+/// Implements `PixelValue` for a `#[repr(C)]` struct.
+///
+/// When all fields share one numeric type the struct becomes a multi-component pixel
+/// stored along an extra fastest axis, written with kind `vector` unless `#[nrrd(kind = "...")]` is given.
+/// Otherwise, or with `#[nrrd(block)]`, it becomes a `block` pixel with fields packed in order,
+/// each one in little endian whatever the endian of the file, as bytes of a block are never swapped.
+/// Every field type of a generic struct must implement `PixelValue`.
+#[proc_macro_derive(PixelValue, attributes(nrrd))]
+pub fn derive_pixel_value(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+#[derive(Default)]
+struct Options {
+    block: bool,
+    kind: Option<LitStr>,
+}
+
+fn expand(input: DeriveInput) -> Result<TokenStream2, Error> {
+    check_repr_c(&input)?;
+    let options = parse_options(&input)?;
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            Fields::Unnamed(fields) => &fields.unnamed,
+            Fields::Unit => return Err(Error::new(input.span(), "struct must have fields")),
+        },
+        _ => return Err(Error::new(input.span(), "only structs are supported")),
+    };
+
+    let members: Vec<Member> = fields
+        .iter()
+        .enumerate()
+        .map(|(i, f)| match &f.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(i.into()),
+        })
+        .collect();
+    let types: Vec<&Type> = fields.iter().map(|f| &f.ty).collect();
+
+    let component_type = shared_numeric_type(&types).filter(|_| !options.block);
+    if component_type.is_none() && options.kind.is_some() {
+        return Err(Error::new(
+            input.span(),
+            "`kind` requires all fields to share one numeric type",
+        ));
+    }
+
+    let body = match component_type {
+        Some(ty) => expand_components(ty, &members, options.kind),
+        None => expand_block(&members, &types),
+    };
+
+    let mut generics = input.generics.clone();
+    if !generics.params.is_empty() {
+        let where_clause = generics.make_where_clause();
+        for ty in &types {
+            where_clause
+                .predicates
+                .push(parse_quote!(#ty: ::rusty_nrrd::PixelValue));
+        }
+    }
+
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::rusty_nrrd::PixelValue for #name #type_generics #where_clause {
+            #body
+        }
+    })
+}
+
+fn expand_components(ty: &Type, members: &[Member], kind: Option<LitStr>) -> TokenStream2 {
+    let count = members.len();
+    let index = 0..count;
+    let index_to = 0..count;
+
+    let kind_fns = match kind {
+        Some(kind) => quote! {
+            #[inline]
+            fn kind() -> ::std::option::Option<&'static str> {
+                ::std::option::Option::Some(#kind)
+            }
+        },
+        None => quote! {
+            #[inline]
+            fn kind() -> ::std::option::Option<&'static str> {
+                ::std::option::Option::Some("vector")
+            }
+
+            #[inline]
+            fn accepts_kind(_kind: &str) -> bool {
+                true
+            }
+        },
+    };
+
+    quote! {
+        fn from_bytes(buffer: &[u8], endian: ::rusty_nrrd::Endian) -> Self {
+            let size = <#ty as ::rusty_nrrd::PixelValue>::pixel_size();
+            Self {
+                #(#members: <#ty as ::rusty_nrrd::PixelValue>::from_bytes(&buffer[#index * size..], endian),)*
+            }
+        }
+
+        fn to_bytes(&self, buffer: &mut [u8], endian: ::rusty_nrrd::Endian) {
+            let size = <#ty as ::rusty_nrrd::PixelValue>::pixel_size();
+            #(::rusty_nrrd::PixelValue::to_bytes(
+                &self.#members,
+                &mut buffer[#index_to * size..(#index_to + 1) * size],
+                endian,
+            );)*
+        }
+
+        #[inline]
+        fn pixel_type() -> ::rusty_nrrd::PixelType {
+            <#ty as ::rusty_nrrd::PixelValue>::pixel_type()
+        }
+
+        #[inline]
+        fn components() -> usize {
+            #count
+        }
+
+        #kind_fns
+    }
+}
+
+fn expand_block(members: &[Member], types: &[&Type]) -> TokenStream2 {
+    quote! {
+        #[allow(unused_assignments)]
+        fn from_bytes(buffer: &[u8], _endian: ::rusty_nrrd::Endian) -> Self {
+            let endian = ::rusty_nrrd::Endian::Little;
+            let mut offset = 0;
+            Self {
+                #(#members: {
+                    let value = <#types as ::rusty_nrrd::PixelValue>::from_bytes(&buffer[offset..], endian);
+                    offset += <#types as ::rusty_nrrd::PixelValue>::pixel_size();
+                    value
+                },)*
+            }
+        }
+
+        #[allow(unused_assignments)]
+        fn to_bytes(&self, buffer: &mut [u8], _endian: ::rusty_nrrd::Endian) {
+            let endian = ::rusty_nrrd::Endian::Little;
+            let mut offset = 0;
+            #({
+                let size = <#types as ::rusty_nrrd::PixelValue>::pixel_size();
+                ::rusty_nrrd::PixelValue::to_bytes(&self.#members, &mut buffer[offset..offset + size], endian);
+                offset += size;
+            })*
+        }
+
+        #[inline]
+        fn pixel_type() -> ::rusty_nrrd::PixelType {
+            let size = 0 #(+ <#types as ::rusty_nrrd::PixelValue>::pixel_size())*;
+            ::rusty_nrrd::PixelType::Block(size as i32)
+        }
+    }
+}
+
+/// Type of all fields if it is the same built-in numeric type
+fn shared_numeric_type<'t>(types: &[&'t Type]) -> Option<&'t Type> {
+    let first = *types.first()?;
+    let name = match first {
+        Type::Path(path) if path.qself.is_none() => path.path.get_ident()?.to_string(),
+        _ => return None,
+    };
+
+    let is_shared = types.iter().all(|ty| quote!(#ty).to_string() == name);
+    (NUMERIC_TYPES.contains(&name.as_str()) && is_shared).then_some(first)
+}
+
+fn check_repr_c(input: &DeriveInput) -> Result<(), Error> {
+    let mut is_repr_c = false;
+
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("repr")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("C") {
+                is_repr_c = true;
+            }
+            // Skip arguments such as the `(8)` of `align(8)`
+            if meta.input.peek(Paren) {
+                meta.input.parse::<Group>()?;
+            }
+            Ok(())
+        })?;
+    }
+
+    if !is_repr_c {
+        return Err(Error::new(input.span(), "struct must be `#[repr(C)]`"));
+    }
+
+    Ok(())
+}
+
+fn parse_options(input: &DeriveInput) -> Result<Options, Error> {
+    let mut options = Options::default();
+
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("nrrd")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("block") {
+                options.block = true;
+                Ok(())
+            } else if meta.path.is_ident("kind") {
+                options.kind = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `block` or `kind = \"...\"`"))
+            }
+        })?;
+    }
+
+    if options.block && options.kind.is_some() {
+        return Err(Error::new(
+            input.span(),
+            "`block` and `kind` cannot be used together",
+        ));
+    }
+
+    Ok(options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::expand;
+    use syn::{parse_quote, DeriveInput};
+
+    fn error(input: DeriveInput) -> String {
+        expand(input).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn expands_shared_numeric_fields_to_components() {
+        let expanded = expand(parse_quote! {
+            #[repr(C)]
+            #[nrrd(kind = "3-vector")]
+            struct Point { x: f32, y: f32, z: f32 }
+        })
+        .unwrap()
+        .to_string();
+
+        assert!(expanded.contains("fn components () -> usize { 3usize }"));
+        assert!(expanded.contains("Some (\"3-vector\")"));
+        assert!(!expanded.contains("Block"));
+        assert!(!expanded.contains("accepts_kind"));
+    }
+
+    #[test]
+    fn expands_mixed_fields_to_block() {
+        let expanded = expand(parse_quote! {
+            #[repr(C, packed)]
+            struct Record(u8, f64);
+        })
+        .unwrap()
+        .to_string();
+        assert!(expanded.contains("PixelType :: Block"));
+        assert!(!expanded.contains("fn components"));
+
+        let forced = expand(parse_quote! {
+            #[repr(C)]
+            #[nrrd(block)]
+            struct Pair { a: u16, b: u16 }
+        })
+        .unwrap()
+        .to_string();
+        assert!(forced.contains("PixelType :: Block"));
+        assert!(forced.contains("Endian :: Little"));
+    }
+
+    #[test]
+    fn bounds_field_types_of_generic_structs() {
+        let expanded = expand(parse_quote! {
+            #[repr(C)]
+            struct Tagged<T> { tag: u8, value: T }
+        })
+        .unwrap()
+        .to_string();
+
+        assert!(expanded.contains("T : :: rusty_nrrd :: PixelValue"));
+        assert!(expanded.contains("u8 : :: rusty_nrrd :: PixelValue"));
+    }
+
+    #[test]
+    fn accepts_repr_arguments() {
+        assert!(expand(parse_quote! {
+            #[repr(C, align(8))]
+            struct Aligned { a: f64, b: f64 }
+        })
+        .is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        assert!(error(parse_quote! { struct Plain { a: u8 } }).contains("repr(C)"));
+        assert!(
+            error(parse_quote! { #[repr(align(8))] struct Aligned { a: u8 } }).contains("repr(C)")
+        );
+        assert!(error(parse_quote! { #[repr(C)] enum Choice { A } }).contains("only structs"));
+        assert!(error(parse_quote! { #[repr(C)] struct Unit; }).contains("must have fields"));
+        assert!(error(parse_quote! {
+            #[repr(C)]
+            #[nrrd(kind = "complex")]
+            struct Mixed { a: u8, b: i8 }
+        })
+        .contains("share one numeric type"));
+        assert!(error(parse_quote! {
+            #[repr(C)]
+            #[nrrd(block, kind = "complex")]
+            struct Both { a: u8 }
+        })
+        .contains("cannot be used together"));
+        assert!(error(parse_quote! {
+            #[repr(C)]
+            #[nrrd(packed)]
+            struct Unknown { a: u8 }
+        })
+        .contains("expected `block`"));
+    }
+}
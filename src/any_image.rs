@@ -0,0 +1,192 @@
+use crate::{
+    image::{Image, ImageFromNrrdErr},
+    nrrd::{reader::read_nrrd, Nrrd, PixelType},
+};
+use std::io::Read;
+
+/// Image of any pixel type known only at runtime
+pub enum AnyImage<const D: usize> {
+    I8(Image<i8, D>),
+    U8(Image<u8, D>),
+    I16(Image<i16, D>),
+    U16(Image<u16, D>),
+    I32(Image<i32, D>),
+    U32(Image<u32, D>),
+    I64(Image<i64, D>),
+    U64(Image<u64, D>),
+    F32(Image<f32, D>),
+    F64(Image<f64, D>),
+}
+
+/// Runs `$body` with `$image` bound to the `Image<T, D>` inside an `AnyImage`.
+/// Works on owned values and references alike.
+///
+/// ```
+/// use rusty_nrrd::{visit_any_image, AnyImage, Image};
+///
+/// let image: AnyImage<2> = Image::<u16, 2>::new(3, [4, 2]).into();
+/// let count = visit_any_image!(&image, img => img.pixels().len());
+/// assert_eq!(count, 8);
+/// ```
+#[macro_export]
+macro_rules! visit_any_image {
+    ($any:expr, $image:ident => $body:expr) => {
+        match $any {
+            $crate::AnyImage::I8($image) => $body,
+            $crate::AnyImage::U8($image) => $body,
+            $crate::AnyImage::I16($image) => $body,
+            $crate::AnyImage::U16($image) => $body,
+            $crate::AnyImage::I32($image) => $body,
+            $crate::AnyImage::U32($image) => $body,
+            $crate::AnyImage::I64($image) => $body,
+            $crate::AnyImage::U64($image) => $body,
+            $crate::AnyImage::F32($image) => $body,
+            $crate::AnyImage::F64($image) => $body,
+        }
+    };
+}
+
+/// Reads image with whatever pixel type the file stores
+pub fn read_any<TRead: Read, const D: usize>(
+    reader: TRead,
+) -> Result<AnyImage<D>, ImageFromNrrdErr> {
+    let nrrd = read_nrrd(reader)?;
+    AnyImage::try_from(&nrrd)
+}
+
+impl<const D: usize> AnyImage<D> {
+    pub fn pixel_type(&self) -> PixelType {
+        match self {
+            AnyImage::I8(_) => PixelType::Int8,
+            AnyImage::U8(_) => PixelType::UInt8,
+            AnyImage::I16(_) => PixelType::Int16,
+            AnyImage::U16(_) => PixelType::UInt16,
+            AnyImage::I32(_) => PixelType::Int32,
+            AnyImage::U32(_) => PixelType::UInt32,
+            AnyImage::I64(_) => PixelType::Int64,
+            AnyImage::U64(_) => PixelType::UInt64,
+            AnyImage::F32(_) => PixelType::Float32,
+            AnyImage::F64(_) => PixelType::Float64,
+        }
+    }
+
+    #[inline]
+    pub fn sizes(&self) -> &[usize; D] {
+        visit_any_image!(self, image => image.sizes())
+    }
+
+    #[inline]
+    pub fn pixels_count(&self) -> usize {
+        visit_any_image!(self, image => image.pixels_count())
+    }
+}
+
+/// Block pixels have no `Image` counterpart and fail with `PixelTypesDoNotMatch`
+impl<const D: usize> TryFrom<&Nrrd> for AnyImage<D> {
+    type Error = ImageFromNrrdErr;
+
+    fn try_from(nrrd: &Nrrd) -> Result<Self, Self::Error> {
+        Ok(match nrrd.pixel_type() {
+            PixelType::Int8 => AnyImage::I8(nrrd.try_into()?),
+            PixelType::UInt8 => AnyImage::U8(nrrd.try_into()?),
+            PixelType::Int16 => AnyImage::I16(nrrd.try_into()?),
+            PixelType::UInt16 => AnyImage::U16(nrrd.try_into()?),
+            PixelType::Int32 => AnyImage::I32(nrrd.try_into()?),
+            PixelType::UInt32 => AnyImage::U32(nrrd.try_into()?),
+            PixelType::Int64 => AnyImage::I64(nrrd.try_into()?),
+            PixelType::UInt64 => AnyImage::U64(nrrd.try_into()?),
+            PixelType::Float32 => AnyImage::F32(nrrd.try_into()?),
+            PixelType::Float64 => AnyImage::F64(nrrd.try_into()?),
+            PixelType::Block(_) => return Err(ImageFromNrrdErr::PixelTypesDoNotMatch),
+        })
+    }
+}
+
+impl<const D: usize> From<&AnyImage<D>> for Nrrd {
+    #[inline]
+    fn from(image: &AnyImage<D>) -> Self {
+        visit_any_image!(image, image => Nrrd::from(image))
+    }
+}
+
+macro_rules! impl_from_image {
+    ($type: ty, $variant: ident) => {
+        impl<const D: usize> From<Image<$type, D>> for AnyImage<D> {
+            #[inline]
+            fn from(image: Image<$type, D>) -> Self {
+                AnyImage::$variant(image)
+            }
+        }
+    };
+}
+
+impl_from_image!(i8, I8);
+impl_from_image!(u8, U8);
+impl_from_image!(i16, I16);
+impl_from_image!(u16, U16);
+impl_from_image!(i32, I32);
+impl_from_image!(u32, U32);
+impl_from_image!(i64, I64);
+impl_from_image!(u64, U64);
+impl_from_image!(f32, F32);
+impl_from_image!(f64, F64);
+
+#[cfg(test)]
+mod tests {
+    use super::{read_any, AnyImage};
+    use crate::{
+        convert::ConversionPolicy,
+        image::{Image, ImageFromNrrdErr},
+        nrrd::{writer::write_nrrd, Nrrd, PixelType},
+        pixel::NumericPixel,
+    };
+
+    /// Reads an image of `T` back as `AnyImage` and checks which variant it became
+    fn check<T: NumericPixel>(pixel_type: PixelType) {
+        let image = Image::<T, 2>::from_fn([3, 2], |[x, y]| {
+            T::from_f64((x + 3 * y) as f64, ConversionPolicy::Exact).unwrap()
+        });
+        let nrrd = Nrrd::from(&image);
+
+        let any = AnyImage::<2>::try_from(&nrrd).unwrap();
+        assert_eq!(any.pixel_type(), pixel_type);
+        assert_eq!(any.sizes(), &[3, 2]);
+        assert_eq!(any.pixels_count(), 6);
+        assert_eq!(Nrrd::from(&any).buffer(), nrrd.buffer());
+
+        let mut file = Vec::new();
+        write_nrrd(&nrrd, &mut file).unwrap();
+        let read = read_any::<_, 2>(file.as_slice()).unwrap();
+        assert_eq!(read.pixel_type(), pixel_type);
+    }
+
+    #[test]
+    fn reads_every_variant() {
+        check::<i8>(PixelType::Int8);
+        check::<u8>(PixelType::UInt8);
+        check::<i16>(PixelType::Int16);
+        check::<u16>(PixelType::UInt16);
+        check::<i32>(PixelType::Int32);
+        check::<u32>(PixelType::UInt32);
+        check::<i64>(PixelType::Int64);
+        check::<u64>(PixelType::UInt64);
+        check::<f32>(PixelType::Float32);
+        check::<f64>(PixelType::Float64);
+    }
+
+    #[test]
+    fn rejects_blocks_and_other_dimensions() {
+        let mut nrrd = Nrrd::raw_header_of(PixelType::Block(3), None, &[2]);
+        nrrd.set_buffer(vec![0; 6]);
+        assert!(matches!(
+            AnyImage::<1>::try_from(&nrrd),
+            Err(ImageFromNrrdErr::PixelTypesDoNotMatch)
+        ));
+
+        let nrrd = Nrrd::from(&Image::<f32, 2>::new(0.0, [2, 2]));
+        assert!(matches!(
+            AnyImage::<3>::try_from(&nrrd),
+            Err(ImageFromNrrdErr::DimensionsDoNotMatch)
+        ));
+    }
+}
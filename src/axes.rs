@@ -0,0 +1,1062 @@
+//! Axis surgery in the spirit of `unu`: permute, flip, slice, join, stack, split, merge,
+//! insert or delete singleton axes and reshape.
+//!
+//! `Nrrd` operations move raw pixels and keep per-axis fields (`spacings`, `kinds`, `labels`, ...)
+//! with the axes they describe. Flipping and slicing also move `space origin`, so that pixels keep
+//! their position in space, and flipping negates the `space directions` of the axis.
+//! Axes that gain or lose pixels drop `axis maxs` and kinds that imply a size.
+//!
+//! `Image` operations that change the number of axes take it as a const parameter `E`,
+//! which is checked at runtime.
+
+use crate::{image::Image, nrrd::Nrrd, pixel::PixelValue, storage::Storage};
+
+#[derive(Debug)]
+pub enum AxisErr {
+    AxisOutOfBounds,
+    IndexOutOfBounds,
+    InvalidPermutation,
+    DimensionsDoNotMatch,
+    SizesDoNotMatch,
+    PixelTypesDoNotMatch,
+    EndianDoesNotMatch,
+    AxisNotSingleton,
+    BufferSizeDoesNotMatch,
+    NothingToJoin,
+    AllBackground,
+    /// Pixels outside of an empty axis can only be filled with a constant
+    EmptyAxis,
+}
+
+const PER_AXIS_FIELDS: [&str; 10] = [
+    "spacings",
+    "thicknesses",
+    "axis mins",
+    "axis maxs",
+    "centers",
+    "centerings",
+    "labels",
+    "units",
+    "kinds",
+    "space directions",
+];
+
+/// Kinds that any number of pixels can have
+const SIZE_FREE_KINDS: [&str; 9] = [
+    "domain",
+    "space",
+    "time",
+    "list",
+    "point",
+    "vector",
+    "covariant-vector",
+    "normal",
+    "???",
+];
+
+impl Nrrd {
+    /// Axis `i` of the result is axis `axes[i]` of this one
+    pub fn permute_axes(&self, axes: &[usize]) -> Result<Nrrd, AxisErr> {
+        let sizes = self.checked_sizes()?;
+        check_permutation(axes, sizes.len())?;
+
+        let strides = contiguous_strides(&sizes);
+        let new_sizes = axes.iter().map(|&a| sizes[a]).collect::<Vec<_>>();
+        let new_strides = axes.iter().map(|&a| strides[a]).collect::<Vec<_>>();
+        let buffer = self.gather(0, &new_strides, &new_sizes);
+
+        let sources = axes.iter().map(|&a| Some(a)).collect::<Vec<_>>();
+        let fields = AxisFields::read(self).remap(&sources);
+        Ok(fields.apply(self.with_axes(&new_sizes, buffer)))
+    }
+
+    /// Reverses the order of pixels along `axis`
+    pub fn flip(&self, axis: usize) -> Result<Nrrd, AxisErr> {
+        let sizes = self.checked_sizes()?;
+        check_axis(axis, sizes.len())?;
+
+        let mut strides = contiguous_strides(&sizes);
+        let last = sizes[axis].saturating_sub(1);
+        let start = last as isize * strides[axis];
+        strides[axis] = -strides[axis];
+        let buffer = self.gather(start, &strides, &sizes);
+
+        let mut fields = AxisFields::read(self);
+        fields.flip(axis, last);
+        Ok(fields.apply(self.with_axes(&sizes, buffer)))
+    }
+
+    /// Pixels at `position` along `axis`, with that axis removed
+    pub fn slice(&self, axis: usize, position: usize) -> Result<Nrrd, AxisErr> {
+        let sizes = self.checked_sizes()?;
+        let new_sizes = sliced_sizes(&sizes, axis, position)?;
+        if new_sizes.is_empty() {
+            return Err(AxisErr::DimensionsDoNotMatch);
+        }
+
+        let mut strides = contiguous_strides(&sizes);
+        let start = position as isize * strides.remove(axis);
+        let buffer = self.gather(start, &strides, &new_sizes);
+
+        let mut fields = AxisFields::read(self);
+        fields.shift_origin(axis, position as f64);
+        let fields = fields.remap(&without_axis(sizes.len(), axis));
+        Ok(fields.apply(self.with_axes(&new_sizes, buffer)))
+    }
+
+    /// Concatenates `parts` along an existing `axis`, other sizes must match
+    pub fn join(parts: &[&Nrrd], axis: usize) -> Result<Nrrd, AxisErr> {
+        let (first, sizes) = check_parts(parts)?;
+        check_axis(axis, sizes.len())?;
+
+        let mut new_sizes = sizes.clone();
+        new_sizes[axis] = 0;
+        for part in parts {
+            let part_sizes = part.checked_sizes()?;
+            let matches = (0..sizes.len()).all(|a| a == axis || part_sizes[a] == sizes[a]);
+            if part_sizes.len() != sizes.len() || !matches {
+                return Err(AxisErr::SizesDoNotMatch);
+            }
+            new_sizes[axis] += part_sizes[axis];
+        }
+
+        let inner = sizes[..axis].iter().product::<usize>() * first.pixel_type().size();
+        let outer = sizes[axis + 1..].iter().product();
+        let blocks = parts
+            .iter()
+            .map(|p| inner * p.sizes()[axis] as usize)
+            .collect::<Vec<_>>();
+        let buffers = parts
+            .iter()
+            .map(|p| p.buffer().as_slice())
+            .collect::<Vec<_>>();
+        let buffer = concat_blocks(&buffers, &blocks, outer);
+
+        let mut fields = AxisFields::read(first);
+        fields.resized(axis);
+        Ok(fields.apply(first.with_axes(&new_sizes, buffer)))
+    }
+
+    /// Stacks `parts` of equal sizes along a new axis inserted at `axis`
+    pub fn stack(parts: &[&Nrrd], axis: usize) -> Result<Nrrd, AxisErr> {
+        let (first, sizes) = check_parts(parts)?;
+        for part in parts {
+            if part.checked_sizes()? != sizes {
+                return Err(AxisErr::SizesDoNotMatch);
+            }
+        }
+
+        let mut new_sizes = inserted_sizes(&sizes, axis)?;
+        new_sizes[axis] = parts.len();
+
+        let inner = sizes[..axis].iter().product::<usize>() * first.pixel_type().size();
+        let outer = sizes[axis..].iter().product();
+        let buffers = parts
+            .iter()
+            .map(|p| p.buffer().as_slice())
+            .collect::<Vec<_>>();
+        let buffer = concat_blocks(&buffers, &vec![inner; parts.len()], outer);
+
+        let fields = AxisFields::read(first).remap(&with_new_axis(sizes.len(), axis));
+        Ok(fields.apply(first.with_axes(&new_sizes, buffer)))
+    }
+
+    /// Splits `axis` into a fast axis of `fast_size` pixels followed by a slow one
+    pub fn split_axis(&self, axis: usize, fast_size: usize) -> Result<Nrrd, AxisErr> {
+        let sizes = self.checked_sizes()?;
+        let new_sizes = split_sizes(&sizes, axis, fast_size)?;
+
+        let mut sources = (0..sizes.len()).map(Some).collect::<Vec<_>>();
+        sources.insert(axis, Some(axis));
+
+        let mut fields = AxisFields::read(self).remap(&sources);
+        fields.resized(axis);
+        fields.resized(axis + 1);
+        fields.scale_step(axis + 1, fast_size as f64);
+        Ok(fields.apply(self.with_axes(&new_sizes, self.buffer().clone())))
+    }
+
+    /// Merges `axis` and the slower one following it into a single axis
+    pub fn merge_axes(&self, axis: usize) -> Result<Nrrd, AxisErr> {
+        let sizes = self.checked_sizes()?;
+        let new_sizes = merged_sizes(&sizes, axis)?;
+
+        let mut fields = AxisFields::read(self).remap(&without_axis(sizes.len(), axis + 1));
+        fields.resized(axis);
+        Ok(fields.apply(self.with_axes(&new_sizes, self.buffer().clone())))
+    }
+
+    /// Inserts an axis of size 1 at `axis`
+    pub fn insert_axis(&self, axis: usize) -> Result<Nrrd, AxisErr> {
+        let sizes = self.checked_sizes()?;
+        let new_sizes = inserted_sizes(&sizes, axis)?;
+
+        let fields = AxisFields::read(self).remap(&with_new_axis(sizes.len(), axis));
+        Ok(fields.apply(self.with_axes(&new_sizes, self.buffer().clone())))
+    }
+
+    /// Removes `axis`, which must have size 1
+    pub fn delete_axis(&self, axis: usize) -> Result<Nrrd, AxisErr> {
+        let sizes = self.checked_sizes()?;
+        let new_sizes = deleted_sizes(&sizes, axis)?;
+        if new_sizes.is_empty() {
+            return Err(AxisErr::DimensionsDoNotMatch);
+        }
+
+        let fields = AxisFields::read(self).remap(&without_axis(sizes.len(), axis));
+        Ok(fields.apply(self.with_axes(&new_sizes, self.buffer().clone())))
+    }
+
+    /// Same pixels in the same order with other sizes, per-axis fields become unknown
+    pub fn reshape(&self, sizes: &[usize]) -> Result<Nrrd, AxisErr> {
+        let old_sizes = self.checked_sizes()?;
+        if sizes.is_empty() || sizes.iter().product::<usize>() != old_sizes.iter().product() {
+            return Err(AxisErr::SizesDoNotMatch);
+        }
+
+        let fields = AxisFields::read(self).remap(&vec![None; sizes.len()]);
+        Ok(fields.apply(self.with_axes(sizes, self.buffer().clone())))
+    }
+
+    /// Sizes of axes, checked against the length of the buffer
+    pub(crate) fn checked_sizes(&self) -> Result<Vec<usize>, AxisErr> {
+        let sizes = self.sizes().iter().map(|&s| s as usize).collect::<Vec<_>>();
+        let expected = sizes.iter().product::<usize>() * self.pixel_type().size();
+
+        match self.buffer().len() == expected {
+            true => Ok(sizes),
+            false => Err(AxisErr::BufferSizeDoesNotMatch),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn gather(&self, start: isize, strides: &[isize], sizes: &[usize]) -> Vec<u8> {
+        gather(
+            self.buffer(),
+            self.pixel_type().size(),
+            start,
+            strides,
+            sizes,
+        )
+    }
+}
+
+/// First part of `parts` with its sizes, after checking that all parts hold the same pixels
+fn check_parts<'p>(parts: &[&'p Nrrd]) -> Result<(&'p Nrrd, Vec<usize>), AxisErr> {
+    let first = *parts.first().ok_or(AxisErr::NothingToJoin)?;
+
+    for part in parts {
+        if part.pixel_type() != first.pixel_type() {
+            return Err(AxisErr::PixelTypesDoNotMatch);
+        }
+        if part.endian() != first.endian() && first.pixel_type().size() > 1 {
+            return Err(AxisErr::EndianDoesNotMatch);
+        }
+        if part.dimension() != first.dimension() {
+            return Err(AxisErr::DimensionsDoNotMatch);
+        }
+    }
+
+    Ok((first, first.checked_sizes()?))
+}
+
+/// Per-axis fields of a header, with one token per axis
+pub(crate) struct AxisFields {
+    fields: Vec<(&'static str, Vec<String>)>,
+    origin: Option<Vec<f64>>,
+}
+
+impl AxisFields {
+    /// Fields whose number of tokens does not match the dimension are left out
+    pub(crate) fn read(nrrd: &Nrrd) -> Self {
+        let dimension = nrrd.sizes().len();
+        let fields = PER_AXIS_FIELDS
+            .iter()
+            .filter_map(|&identifier| {
+                let tokens = axis_tokens(nrrd.field(identifier)?);
+                (tokens.len() == dimension).then_some((identifier, tokens))
+            })
+            .collect();
+        let origin = nrrd.field("space origin").and_then(parse_vector);
+
+        Self { fields, origin }
+    }
+
+    /// Axis `i` of the result takes its fields from axis `sources[i]`, `None` for a new axis
+    fn remap(&self, sources: &[Option<usize>]) -> Self {
+        let fields = self
+            .fields
+            .iter()
+            .map(|(identifier, tokens)| {
+                let remapped = sources
+                    .iter()
+                    .map(|source| match source {
+                        Some(axis) => tokens[*axis].clone(),
+                        None => unknown_token(identifier).to_string(),
+                    })
+                    .collect();
+                (*identifier, remapped)
+            })
+            .collect();
+
+        Self {
+            fields,
+            origin: self.origin.clone(),
+        }
+    }
+
+    /// Replaces fields of `nrrd`, which must have as many axes as these fields
+    pub(crate) fn apply(self, mut nrrd: Nrrd) -> Nrrd {
+        for identifier in PER_AXIS_FIELDS {
+            nrrd.remove_field(identifier);
+        }
+        for (identifier, tokens) in self.fields {
+            nrrd.set_field(identifier, tokens.join(" "));
+        }
+        if let Some(origin) = self.origin {
+            nrrd.set_field("space origin", format_vector(&origin));
+        }
+
+        nrrd
+    }
+
+    fn tokens(&self, identifier: &str) -> Option<&Vec<String>> {
+        self.fields
+            .iter()
+            .find(|(id, _)| *id == identifier)
+            .map(|(_, tokens)| tokens)
+    }
+
+    fn tokens_mut(&mut self, identifier: &str) -> Option<&mut Vec<String>> {
+        self.fields
+            .iter_mut()
+            .find(|(id, _)| *id == identifier)
+            .map(|(_, tokens)| tokens)
+    }
+
+    fn direction(&self, axis: usize) -> Option<Vec<f64>> {
+        parse_vector(&self.tokens("space directions")?[axis])
+    }
+
+    /// Finite number of a field at `axis`
+    fn number(&self, identifier: &str, axis: usize) -> Option<f64> {
+        let value = self.tokens(identifier)?[axis].parse::<f64>().ok()?;
+        value.is_finite().then_some(value)
+    }
+
+    /// Returns `true` if `centers` or `centerings` puts samples of `axis` on the bounds
+    pub(crate) fn is_node(&self, axis: usize) -> bool {
+        ["centers", "centerings"]
+            .iter()
+            .filter_map(|id| self.tokens(id))
+            .any(|tokens| tokens[axis].eq_ignore_ascii_case("node"))
+    }
+
+    /// Distance between neighbours along `axis` of `size` pixels, from `spacings` or the axis bounds
+    fn spacing(&self, axis: usize, size: usize) -> Option<f64> {
+        self.number("spacings", axis).or_else(|| {
+            let min = self.number("axis mins", axis)?;
+            let max = self.number("axis maxs", axis)?;
+            let intervals = intervals(size, self.is_node(axis));
+            (intervals > 0).then(|| (max - min) / intervals as f64)
+        })
+    }
+
+    /// Position of the first pixel along `axis` of `size` pixels and the distance between
+    /// neighbours, measured along the axis. Axes are assumed orthogonal, unknown values are
+    /// `(0, 1)`.
+    pub(crate) fn placement(&self, axis: usize, size: usize) -> (f64, f64) {
+        if let Some(direction) = self.direction(axis) {
+            let length = direction.iter().map(|d| d * d).sum::<f64>().sqrt();
+            if length > 0.0 {
+                let first = self.origin.as_ref().map_or(0.0, |origin| {
+                    origin
+                        .iter()
+                        .zip(&direction)
+                        .map(|(o, d)| o * d)
+                        .sum::<f64>()
+                        / length
+                });
+                return (first, length);
+            }
+        }
+
+        let step = self.spacing(axis, size).unwrap_or(1.0);
+        let first = match self.number("axis mins", axis) {
+            Some(min) if self.is_node(axis) => min,
+            Some(min) => min + step / 2.0,
+            None => 0.0,
+        };
+        (first, step)
+    }
+
+    /// Moves origin by `steps` pixels along `axis`
+    pub(crate) fn shift_origin(&mut self, axis: usize, steps: f64) {
+        let direction = self.direction(axis);
+        if let (Some(origin), Some(direction)) = (&mut self.origin, direction) {
+            for (o, d) in origin.iter_mut().zip(direction) {
+                *o += steps * d;
+            }
+        }
+    }
+
+    /// Updates fields after `axis` of `old_size` pixels was cut or extended to `new_size` pixels,
+    /// the first of which was at `offset` of the old ones
+    #[inline]
+    pub(crate) fn move_range(
+        &mut self,
+        axis: usize,
+        old_size: usize,
+        offset: isize,
+        new_size: usize,
+    ) {
+        self.resample(axis, old_size, offset as f64, 1.0, new_size);
+    }
+
+    /// Updates fields after `axis` of `old_size` pixels was sampled at `new_size` positions,
+    /// starting at `first` and `step` apart, both in old pixels
+    pub(crate) fn resample(
+        &mut self,
+        axis: usize,
+        old_size: usize,
+        first: f64,
+        step: f64,
+        new_size: usize,
+    ) {
+        if first == 0.0 && step == 1.0 && new_size == old_size {
+            return;
+        }
+
+        let is_node = self.is_node(axis);
+        // Cell centred bounds lie half a pixel before the first sample
+        let start = match is_node {
+            true => first,
+            false => first + 0.5 - step / 2.0,
+        };
+
+        let old_step = self.spacing(axis, old_size);
+        let new_min = self
+            .number("axis mins", axis)
+            .zip(old_step)
+            .map(|(min, old_step)| min + start * old_step);
+        let new_max = new_min
+            .zip(old_step)
+            .map(|(min, old_step)| min + intervals(new_size, is_node) as f64 * step * old_step);
+
+        self.shift_origin(axis, first);
+        if step != 1.0 {
+            self.scale_step(axis, step);
+        }
+
+        if new_size != old_size {
+            self.resized(axis);
+        }
+        let format = |value: Option<f64>| value.map_or("nan".to_string(), |v| v.to_string());
+        if let Some(tokens) = self.tokens_mut("axis mins") {
+            tokens[axis] = format(new_min);
+        }
+        if let Some(tokens) = self.tokens_mut("axis maxs") {
+            tokens[axis] = format(new_max);
+        }
+    }
+
+    /// Updates fields after pixels along `axis` were reversed, `last` is the index of its last pixel
+    fn flip(&mut self, axis: usize, last: usize) {
+        self.shift_origin(axis, last as f64);
+
+        if let Some(direction) = self.direction(axis) {
+            let negated = direction.iter().map(|d| -d).collect::<Vec<_>>();
+            self.tokens_mut("space directions").unwrap()[axis] = format_vector(&negated);
+        }
+
+        let min = self.tokens_mut("axis mins").map(|t| t[axis].clone());
+        let max = self.tokens_mut("axis maxs").map(|t| t[axis].clone());
+        if let Some(tokens) = self.tokens_mut("axis mins") {
+            tokens[axis] = max.unwrap_or_else(|| "nan".to_string());
+        }
+        if let Some(tokens) = self.tokens_mut("axis maxs") {
+            tokens[axis] = min.unwrap_or_else(|| "nan".to_string());
+        }
+    }
+
+    /// Multiplies the distance between neighbours along `axis`
+    fn scale_step(&mut self, axis: usize, factor: f64) {
+        if let Some(tokens) = self.tokens_mut("spacings") {
+            if let Ok(spacing) = tokens[axis].parse::<f64>() {
+                tokens[axis] = (spacing * factor).to_string();
+            }
+        }
+
+        if let Some(direction) = self.direction(axis) {
+            let scaled = direction.iter().map(|d| d * factor).collect::<Vec<_>>();
+            self.tokens_mut("space directions").unwrap()[axis] = format_vector(&scaled);
+        }
+    }
+
+    /// Drops values that no longer hold after the number of pixels along `axis` changed
+    fn resized(&mut self, axis: usize) {
+        if let Some(tokens) = self.tokens_mut("axis maxs") {
+            tokens[axis] = "nan".to_string();
+        }
+
+        if let Some(tokens) = self.tokens_mut("kinds") {
+            let kind = tokens[axis].to_lowercase();
+            if !SIZE_FREE_KINDS.contains(&kind.as_str()) {
+                tokens[axis] = "???".to_string();
+            }
+        }
+    }
+}
+
+/// Number of steps between the ends of an axis of `size` pixels,
+/// node centred samples span one interval less than cell centred ones
+#[inline]
+fn intervals(size: usize, is_node: bool) -> usize {
+    match is_node {
+        true => size.saturating_sub(1),
+        false => size,
+    }
+}
+
+/// Value of a per-axis field for an axis nothing is known about
+fn unknown_token(identifier: &str) -> &'static str {
+    match identifier {
+        "spacings" | "thicknesses" | "axis mins" | "axis maxs" => "nan",
+        "labels" | "units" => "\"\"",
+        "space directions" => "none",
+        _ => "???",
+    }
+}
+
+/// Splits descriptor into per-axis tokens, keeping quoted strings and vectors whole
+fn axis_tokens(descriptor: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut is_quoted = false;
+    let mut is_escaped = false;
+    let mut depth = 0;
+
+    for c in descriptor.chars() {
+        match c {
+            _ if is_escaped => is_escaped = false,
+            '\\' if is_quoted => is_escaped = true,
+            '"' => is_quoted = !is_quoted,
+            '(' if !is_quoted => depth += 1,
+            ')' if !is_quoted => depth -= 1,
+            c if c.is_whitespace() && !is_quoted && depth == 0 => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+                continue;
+            }
+            _ => (),
+        }
+        token.push(c);
+    }
+
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+
+    tokens
+}
+
+/// Parses `(x,y,z)`, returns `None` for `none` and malformed vectors
+fn parse_vector(token: &str) -> Option<Vec<f64>> {
+    let inner = token.trim().strip_prefix('(')?.strip_suffix(')')?;
+    inner.split(',').map(|v| v.trim().parse().ok()).collect()
+}
+
+fn format_vector(vector: &[f64]) -> String {
+    // Adding zero turns `-0` from negated components into `0`
+    let values = vector
+        .iter()
+        .map(|v| (v + 0.0).to_string())
+        .collect::<Vec<_>>();
+    format!("({})", values.join(","))
+}
+
+fn check_axis(axis: usize, dimension: usize) -> Result<(), AxisErr> {
+    match axis < dimension {
+        true => Ok(()),
+        false => Err(AxisErr::AxisOutOfBounds),
+    }
+}
+
+fn check_permutation(axes: &[usize], dimension: usize) -> Result<(), AxisErr> {
+    let mut seen = vec![false; dimension];
+    if axes.len() != dimension {
+        return Err(AxisErr::InvalidPermutation);
+    }
+
+    for &axis in axes {
+        if axis >= dimension || seen[axis] {
+            return Err(AxisErr::InvalidPermutation);
+        }
+        seen[axis] = true;
+    }
+
+    Ok(())
+}
+
+/// Strides in pixels of a contiguous buffer, first axis is the fastest
+pub(crate) fn contiguous_strides(sizes: &[usize]) -> Vec<isize> {
+    let mut stride = 1;
+    sizes
+        .iter()
+        .map(|&size| {
+            let current = stride as isize;
+            stride *= size;
+            current
+        })
+        .collect()
+}
+
+fn sliced_sizes(sizes: &[usize], axis: usize, position: usize) -> Result<Vec<usize>, AxisErr> {
+    check_axis(axis, sizes.len())?;
+    if position >= sizes[axis] {
+        return Err(AxisErr::IndexOutOfBounds);
+    }
+
+    let mut new_sizes = sizes.to_vec();
+    new_sizes.remove(axis);
+    Ok(new_sizes)
+}
+
+fn inserted_sizes(sizes: &[usize], axis: usize) -> Result<Vec<usize>, AxisErr> {
+    check_axis(axis, sizes.len() + 1)?;
+
+    let mut new_sizes = sizes.to_vec();
+    new_sizes.insert(axis, 1);
+    Ok(new_sizes)
+}
+
+fn deleted_sizes(sizes: &[usize], axis: usize) -> Result<Vec<usize>, AxisErr> {
+    check_axis(axis, sizes.len())?;
+    if sizes[axis] != 1 {
+        return Err(AxisErr::AxisNotSingleton);
+    }
+
+    let mut new_sizes = sizes.to_vec();
+    new_sizes.remove(axis);
+    Ok(new_sizes)
+}
+
+fn split_sizes(sizes: &[usize], axis: usize, fast_size: usize) -> Result<Vec<usize>, AxisErr> {
+    check_axis(axis, sizes.len())?;
+    if fast_size == 0 || !sizes[axis].is_multiple_of(fast_size) {
+        return Err(AxisErr::SizesDoNotMatch);
+    }
+
+    let mut new_sizes = sizes.to_vec();
+    new_sizes[axis] = fast_size;
+    new_sizes.insert(axis + 1, sizes[axis] / fast_size);
+    Ok(new_sizes)
+}
+
+fn merged_sizes(sizes: &[usize], axis: usize) -> Result<Vec<usize>, AxisErr> {
+    check_axis(axis + 1, sizes.len())?;
+
+    let mut new_sizes = sizes.to_vec();
+    let slow = new_sizes.remove(axis + 1);
+    new_sizes[axis] *= slow;
+    Ok(new_sizes)
+}
+
+/// Sources of axes after `axis` was removed
+fn without_axis(dimension: usize, axis: usize) -> Vec<Option<usize>> {
+    (0..dimension).filter(|&a| a != axis).map(Some).collect()
+}
+
+/// Sources of axes after a new one was inserted at `axis`
+fn with_new_axis(dimension: usize, axis: usize) -> Vec<Option<usize>> {
+    let mut sources = (0..dimension).map(Some).collect::<Vec<_>>();
+    sources.insert(axis, None);
+    sources
+}
+
+/// Copies pixels of `pixel_size` bytes visited with `strides` from `start`, first axis is the fastest
+fn gather(
+    bytes: &[u8],
+    pixel_size: usize,
+    start: isize,
+    strides: &[isize],
+    sizes: &[usize],
+) -> Vec<u8> {
+    let count = sizes.iter().product::<usize>();
+    let mut gathered = Vec::with_capacity(count * pixel_size);
+    if count == 0 {
+        return gathered;
+    }
+
+    // Pixels along a contiguous first axis are copied at once
+    let run_axes = usize::from(strides.first() == Some(&1));
+    let run = match run_axes {
+        1 => sizes[0],
+        _ => 1,
+    };
+
+    let mut index = vec![0; sizes.len()];
+    for _ in 0..count / run {
+        let mut offset = start;
+        for (&i, &stride) in index.iter().zip(strides).skip(run_axes) {
+            offset += i as isize * stride;
+        }
+
+        let begin = offset as usize * pixel_size;
+        gathered.extend_from_slice(&bytes[begin..begin + run * pixel_size]);
+
+        for (i, &size) in index.iter_mut().zip(sizes).skip(run_axes) {
+            *i += 1;
+            if *i < size {
+                break;
+            }
+            *i = 0;
+        }
+    }
+
+    gathered
+}
+
+/// Interleaves blocks of `parts`: `outer` times one block of `blocks[p]` elements from each part `p`
+fn concat_blocks<T: Clone>(parts: &[&[T]], blocks: &[usize], outer: usize) -> Vec<T> {
+    let mut joined = Vec::with_capacity(blocks.iter().sum::<usize>() * outer);
+
+    for o in 0..outer {
+        for (part, &block) in parts.iter().zip(blocks) {
+            joined.extend_from_slice(&part[o * block..(o + 1) * block]);
+        }
+    }
+
+    joined
+}
+
+/// Sizes as an array of `E` axes
+fn fixed<const E: usize>(sizes: &[usize]) -> Result<[usize; E], AxisErr> {
+    sizes.try_into().map_err(|_| AxisErr::DimensionsDoNotMatch)
+}
+
+impl<T: PixelValue, const D: usize> Image<T, D> {
+    /// Concatenates `parts` along an existing `axis`, other sizes must match
+    pub fn join<S: Storage<T>>(parts: &[&Image<T, D, S>], axis: usize) -> Result<Self, AxisErr> {
+        let first = parts.first().ok_or(AxisErr::NothingToJoin)?;
+        check_axis(axis, D)?;
+
+        let mut sizes = *first.sizes();
+        sizes[axis] = 0;
+        for part in parts {
+            let matches = (0..D).all(|a| a == axis || part.sizes()[a] == first.sizes()[a]);
+            if !matches {
+                return Err(AxisErr::SizesDoNotMatch);
+            }
+            sizes[axis] += part.sizes()[axis];
+        }
+
+        let inner = first.sizes()[..axis].iter().product::<usize>();
+        let outer = first.sizes()[axis + 1..].iter().product();
+        let blocks = parts
+            .iter()
+            .map(|p| inner * p.sizes()[axis])
+            .collect::<Vec<_>>();
+        let pixels = parts.iter().map(|p| p.pixels()).collect::<Vec<_>>();
+
+        Ok(Self::from_parts(
+            concat_blocks(&pixels, &blocks, outer),
+            sizes,
+        ))
+    }
+
+    /// Stacks `parts` of equal sizes along a new axis inserted at `axis`
+    pub fn stack<const E: usize, S: Storage<T>>(
+        parts: &[&Image<T, E, S>],
+        axis: usize,
+    ) -> Result<Self, AxisErr> {
+        let first = parts.first().ok_or(AxisErr::NothingToJoin)?;
+        if parts.iter().any(|p| p.sizes() != first.sizes()) {
+            return Err(AxisErr::SizesDoNotMatch);
+        }
+
+        let mut sizes = inserted_sizes(first.sizes(), axis)?;
+        sizes[axis] = parts.len();
+        let sizes = fixed(&sizes)?;
+
+        let inner = first.sizes()[..axis].iter().product::<usize>();
+        let outer = first.sizes()[axis..].iter().product();
+        let pixels = parts.iter().map(|p| p.pixels()).collect::<Vec<_>>();
+
+        Ok(Self::from_parts(
+            concat_blocks(&pixels, &vec![inner; parts.len()], outer),
+            sizes,
+        ))
+    }
+}
+
+impl<T: PixelValue, const D: usize, S: Storage<T>> Image<T, D, S> {
+    /// Axis `i` of the result is axis `axes[i]` of this image
+    pub fn permute_axes(&self, axes: [usize; D]) -> Result<Image<T, D>, AxisErr> {
+        check_permutation(&axes, D)?;
+        Ok(self.view().permute(axes).to_image())
+    }
+
+    /// Reverses the order of pixels along `axis`
+    pub fn flip(&self, axis: usize) -> Result<Image<T, D>, AxisErr> {
+        check_axis(axis, D)?;
+        Ok(self.view().flip(axis).to_image())
+    }
+
+    /// Pixels at `position` along `axis`, with that axis removed
+    pub fn slice<const E: usize>(
+        &self,
+        axis: usize,
+        position: usize,
+    ) -> Result<Image<T, E>, AxisErr> {
+        let sizes = fixed(&sliced_sizes(self.sizes(), axis, position)?)?;
+
+        let mut origin = [0; D];
+        origin[axis] = position;
+        let mut region = *self.sizes();
+        region[axis] = 1;
+        let view = self.view().region(origin, region).expect("region fits");
+
+        Ok(Image::from_parts(view.iter().cloned().collect(), sizes))
+    }
+
+    /// Splits `axis` into a fast axis of `fast_size` pixels followed by a slow one
+    pub fn split_axis<const E: usize>(
+        self,
+        axis: usize,
+        fast_size: usize,
+    ) -> Result<Image<T, E, S>, AxisErr> {
+        let sizes = split_sizes(self.sizes(), axis, fast_size)?;
+        self.with_sizes(&sizes)
+    }
+
+    /// Merges `axis` and the slower one following it into a single axis
+    pub fn merge_axes<const E: usize>(self, axis: usize) -> Result<Image<T, E, S>, AxisErr> {
+        let sizes = merged_sizes(self.sizes(), axis)?;
+        self.with_sizes(&sizes)
+    }
+
+    /// Inserts an axis of size 1 at `axis`
+    pub fn insert_axis<const E: usize>(self, axis: usize) -> Result<Image<T, E, S>, AxisErr> {
+        let sizes = inserted_sizes(self.sizes(), axis)?;
+        self.with_sizes(&sizes)
+    }
+
+    /// Removes `axis`, which must have size 1
+    pub fn delete_axis<const E: usize>(self, axis: usize) -> Result<Image<T, E, S>, AxisErr> {
+        let sizes = deleted_sizes(self.sizes(), axis)?;
+        self.with_sizes(&sizes)
+    }
+
+    /// Same pixels in the same order with other sizes
+    pub fn reshape<const E: usize>(self, sizes: [usize; E]) -> Result<Image<T, E, S>, AxisErr> {
+        if sizes.iter().product::<usize>() != self.pixels_count() {
+            return Err(AxisErr::SizesDoNotMatch);
+        }
+        self.with_sizes(&sizes)
+    }
+
+    /// Keeps pixels where they are, `sizes` must hold as many pixels
+    fn with_sizes<const E: usize>(self, sizes: &[usize]) -> Result<Image<T, E, S>, AxisErr> {
+        let sizes = fixed(sizes)?;
+        Ok(Image::from_storage(self.into_storage(), sizes).expect("pixels count is unchanged"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AxisErr;
+    use crate::{image::Image, nrrd::Nrrd};
+
+    fn image() -> Image<u16, 3> {
+        Image::from_fn([2, 3, 4], |[x, y, z]| (x + 10 * y + 100 * z) as u16)
+    }
+
+    fn nrrd() -> Nrrd {
+        let mut nrrd = Nrrd::from(&image());
+        nrrd.set_field("labels", "\"x\" \"y\" \"z\"".to_string());
+        nrrd.set_field("kinds", "domain domain 4-vector".to_string());
+        nrrd.set_field("axis mins", "0 0 0".to_string());
+        nrrd.set_field("axis maxs", "2 3 4".to_string());
+        nrrd.set_field("space origin", "(10,20,30)".to_string());
+        nrrd.set_field("space directions", "(2,0,0) (0,3,0) (0,0,4)".to_string());
+        nrrd
+    }
+
+    fn pixels<const D: usize>(nrrd: &Nrrd) -> Vec<u16> {
+        Image::<u16, D>::try_from(nrrd).unwrap().into_vec()
+    }
+
+    #[test]
+    fn permute_moves_axis_fields() {
+        let permuted = nrrd().permute_axes(&[2, 0, 1]).unwrap();
+        assert_eq!(permuted.sizes(), &[4, 2, 3]);
+        assert_eq!(permuted.field("labels"), Some("\"z\" \"x\" \"y\""));
+        assert_eq!(permuted.field("kinds"), Some("4-vector domain domain"));
+        assert_eq!(
+            permuted.field("space directions"),
+            Some("(0,0,4) (2,0,0) (0,3,0)")
+        );
+        assert_eq!(permuted.field("space origin"), Some("(10,20,30)"));
+
+        let expected = image().permute_axes([2, 0, 1]).unwrap();
+        assert_eq!(pixels::<3>(&permuted), expected.pixels());
+        assert_eq!(expected[[3, 1, 2]], 321);
+
+        assert!(matches!(
+            nrrd().permute_axes(&[0, 0, 1]),
+            Err(AxisErr::InvalidPermutation)
+        ));
+        assert!(matches!(
+            image().permute_axes([0, 1, 3]),
+            Err(AxisErr::InvalidPermutation)
+        ));
+    }
+
+    #[test]
+    fn flip_keeps_pixels_in_place() {
+        let flipped = nrrd().flip(0).unwrap();
+        assert_eq!(flipped.field("space origin"), Some("(12,20,30)"));
+        assert_eq!(
+            flipped.field("space directions"),
+            Some("(-2,0,0) (0,3,0) (0,0,4)")
+        );
+        assert_eq!(flipped.field("axis mins"), Some("2 0 0"));
+        assert_eq!(flipped.field("axis maxs"), Some("0 3 4"));
+
+        let expected = image().flip(0).unwrap();
+        assert_eq!(pixels::<3>(&flipped), expected.pixels());
+        assert_eq!(expected[[0, 2, 3]], 321);
+        assert!(matches!(nrrd().flip(3), Err(AxisErr::AxisOutOfBounds)));
+    }
+
+    #[test]
+    fn slice_moves_origin_to_the_position() {
+        let sliced = nrrd().slice(1, 2).unwrap();
+        assert_eq!(sliced.sizes(), &[2, 4]);
+        assert_eq!(sliced.field("space origin"), Some("(10,26,30)"));
+        assert_eq!(sliced.field("space directions"), Some("(2,0,0) (0,0,4)"));
+        assert_eq!(sliced.field("labels"), Some("\"x\" \"z\""));
+
+        let expected = image().slice::<2>(1, 2).unwrap();
+        assert_eq!(pixels::<2>(&sliced), expected.pixels());
+        assert_eq!(expected[[1, 3]], 321);
+
+        assert!(matches!(nrrd().slice(1, 3), Err(AxisErr::IndexOutOfBounds)));
+        let line = Nrrd::from(&Image::<u8, 1>::new(0, [3]));
+        assert!(matches!(
+            line.slice(0, 0),
+            Err(AxisErr::DimensionsDoNotMatch)
+        ));
+        assert!(matches!(
+            image().slice::<3>(0, 0),
+            Err(AxisErr::DimensionsDoNotMatch)
+        ));
+    }
+
+    #[test]
+    fn join_and_stack_combine_parts() {
+        let (a, b) = (nrrd(), nrrd().flip(2).unwrap());
+
+        let joined = Nrrd::join(&[&a, &b], 2).unwrap();
+        assert_eq!(joined.sizes(), &[2, 3, 8]);
+        assert_eq!(joined.field("axis maxs"), Some("2 3 nan"));
+        assert_eq!(joined.field("kinds"), Some("domain domain ???"));
+        let image_b = image().flip(2).unwrap();
+        let expected = Image::join(&[&image(), &image_b], 2).unwrap();
+        assert_eq!(pixels::<3>(&joined), expected.pixels());
+        assert_eq!(expected[[1, 2, 4]], 321);
+
+        let stacked = Nrrd::stack(&[&a, &b], 1).unwrap();
+        assert_eq!(stacked.sizes(), &[2, 2, 3, 4]);
+        assert_eq!(stacked.field("labels"), Some("\"x\" \"\" \"y\" \"z\""));
+        assert_eq!(
+            stacked.field("space directions"),
+            Some("(2,0,0) none (0,3,0) (0,0,4)")
+        );
+        assert_eq!(stacked.field("axis mins"), Some("0 nan 0 0"));
+        let expected = Image::<u16, 4>::stack(&[&image(), &image_b], 1).unwrap();
+        assert_eq!(pixels::<4>(&stacked), expected.pixels());
+        assert_eq!(expected[[1, 1, 2, 0]], 321);
+
+        let other = Nrrd::from(&Image::<u16, 3>::new(0, [2, 2, 4]));
+        assert!(matches!(
+            Nrrd::join(&[&a, &other], 2),
+            Err(AxisErr::SizesDoNotMatch)
+        ));
+        assert!(matches!(
+            Nrrd::stack(&[&a, &other], 0),
+            Err(AxisErr::SizesDoNotMatch)
+        ));
+        assert!(matches!(Nrrd::join(&[], 0), Err(AxisErr::NothingToJoin)));
+        let bytes = Nrrd::from(&Image::<u8, 3>::new(0, [2, 3, 4]));
+        assert!(matches!(
+            Nrrd::join(&[&a, &bytes], 2),
+            Err(AxisErr::PixelTypesDoNotMatch)
+        ));
+    }
+
+    #[test]
+    fn split_and_merge_are_inverse() {
+        let split = nrrd().split_axis(2, 2).unwrap();
+        assert_eq!(split.sizes(), &[2, 3, 2, 2]);
+        assert_eq!(split.field("labels"), Some("\"x\" \"y\" \"z\" \"z\""));
+        assert_eq!(split.field("kinds"), Some("domain domain ??? ???"));
+        assert_eq!(
+            split.field("space directions"),
+            Some("(2,0,0) (0,3,0) (0,0,4) (0,0,8)")
+        );
+        assert_eq!(split.buffer(), nrrd().buffer());
+
+        let merged = split.merge_axes(2).unwrap();
+        assert_eq!(merged.sizes(), &[2, 3, 4]);
+        assert_eq!(merged.buffer(), nrrd().buffer());
+
+        assert!(matches!(
+            nrrd().split_axis(2, 3),
+            Err(AxisErr::SizesDoNotMatch)
+        ));
+        assert!(matches!(
+            nrrd().merge_axes(2),
+            Err(AxisErr::AxisOutOfBounds)
+        ));
+
+        let split = image().split_axis::<4>(2, 2).unwrap();
+        assert_eq!(split[[1, 2, 1, 1]], 321);
+        let merged = split.merge_axes::<3>(0).unwrap();
+        assert_eq!(merged.sizes(), &[6, 2, 2]);
+        assert!(matches!(
+            image().split_axis::<3>(2, 2),
+            Err(AxisErr::DimensionsDoNotMatch)
+        ));
+    }
+
+    #[test]
+    fn singleton_axes_and_reshape() {
+        let inserted = nrrd().insert_axis(3).unwrap();
+        assert_eq!(inserted.sizes(), &[2, 3, 4, 1]);
+        assert_eq!(inserted.field("labels"), Some("\"x\" \"y\" \"z\" \"\""));
+        let deleted = inserted.delete_axis(3).unwrap();
+        assert_eq!(deleted.sizes(), &[2, 3, 4]);
+        assert_eq!(deleted.field("labels"), nrrd().field("labels"));
+        assert!(matches!(
+            nrrd().delete_axis(0),
+            Err(AxisErr::AxisNotSingleton)
+        ));
+
+        let reshaped = nrrd().reshape(&[6, 4]).unwrap();
+        assert_eq!(reshaped.field("labels"), Some("\"\" \"\""));
+        assert_eq!(reshaped.field("space directions"), Some("none none"));
+        assert_eq!(reshaped.buffer(), nrrd().buffer());
+        assert!(matches!(
+            nrrd().reshape(&[5, 5]),
+            Err(AxisErr::SizesDoNotMatch)
+        ));
+
+        let image = image().insert_axis::<4>(0).unwrap();
+        assert_eq!(image.sizes(), &[1, 2, 3, 4]);
+        let image = image.delete_axis::<3>(0).unwrap().reshape([6, 4]).unwrap();
+        assert_eq!(image[[5, 3]], 321);
+    }
+}
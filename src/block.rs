@@ -0,0 +1,311 @@
+//! Opaque fixed-size records stored with NRRD `block` type.
+//!
+//! Bytes of a block are never swapped, whatever the endian of the file.
+
+use crate::{
+    image::{fixed_sizes, linear_offset, Image, ImageFromNrrdErr},
+    nrrd::{
+        reader::{read_nrrd, ReadNrrdErr},
+        Endian, Nrrd, PixelType,
+    },
+    pixel::{PixelValue, PodPixel},
+    storage::Storage,
+};
+use std::{
+    io::Read,
+    ops::{Deref, DerefMut, Index, IndexMut},
+};
+
+/// Block of `N` bytes, checked against `block size` when reading.
+/// It wraps `[u8; N]` instead of being that array, because `[T; N]` of numeric `T`
+/// already reads `N` components, so `Image<[u8; 4], D>` is a `uchar` image with a component axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct Block<const N: usize>(pub [u8; N]);
+
+impl<const N: usize> Default for Block<N> {
+    #[inline]
+    fn default() -> Self {
+        Self([0; N])
+    }
+}
+
+impl<const N: usize> From<[u8; N]> for Block<N> {
+    #[inline]
+    fn from(bytes: [u8; N]) -> Self {
+        Self(bytes)
+    }
+}
+
+impl<const N: usize> Deref for Block<N> {
+    type Target = [u8; N];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<const N: usize> DerefMut for Block<N> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<const N: usize> PixelValue for Block<N> {
+    #[inline]
+    fn from_bytes(buffer: &[u8], _endian: Endian) -> Self {
+        let mut block = [0; N];
+        block.copy_from_slice(&buffer[..N]);
+        Self(block)
+    }
+
+    #[inline]
+    fn to_bytes(&self, buffer: &mut [u8], _endian: Endian) {
+        buffer[..N].copy_from_slice(&self.0);
+    }
+
+    #[inline]
+    fn pixel_type() -> PixelType {
+        PixelType::Block(N as i32)
+    }
+}
+
+unsafe impl<const N: usize> PodPixel for Block<N> {}
+
+/// Image of blocks whose size is known only at runtime
+#[derive(Debug, Clone)]
+pub struct BlockImage<const D: usize> {
+    bytes: Vec<u8>,
+    block_size: usize,
+    sizes: [usize; D],
+}
+
+impl<const D: usize> BlockImage<D> {
+    /// Creates image filled with zero bytes, panics if `block_size` is 0 or exceeds `i32::MAX`
+    pub fn new(block_size: usize, sizes: [usize; D]) -> Self {
+        assert!(
+            block_size > 0 && block_size <= i32::MAX as usize,
+            "invalid block size"
+        );
+
+        Self {
+            bytes: vec![0; block_size * sizes.iter().product::<usize>()],
+            block_size,
+            sizes,
+        }
+    }
+
+    pub fn try_read_nrrd<TRead: Read>(reader: TRead) -> Result<Self, ImageFromNrrdErr> {
+        let nrrd = read_nrrd(reader)?;
+        Self::try_from(&nrrd)
+    }
+
+    #[inline]
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    #[inline]
+    pub fn pixels_count(&self) -> usize {
+        self.bytes.len() / self.block_size
+    }
+
+    #[inline]
+    pub fn get(&self, index: &[usize; D]) -> &[u8] {
+        let offset = self.offset(index);
+        &self.bytes[offset..offset + self.block_size]
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, index: &[usize; D]) -> &mut [u8] {
+        let offset = self.offset(index);
+        &mut self.bytes[offset..offset + self.block_size]
+    }
+
+    /// All blocks back to back, first axis is the fastest
+    #[inline]
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    #[inline]
+    pub fn sizes(&self) -> &[usize; D] {
+        &self.sizes
+    }
+
+    /// Returns `None` if block size is not `N`
+    pub fn to_image<const N: usize>(&self) -> Option<Image<Block<N>, D>> {
+        if self.block_size != N {
+            return None;
+        }
+
+        let blocks = self
+            .bytes
+            .chunks_exact(N)
+            .map(|b| Block(b.try_into().unwrap()));
+        Some(Image::from_parts(blocks.collect(), self.sizes))
+    }
+
+    #[inline]
+    fn offset(&self, index: &[usize; D]) -> usize {
+        linear_offset(index, &self.sizes) * self.block_size
+    }
+}
+
+impl<const N: usize, const D: usize, S: Storage<Block<N>>> From<&Image<Block<N>, D, S>>
+    for BlockImage<D>
+{
+    fn from(image: &Image<Block<N>, D, S>) -> Self {
+        Self {
+            bytes: image.pixels().iter().flat_map(|block| block.0).collect(),
+            block_size: N,
+            sizes: *image.sizes(),
+        }
+    }
+}
+
+impl<const D: usize> TryFrom<&Nrrd> for BlockImage<D> {
+    type Error = ImageFromNrrdErr;
+
+    fn try_from(nrrd: &Nrrd) -> Result<Self, Self::Error> {
+        let PixelType::Block(block_size) = nrrd.pixel_type() else {
+            return Err(ImageFromNrrdErr::PixelTypesDoNotMatch);
+        };
+
+        let sizes = fixed_sizes(nrrd.sizes())?;
+        let block_size = block_size as usize;
+        let length = block_size * sizes.iter().product::<usize>();
+        let bytes = nrrd
+            .buffer()
+            .get(..length)
+            .ok_or_else(|| ReadNrrdErr::Malformed("Buffer size mismatch".to_string()))?;
+
+        Ok(Self {
+            bytes: bytes.to_vec(),
+            block_size,
+            sizes,
+        })
+    }
+}
+
+impl<const D: usize> From<&BlockImage<D>> for Nrrd {
+    fn from(image: &BlockImage<D>) -> Self {
+        let pixel_type = PixelType::Block(image.block_size as i32);
+        let mut nrrd = Nrrd::raw_header_of(pixel_type, None, &image.sizes);
+        nrrd.set_buffer(image.bytes.clone());
+        nrrd
+    }
+}
+
+impl<const D: usize> Index<&[usize; D]> for BlockImage<D> {
+    type Output = [u8];
+
+    #[inline]
+    fn index(&self, index: &[usize; D]) -> &Self::Output {
+        self.get(index)
+    }
+}
+
+impl<const D: usize> Index<[usize; D]> for BlockImage<D> {
+    type Output = [u8];
+
+    #[inline]
+    fn index(&self, index: [usize; D]) -> &Self::Output {
+        self.get(&index)
+    }
+}
+
+impl<const D: usize> IndexMut<&[usize; D]> for BlockImage<D> {
+    #[inline]
+    fn index_mut(&mut self, index: &[usize; D]) -> &mut Self::Output {
+        self.get_mut(index)
+    }
+}
+
+impl<const D: usize> IndexMut<[usize; D]> for BlockImage<D> {
+    #[inline]
+    fn index_mut(&mut self, index: [usize; D]) -> &mut Self::Output {
+        self.get_mut(&index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Block, BlockImage};
+    use crate::{
+        image::{Image, ImageFromNrrdErr},
+        nrrd::{
+            reader::{read_nrrd, ReadNrrdErr},
+            writer::write_nrrd,
+            Nrrd, PixelType,
+        },
+    };
+
+    fn write(nrrd: &Nrrd) -> Vec<u8> {
+        let mut file = Vec::new();
+        write_nrrd(nrrd, &mut file).unwrap();
+        file
+    }
+
+    fn blocks() -> Image<Block<4>, 2> {
+        Image::from_fn([3, 2], |[x, y]| Block([x as u8, y as u8, 0xab, 0xcd]))
+    }
+
+    #[test]
+    fn blocks_round_trip() {
+        let image = blocks();
+        let nrrd = read_nrrd(write(&Nrrd::from(&image)).as_slice()).unwrap();
+        assert_eq!(nrrd.pixel_type(), PixelType::Block(4));
+        assert_eq!(nrrd.field("block size"), Some("4"));
+
+        let read = Image::<Block<4>, 2>::try_from(&nrrd).unwrap();
+        assert_eq!(read.pixels(), image.pixels());
+
+        let wide = write(&Nrrd::from(&BlockImage::new(8, [3, 2])));
+        let err = Image::<Block<4>, 2>::try_read_nrrd(wide.as_slice()).unwrap_err();
+        assert!(matches!(err, ImageFromNrrdErr::PixelTypesDoNotMatch));
+    }
+
+    #[test]
+    fn block_image_round_trip() {
+        let image = BlockImage::from(&blocks());
+        assert_eq!(image.block_size(), 4);
+        assert_eq!(&image[[2, 1]], &[2, 1, 0xab, 0xcd]);
+
+        let nrrd = read_nrrd(write(&Nrrd::from(&image)).as_slice()).unwrap();
+        let read = BlockImage::<2>::try_from(&nrrd).unwrap();
+        assert_eq!(read.sizes(), image.sizes());
+        assert_eq!(read.bytes(), image.bytes());
+
+        assert_eq!(read.to_image::<4>().unwrap().pixels(), blocks().pixels());
+        assert!(read.to_image::<8>().is_none());
+    }
+
+    #[test]
+    fn block_bytes_are_not_swapped() {
+        let mut file = b"NRRD0005\ntype: block\nblock size: 4\ndimension: 1\nsizes: 2\n\
+            encoding: raw\nendian: big\n\n"
+            .to_vec();
+        file.extend_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+
+        let image = Image::<Block<4>, 1>::try_read_nrrd(file.as_slice()).unwrap();
+        assert_eq!(image.pixels(), &[Block([1, 2, 3, 4]), Block([5, 6, 7, 8])]);
+
+        let image = BlockImage::<1>::try_read_nrrd(file.as_slice()).unwrap();
+        assert_eq!(image.bytes(), &[1, 2, 3, 4, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn short_buffer_is_malformed() {
+        let mut nrrd = Nrrd::raw_header_of(PixelType::Block(4), None, &[3]);
+        nrrd.set_buffer(vec![0; 8]);
+
+        let err = BlockImage::<1>::try_from(&nrrd).unwrap_err();
+        assert!(matches!(
+            err,
+            ImageFromNrrdErr::CannotReadNrrd(ReadNrrdErr::Malformed(_))
+        ));
+    }
+}
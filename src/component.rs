@@ -0,0 +1,385 @@
+//! Pixels made of several scalar components.
+//!
+//! NRRD stores them with an extra fastest axis whose `kinds` entry tells what the components mean.
+//! `Image<Rgb<u8>, 3>` reads a 4D file whose first axis has size 3 and kind `RGB-color`.
+
+use crate::{
+    image::Image,
+    nrrd::{Endian, PixelType},
+    pixel::{NumericPixel, PixelValue, PodPixel},
+    storage::Storage,
+};
+
+#[inline]
+fn read_components<T: NumericPixel, const N: usize>(buffer: &[u8], endian: Endian) -> [T; N] {
+    let size = T::pixel_size();
+    std::array::from_fn(|i| T::from_bytes(&buffer[i * size..], endian))
+}
+
+#[inline]
+fn write_components<T: NumericPixel>(components: &[T], buffer: &mut [u8], endian: Endian) {
+    for (value, bytes) in components
+        .iter()
+        .zip(buffer.chunks_exact_mut(T::pixel_size()))
+    {
+        value.to_bytes(bytes, endian);
+    }
+}
+
+/// Any number of components, folded from an axis of any kind and written as `vector`
+impl<T: NumericPixel, const N: usize> PixelValue for [T; N]
+where
+    [T; N]: Default,
+{
+    #[inline]
+    fn from_bytes(buffer: &[u8], endian: Endian) -> Self {
+        read_components(buffer, endian)
+    }
+
+    #[inline]
+    fn to_bytes(&self, buffer: &mut [u8], endian: Endian) {
+        write_components(self, buffer, endian);
+    }
+
+    #[inline]
+    fn pixel_type() -> PixelType {
+        T::pixel_type()
+    }
+
+    #[inline]
+    fn components() -> usize {
+        N
+    }
+
+    #[inline]
+    fn kind() -> Option<&'static str> {
+        Some("vector")
+    }
+
+    #[inline]
+    fn accepts_kind(_kind: &str) -> bool {
+        true
+    }
+}
+
+unsafe impl<T: NumericPixel + PodPixel, const N: usize> PodPixel for [T; N] where [T; N]: Default {}
+
+macro_rules! impl_component_pixel {
+    ($name: ident, $components: literal, [$($field: ident),+], [$($kind: literal),+]) => {
+        impl<T: NumericPixel> PixelValue for $name<T> {
+            fn from_bytes(buffer: &[u8], endian: Endian) -> Self {
+                let [$($field),+] = read_components::<T, $components>(buffer, endian);
+                Self { $($field),+ }
+            }
+
+            fn to_bytes(&self, buffer: &mut [u8], endian: Endian) {
+                write_components(&[$(self.$field),+], buffer, endian);
+            }
+
+            #[inline]
+            fn pixel_type() -> PixelType {
+                T::pixel_type()
+            }
+
+            #[inline]
+            fn components() -> usize {
+                $components
+            }
+
+            #[inline]
+            fn kind() -> Option<&'static str> {
+                [$($kind),+].first().copied()
+            }
+
+            #[inline]
+            fn accepts_kind(kind: &str) -> bool {
+                [$($kind),+].iter().any(|k| k.eq_ignore_ascii_case(kind))
+            }
+        }
+
+        unsafe impl<T: NumericPixel + PodPixel> PodPixel for $name<T> {}
+
+        impl<T: NumericPixel> From<[T; $components]> for $name<T> {
+            #[inline]
+            fn from([$($field),+]: [T; $components]) -> Self {
+                Self { $($field),+ }
+            }
+        }
+
+        impl<T: NumericPixel> From<$name<T>> for [T; $components] {
+            #[inline]
+            fn from(pixel: $name<T>) -> Self {
+                [$(pixel.$field),+]
+            }
+        }
+    };
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Rgb<T> {
+    pub r: T,
+    pub g: T,
+    pub b: T,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Rgba<T> {
+    pub r: T,
+    pub g: T,
+    pub b: T,
+    pub a: T,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Vector3<T> {
+    pub x: T,
+    pub y: T,
+    pub z: T,
+}
+
+/// Symmetric 3x3 matrix, components are stored in the order NRRD uses for `3D-symmetric-matrix`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SymTensor3<T> {
+    pub xx: T,
+    pub xy: T,
+    pub xz: T,
+    pub yy: T,
+    pub yz: T,
+    pub zz: T,
+}
+
+/// Complex number, stored with a 2-component axis of kind `complex`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Complex<T> {
+    pub re: T,
+    pub im: T,
+}
+
+impl_component_pixel!(Rgb, 3, [r, g, b], ["RGB-color", "3-color"]);
+impl_component_pixel!(Rgba, 4, [r, g, b, a], ["RGBA-color", "4-color"]);
+impl_component_pixel!(
+    Vector3,
+    3,
+    [x, y, z],
+    [
+        "3-vector",
+        "3-gradient",
+        "3-normal",
+        "vector",
+        "covariant-vector",
+        "normal"
+    ]
+);
+impl_component_pixel!(
+    SymTensor3,
+    6,
+    [xx, xy, xz, yy, yz, zz],
+    ["3D-symmetric-matrix"]
+);
+impl_component_pixel!(Complex, 2, [re, im], ["complex"]);
+
+impl<T: NumericPixel, const D: usize, S: Storage<Complex<T>>> Image<Complex<T>, D, S> {
+    /// Real parts as a scalar image
+    pub fn real(&self) -> Image<T, D> {
+        self.map(|c| c.re)
+    }
+
+    /// Imaginary parts as a scalar image
+    pub fn imag(&self) -> Image<T, D> {
+        self.map(|c| c.im)
+    }
+}
+
+macro_rules! impl_complex_float {
+    ($type: ty) => {
+        impl Complex<$type> {
+            #[inline]
+            pub fn norm(&self) -> $type {
+                self.re.hypot(self.im)
+            }
+
+            /// Angle in radians in `[-pi, pi]`
+            #[inline]
+            pub fn arg(&self) -> $type {
+                self.im.atan2(self.re)
+            }
+        }
+
+        impl<const D: usize, S: Storage<Complex<$type>>> Image<Complex<$type>, D, S> {
+            /// Magnitudes as a scalar image
+            pub fn magnitude(&self) -> Image<$type, D> {
+                self.map(Complex::<$type>::norm)
+            }
+
+            /// Phases in radians as a scalar image
+            pub fn phase(&self) -> Image<$type, D> {
+                self.map(Complex::<$type>::arg)
+            }
+        }
+    };
+}
+
+impl_complex_float!(f32);
+impl_complex_float!(f64);
+
+#[cfg(test)]
+mod tests {
+    use super::{Complex, Rgb, Rgba, SymTensor3, Vector3};
+    use crate::{
+        image::{Image, ImageFromNrrdErr},
+        nrrd::{reader::read_nrrd, writer::write_nrrd, Nrrd},
+    };
+
+    fn write(nrrd: &Nrrd) -> Vec<u8> {
+        let mut file = Vec::new();
+        write_nrrd(nrrd, &mut file).unwrap();
+        file
+    }
+
+    #[test]
+    fn reads_components_from_the_kinds_axis() {
+        let mut file = b"NRRD0005\ntype: uint8\ndimension: 3\nsizes: 3 2 1\n\
+            kinds: RGB-color domain domain\nencoding: raw\nendian: little\n\n"
+            .to_vec();
+        file.extend_from_slice(&[1, 2, 3, 4, 5, 6]);
+
+        let image = Image::<Rgb<u8>, 2>::try_read_nrrd(file.as_slice()).unwrap();
+        assert_eq!(image.sizes(), &[2, 1]);
+        assert_eq!(image[[1, 0]], Rgb { r: 4, g: 5, b: 6 });
+
+        let arrays = Image::<[u8; 3], 2>::try_read_nrrd(file.as_slice()).unwrap();
+        assert_eq!(arrays[[0, 0]], [1, 2, 3]);
+
+        let scalars = Image::<u8, 3>::try_read_nrrd(file.as_slice()).unwrap();
+        assert_eq!(scalars[[2, 1, 0]], 6);
+    }
+
+    #[test]
+    fn writes_components_with_their_kind() {
+        let image = Image::<Rgba<u16>, 2>::from_fn([2, 3], |[x, y]| Rgba {
+            r: x as u16,
+            g: y as u16,
+            b: 300,
+            a: u16::MAX,
+        });
+
+        let read = read_nrrd(write(&Nrrd::from(&image)).as_slice()).unwrap();
+        assert_eq!(read.sizes(), &[4, 2, 3]);
+        assert_eq!(read.field("kinds"), Some("RGBA-color domain domain"));
+        let back = Image::<Rgba<u16>, 2>::try_from(&read).unwrap();
+        assert_eq!(back.pixels(), image.pixels());
+
+        let tensors = Image::<SymTensor3<f32>, 1>::from_fn([2], |[i]| SymTensor3 {
+            xx: i as f32,
+            xy: 1.0,
+            xz: 2.0,
+            yy: 3.0,
+            yz: 4.0,
+            zz: 5.0,
+        });
+        let read = read_nrrd(write(&Nrrd::from(&tensors)).as_slice()).unwrap();
+        assert_eq!(read.field("kinds"), Some("3D-symmetric-matrix domain"));
+        assert_eq!(
+            Image::<SymTensor3<f32>, 1>::try_from(&read)
+                .unwrap()
+                .pixels(),
+            tensors.pixels()
+        );
+
+        let arrays = Image::<[i32; 5], 1>::from_fn([3], |[i]| [i as i32; 5]);
+        let read = read_nrrd(write(&Nrrd::from(&arrays)).as_slice()).unwrap();
+        assert_eq!(read.field("kinds"), Some("vector domain"));
+        assert_eq!(
+            Image::<[i32; 5], 1>::try_from(&read).unwrap().pixels(),
+            arrays.pixels()
+        );
+    }
+
+    #[test]
+    fn checks_component_count_and_kind() {
+        let image = Image::<Vector3<f64>, 2>::new(Vector3::default(), [2, 2]);
+        let nrrd = Nrrd::from(&image);
+        assert_eq!(nrrd.field("kinds"), Some("3-vector domain domain"));
+
+        assert!(Image::<[f64; 3], 2>::try_from(&nrrd).is_ok());
+        assert!(matches!(
+            Image::<Rgb<f64>, 2>::try_from(&nrrd),
+            Err(ImageFromNrrdErr::KindDoesNotMatch)
+        ));
+        assert!(matches!(
+            Image::<Rgba<f64>, 2>::try_from(&nrrd),
+            Err(ImageFromNrrdErr::ComponentsDoNotMatch)
+        ));
+        assert!(matches!(
+            Image::<Vector3<f32>, 2>::try_from(&nrrd),
+            Err(ImageFromNrrdErr::PixelTypesDoNotMatch)
+        ));
+
+        let mut file = b"NRRD0005\ntype: float\ndimension: 2\nsizes: 3 1\n\
+            kinds: normal domain\nencoding: raw\nendian: little\n\n"
+            .to_vec();
+        file.extend([1f32, 2.0, 3.0].iter().flat_map(|v| v.to_le_bytes()));
+        let normals = Image::<Vector3<f32>, 1>::try_read_nrrd(file.as_slice()).unwrap();
+        assert_eq!(
+            normals[[0]],
+            Vector3 {
+                x: 1.0,
+                y: 2.0,
+                z: 3.0
+            }
+        );
+    }
+
+    #[test]
+    fn complex_round_trip_and_parts() {
+        let image = Image::<Complex<f64>, 2>::from_fn([2, 2], |[x, y]| Complex {
+            re: x as f64 - 1.0,
+            im: y as f64,
+        });
+
+        let read = read_nrrd(write(&Nrrd::from(&image)).as_slice()).unwrap();
+        assert_eq!(read.sizes(), &[2, 2, 2]);
+        assert_eq!(read.field("kinds"), Some("complex domain domain"));
+        let back = Image::<Complex<f64>, 2>::try_from(&read).unwrap();
+        assert_eq!(back.pixels(), image.pixels());
+
+        assert_eq!(image.real().pixels(), &[-1.0, 0.0, -1.0, 0.0]);
+        assert_eq!(image.imag().pixels(), &[0.0, 0.0, 1.0, 1.0]);
+        assert_eq!(image.magnitude()[[0, 1]], 2f64.sqrt());
+        assert_eq!(image.phase()[[0, 0]], std::f64::consts::PI);
+        assert_eq!(image.phase()[[1, 1]], std::f64::consts::FRAC_PI_2);
+
+        let value = Complex { re: 3f32, im: -4.0 };
+        assert_eq!(value.norm(), 5.0);
+        assert_eq!(value.arg(), (-4f32).atan2(3.0));
+        assert_eq!(<[f32; 2]>::from(value), [3.0, -4.0]);
+    }
+
+    #[test]
+    fn complex_requires_two_components() {
+        let mut file = b"NRRD0005\ntype: short\ndimension: 2\nsizes: 2 2\n\
+            kinds: complex domain\nencoding: raw\nendian: big\n\n"
+            .to_vec();
+        file.extend([1i16, -2, 3, -4].iter().flat_map(|v| v.to_be_bytes()));
+
+        let image = Image::<Complex<i16>, 1>::try_read_nrrd(file.as_slice()).unwrap();
+        assert_eq!(image.real().pixels(), &[1, 3]);
+        assert_eq!(image.imag().pixels(), &[-2, -4]);
+
+        let vectors = Image::<[f32; 3], 1>::from_fn([2], |_| [0.0; 3]);
+        assert!(matches!(
+            Image::<Complex<f32>, 1>::try_from(&Nrrd::from(&vectors)),
+            Err(ImageFromNrrdErr::ComponentsDoNotMatch)
+        ));
+        let floats = Image::<[f32; 2], 1>::try_read_nrrd(file.as_slice());
+        assert!(matches!(
+            floats,
+            Err(ImageFromNrrdErr::PixelTypesDoNotMatch)
+        ));
+    }
+}
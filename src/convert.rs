@@ -0,0 +1,470 @@
+use crate::{
+    image::{decode_pixels, Image, ImageFromNrrdErr},
+    nrrd::{reader::read_nrrd, Nrrd, PixelType},
+    pixel::{IntegerPixel, NumericPixel},
+    storage::Storage,
+    view::ImageView,
+};
+use std::io::Read;
+
+/// How values that do not fit the target pixel type are handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConversionPolicy {
+    /// Fails unless every value is represented exactly
+    #[default]
+    Exact,
+    /// Clamps to the target range, fractions are truncated toward zero and NaN becomes 0
+    Saturating,
+    /// Like `Saturating`, but fractions are rounded to the nearest integer
+    Rounding,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConvertOptions {
+    pub policy: ConversionPolicy,
+    /// Maps integer values back to the range in `old min` and `old max` when both are present,
+    /// undoing quantisation the same way `unu unquantize` does
+    pub apply_old_range: bool,
+}
+
+impl<T: NumericPixel, const D: usize> Image<T, D> {
+    /// Reads image of any scalar pixel type, converting values to `T`
+    pub fn read_nrrd_as<TRead: Read>(
+        reader: TRead,
+        options: ConvertOptions,
+    ) -> Result<Self, ImageFromNrrdErr> {
+        let nrrd = read_nrrd(reader)?;
+        nrrd.to_image_converting(options)
+    }
+}
+
+impl Nrrd {
+    /// Converts stored pixels to `T`, fails with `ValueNotRepresentable` if `options.policy` rejects a value
+    pub fn to_image_converting<T: NumericPixel, const D: usize>(
+        &self,
+        options: ConvertOptions,
+    ) -> Result<Image<T, D>, ImageFromNrrdErr> {
+        if self.dimension() as usize != D {
+            return Err(ImageFromNrrdErr::DimensionsDoNotMatch);
+        }
+
+        let mut sizes = [0; D];
+        for (size, &nrrd_size) in sizes.iter_mut().zip(self.sizes()) {
+            *size = nrrd_size as usize;
+        }
+
+        let buffer = match self.pixel_type() {
+            PixelType::Int8 => convert_pixels::<i8, T>(self, &sizes, options),
+            PixelType::UInt8 => convert_pixels::<u8, T>(self, &sizes, options),
+            PixelType::Int16 => convert_pixels::<i16, T>(self, &sizes, options),
+            PixelType::UInt16 => convert_pixels::<u16, T>(self, &sizes, options),
+            PixelType::Int32 => convert_pixels::<i32, T>(self, &sizes, options),
+            PixelType::UInt32 => convert_pixels::<u32, T>(self, &sizes, options),
+            PixelType::Int64 => convert_pixels::<i64, T>(self, &sizes, options),
+            PixelType::UInt64 => convert_pixels::<u64, T>(self, &sizes, options),
+            PixelType::Float32 => convert_pixels::<f32, T>(self, &sizes, options),
+            PixelType::Float64 => convert_pixels::<f64, T>(self, &sizes, options),
+            PixelType::Block(_) => Err(ImageFromNrrdErr::PixelTypesDoNotMatch),
+        }?;
+
+        Ok(Image::from_parts(buffer, sizes))
+    }
+
+    /// `old min` and `old max` fields, if both are present and valid
+    pub(crate) fn old_range(&self) -> Option<(f64, f64)> {
+        let min = self.field("old min")?.trim().parse().ok()?;
+        let max = self.field("old max")?.trim().parse().ok()?;
+        Some((min, max))
+    }
+}
+
+fn convert_pixels<S: NumericPixel, T: NumericPixel>(
+    nrrd: &Nrrd,
+    sizes: &[usize],
+    options: ConvertOptions,
+) -> Result<Vec<T>, ImageFromNrrdErr> {
+    let stored: Vec<S> = decode_pixels(sizes.iter().product(), nrrd.buffer(), nrrd.endian())?;
+    let old_range = nrrd.old_range().filter(|_| options.apply_old_range);
+
+    stored
+        .into_iter()
+        .map(|value| {
+            let converted = match (value.to_i128(), old_range) {
+                (Some(value), Some((min, max))) => {
+                    T::from_f64(unquantize::<S>(value, min, max), options.policy)
+                }
+                _ => convert_value(value, options.policy),
+            };
+
+            converted.ok_or(ImageFromNrrdErr::ValueNotRepresentable)
+        })
+        .collect()
+}
+
+/// Integers are converted without going through `f64`, so 64 bit values stay exact
+#[inline]
+fn convert_value<S: NumericPixel, T: NumericPixel>(
+    value: S,
+    policy: ConversionPolicy,
+) -> Option<T> {
+    match value.to_i128() {
+        Some(value) => T::from_i128(value, policy),
+        None => T::from_f64(value.to_f64(), policy),
+    }
+}
+
+/// Center of the cell that integer `value` of type `S` covers within `[min, max]`
+fn unquantize<S: NumericPixel>(value: i128, min: f64, max: f64) -> f64 {
+    let type_min = S::MIN.to_i128().unwrap_or(0);
+    min + ((value - type_min) as f64 + 0.5) * (max - min) / values_count::<S>()
+}
+
+/// Number of distinct values of type `T`, treating it as an integer type
+#[inline]
+fn values_count<T: NumericPixel>() -> f64 {
+    2f64.powi(8 * T::pixel_type().size() as i32)
+}
+
+/// Input range mapped onto the output type by `Image::quantize`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QuantizeRange {
+    /// Smallest and largest value in the image, ignoring NaN
+    Data,
+    /// Explicit `[min, max]`
+    MinMax(f64, f64),
+    /// Range of `width` centered on `level`
+    Window { level: f64, width: f64 },
+}
+
+/// Quantised or rescaled image together with the range it was mapped from.
+/// The range spans every value of `T`, each one standing for the center of its cell.
+/// Converting it to `Nrrd` records the range in `old min` and `old max`.
+#[derive(Debug, Clone)]
+pub struct QuantizedImage<T: NumericPixel, const D: usize> {
+    pub image: Image<T, D>,
+    /// `None` when values cannot be mapped back, see `rescale_to`
+    pub old_range: Option<(f64, f64)>,
+}
+
+impl<T: NumericPixel, const D: usize> From<&QuantizedImage<T, D>> for Nrrd {
+    fn from(quantized: &QuantizedImage<T, D>) -> Self {
+        let mut nrrd = Nrrd::from(&quantized.image);
+        if let Some((min, max)) = quantized.old_range {
+            nrrd.set_field("old min", min.to_string());
+            nrrd.set_field("old max", max.to_string());
+        }
+        nrrd
+    }
+}
+
+impl<T: NumericPixel, const D: usize, S: Storage<T>> Image<T, D, S> {
+    /// Same as `ImageView::cast`
+    #[inline]
+    pub fn cast<U: NumericPixel>(&self) -> Image<U, D> {
+        self.view().cast()
+    }
+
+    /// Same as `ImageView::cast_with`
+    #[inline]
+    pub fn cast_with<U: NumericPixel>(&self, policy: ConversionPolicy) -> Option<Image<U, D>> {
+        self.view().cast_with(policy)
+    }
+
+    /// Same as `ImageView::rescale_to`
+    #[inline]
+    pub fn rescale_to<U: NumericPixel>(
+        &self,
+        in_range: (f64, f64),
+        out_range: (f64, f64),
+    ) -> QuantizedImage<U, D> {
+        self.view().rescale_to(in_range, out_range)
+    }
+
+    /// Same as `ImageView::quantize`
+    #[inline]
+    pub fn quantize<U: IntegerPixel>(&self, range: QuantizeRange) -> QuantizedImage<U, D> {
+        self.view().quantize(range)
+    }
+}
+
+impl<T: NumericPixel, const D: usize> ImageView<'_, T, D> {
+    /// Converts pixels to `U`, rounding to the nearest value and saturating at the bounds of `U`
+    pub fn cast<U: NumericPixel>(&self) -> Image<U, D> {
+        self.cast_with(ConversionPolicy::Rounding)
+            .expect("rounding conversion always succeeds")
+    }
+
+    /// Converts pixels to `U`, returns `None` if `policy` rejects any of them
+    pub fn cast_with<U: NumericPixel>(&self, policy: ConversionPolicy) -> Option<Image<U, D>> {
+        let buffer = self
+            .iter()
+            .map(|&value| convert_value(value, policy))
+            .collect::<Option<Vec<U>>>()?;
+
+        Some(Image::from_parts(buffer, *self.sizes()))
+    }
+
+    /// Maps `in_range` linearly onto `out_range`, clamping values outside of it,
+    /// and rounds the result to `U`. For integer `U` the old range is the input range that the
+    /// same mapping sends onto every value of `U`, so reading with `apply_old_range` restores
+    /// values up to rounding. There is none for floating point `U` or an empty `in_range`.
+    pub fn rescale_to<U: NumericPixel>(
+        &self,
+        in_range: (f64, f64),
+        out_range: (f64, f64),
+    ) -> QuantizedImage<U, D> {
+        let (in_min, in_max) = in_range;
+        let (out_min, out_max) = out_range;
+        let scale = if in_max != in_min {
+            (out_max - out_min) / (in_max - in_min)
+        } else {
+            0.0
+        };
+        let (low, high) = (out_min.min(out_max), out_min.max(out_max));
+
+        let buffer = self
+            .iter()
+            .map(|&value| {
+                let scaled = out_min + (value.to_f64() - in_min) * scale;
+                U::from_f64(scaled.clamp(low, high), ConversionPolicy::Rounding)
+                    .expect("rounding conversion always succeeds")
+            })
+            .collect();
+
+        // Value `v` of `U` stands for the input that maps onto it before rounding
+        let old_range = match U::MIN.to_i128() {
+            Some(type_min) if scale != 0.0 => {
+                let old_min = in_min + (type_min as f64 - 0.5 - out_min) / scale;
+                Some((old_min, old_min + values_count::<U>() / scale))
+            }
+            _ => None,
+        };
+
+        QuantizedImage {
+            image: Image::from_parts(buffer, *self.sizes()),
+            old_range,
+        }
+    }
+
+    /// Quantises pixels into every value of integer type `U` like `unu quantize`.
+    /// `range` is split into equally sized cells, values outside of it fall into the first or last one.
+    /// NaN falls into the first cell.
+    pub fn quantize<U: IntegerPixel>(&self, range: QuantizeRange) -> QuantizedImage<U, D> {
+        let (min, max) = match range {
+            QuantizeRange::Data => self.value_range(),
+            QuantizeRange::MinMax(min, max) => (min, max),
+            QuantizeRange::Window { level, width } => (level - width / 2.0, level + width / 2.0),
+        };
+
+        let values = values_count::<U>();
+        let type_min = U::MIN.to_f64();
+
+        let buffer = self
+            .iter()
+            .map(|&value| {
+                let cell = if max > min && !value.to_f64().is_nan() {
+                    (values * (value.to_f64() - min) / (max - min)).floor()
+                } else {
+                    0.0
+                };
+                let cell = cell.clamp(0.0, values - 1.0);
+                U::from_f64(type_min + cell, ConversionPolicy::Saturating)
+                    .expect("saturating conversion always succeeds")
+            })
+            .collect();
+
+        QuantizedImage {
+            image: Image::from_parts(buffer, *self.sizes()),
+            old_range: Some((min, max)),
+        }
+    }
+
+    /// Smallest and largest pixel value, ignoring NaN. `(0, 0)` for images without values.
+    fn value_range(&self) -> (f64, f64) {
+        let range = self
+            .iter()
+            .map(|value| value.to_f64())
+            .filter(|value| !value.is_nan())
+            .fold(None, |range: Option<(f64, f64)>, value| match range {
+                Some((min, max)) => Some((min.min(value), max.max(value))),
+                None => Some((value, value)),
+            });
+
+        range.unwrap_or((0.0, 0.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConversionPolicy, ConvertOptions, QuantizeRange};
+    use crate::{
+        image::{Image, ImageFromNrrdErr},
+        nrrd::{writer::write_nrrd, Nrrd},
+        pixel::NumericPixel,
+    };
+
+    fn convert<S: NumericPixel, T: NumericPixel>(
+        pixels: Vec<S>,
+        policy: ConversionPolicy,
+    ) -> Result<Vec<T>, ImageFromNrrdErr> {
+        let sizes = [pixels.len()];
+        let nrrd = Nrrd::from(&Image::from_vec(sizes, pixels).unwrap());
+        let options = ConvertOptions {
+            policy,
+            apply_old_range: false,
+        };
+        let image: Image<T, 1> = nrrd.to_image_converting(options)?;
+        Ok(image.into_vec())
+    }
+
+    #[test]
+    fn integer_overflow_follows_policy() {
+        let err = convert::<u16, u8>(vec![7, 300], ConversionPolicy::Exact).unwrap_err();
+        assert!(matches!(err, ImageFromNrrdErr::ValueNotRepresentable));
+        assert_eq!(
+            convert::<u16, u8>(vec![7, 300], ConversionPolicy::Saturating).unwrap(),
+            [7, 255]
+        );
+        assert_eq!(
+            convert::<i16, u8>(vec![-5, 5], ConversionPolicy::Rounding).unwrap(),
+            [0, 5]
+        );
+        assert_eq!(
+            convert::<u64, i64>(vec![u64::MAX], ConversionPolicy::Saturating).unwrap(),
+            [i64::MAX]
+        );
+    }
+
+    #[test]
+    fn fractions_follow_policy() {
+        let pixels = vec![2.5f32, -1.7, f32::NAN, 1e10];
+        let err = convert::<f32, i16>(pixels.clone(), ConversionPolicy::Exact).unwrap_err();
+        assert!(matches!(err, ImageFromNrrdErr::ValueNotRepresentable));
+        assert_eq!(
+            convert::<f32, i16>(pixels.clone(), ConversionPolicy::Saturating).unwrap(),
+            [2, -1, 0, i16::MAX]
+        );
+        assert_eq!(
+            convert::<f32, i16>(pixels, ConversionPolicy::Rounding).unwrap(),
+            [3, -2, 0, i16::MAX]
+        );
+        assert_eq!(
+            convert::<f64, u8>(vec![4.0, 255.0], ConversionPolicy::Exact).unwrap(),
+            [4, 255]
+        );
+    }
+
+    #[test]
+    fn large_integers_stay_exact() {
+        let beyond_f64 = (1i64 << 53) + 1;
+        let err = convert::<i64, f64>(vec![beyond_f64], ConversionPolicy::Exact).unwrap_err();
+        assert!(matches!(err, ImageFromNrrdErr::ValueNotRepresentable));
+        assert_eq!(
+            convert::<i64, f64>(vec![beyond_f64], ConversionPolicy::Saturating).unwrap(),
+            [(1i64 << 53) as f64]
+        );
+        assert_eq!(
+            convert::<i64, u64>(vec![beyond_f64], ConversionPolicy::Exact).unwrap(),
+            [beyond_f64 as u64]
+        );
+        assert_eq!(
+            convert::<f64, f32>(vec![0.5, 1e300], ConversionPolicy::Saturating).unwrap(),
+            [0.5, f32::MAX]
+        );
+        assert!(convert::<f64, f32>(vec![0.1], ConversionPolicy::Exact).is_err());
+    }
+
+    #[test]
+    fn reads_through_old_range_and_as_other_type() {
+        let image = Image::<u8, 2>::from_fn([2, 2], |[x, y]| (x + 2 * y) as u8);
+        let mut nrrd = Nrrd::from(&image);
+        nrrd.set_field("old min", "0".to_string());
+        nrrd.set_field("old max", "256".to_string());
+        let mut file = Vec::new();
+        write_nrrd(&nrrd, &mut file).unwrap();
+
+        let options = ConvertOptions::default();
+        let plain = Image::<f32, 2>::read_nrrd_as(file.as_slice(), options).unwrap();
+        assert_eq!(plain.pixels(), &[0.0, 1.0, 2.0, 3.0]);
+
+        let options = ConvertOptions {
+            apply_old_range: true,
+            ..options
+        };
+        let unquantized = Image::<f32, 2>::read_nrrd_as(file.as_slice(), options).unwrap();
+        assert_eq!(unquantized.pixels(), &[0.5, 1.5, 2.5, 3.5]);
+
+        let err = Image::<u8, 2>::read_nrrd_as(file.as_slice(), options).unwrap_err();
+        assert!(matches!(err, ImageFromNrrdErr::ValueNotRepresentable));
+        let err = Image::<f32, 3>::read_nrrd_as(file.as_slice(), options).unwrap_err();
+        assert!(matches!(err, ImageFromNrrdErr::DimensionsDoNotMatch));
+    }
+
+    #[test]
+    fn casts_images() {
+        let image = Image::<f64, 1>::from_vec([3], vec![-0.6, 1.5, 70000.0]).unwrap();
+        assert_eq!(image.cast::<u16>().pixels(), &[0, 2, u16::MAX]);
+        assert!(image.cast_with::<u16>(ConversionPolicy::Exact).is_none());
+        assert_eq!(
+            image
+                .cast_with::<i32>(ConversionPolicy::Saturating)
+                .unwrap()
+                .pixels(),
+            &[0, 1, 70000]
+        );
+    }
+
+    #[test]
+    fn converts_views_in_view_order() {
+        let image = Image::<f64, 2>::from_fn([3, 2], |[x, y]| (x + 3 * y) as f64 * 10.0);
+        let view = image.view().flip(0).region([0, 1], [2, 1]).unwrap();
+
+        assert_eq!(view.cast::<u8>().pixels(), &[50, 40]);
+        let quantized = view.quantize::<u8>(QuantizeRange::Data);
+        assert_eq!(quantized.image.pixels(), &[255, 0]);
+        assert_eq!(quantized.old_range, Some((40.0, 50.0)));
+    }
+
+    #[test]
+    fn rescaled_values_read_back_through_old_range() {
+        let image = Image::<f32, 1>::from_fn([80], |[i]| i as f32 * 1.25 - 10.0);
+
+        // Output range covers only part of `u8`, cells are half a unit of input wide
+        let rescaled = image.rescale_to::<u8>((-10.0, 90.0), (0.0, 200.0));
+        let nrrd = Nrrd::from(&rescaled);
+        assert!(nrrd.field("old min").is_some());
+
+        let options = ConvertOptions {
+            policy: ConversionPolicy::Exact,
+            apply_old_range: true,
+        };
+        let read: Image<f64, 1> = nrrd.to_image_converting(options).unwrap();
+        for (&original, &value) in image.pixels().iter().zip(read.pixels()) {
+            assert!(
+                (original as f64 - value).abs() <= 0.25,
+                "{original} read as {value}"
+            );
+        }
+
+        let reversed = image.rescale_to::<i16>((-10.0, 90.0), (1000.0, -1000.0));
+        let read: Image<f64, 1> = Nrrd::from(&reversed).to_image_converting(options).unwrap();
+        for (&original, &value) in image.pixels().iter().zip(read.pixels()) {
+            assert!(
+                (original as f64 - value).abs() <= 0.025,
+                "{original} read as {value}"
+            );
+        }
+    }
+
+    #[test]
+    fn rescaling_without_cells_records_no_range() {
+        let image = Image::<u8, 1>::from_fn([4], |[i]| i as u8);
+
+        let floats = image.rescale_to::<f32>((0.0, 3.0), (0.0, 1.0));
+        assert_eq!(floats.old_range, None);
+        assert!(Nrrd::from(&floats).field("old min").is_none());
+
+        let flat = image.rescale_to::<u8>((2.0, 2.0), (0.0, 255.0));
+        assert_eq!(flat.old_range, None);
+    }
+}
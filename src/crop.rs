@@ -0,0 +1,512 @@
+//! Cropping and padding.
+//!
+//! `Nrrd` operations move `space origin` and `axis mins` along with the first pixel, and set
+//! `axis maxs` for the new sizes, so that kept pixels stay where they were in space.
+//! `axis maxs` is written as `nan` when the step along the axis is unknown.
+//! `Image` carries no geometry, callers place the result from the origin or lower padding they pass.
+
+use crate::{
+    axes::{contiguous_strides, AxisErr, AxisFields},
+    image::Image,
+    neighbourhood::Boundary,
+    nrrd::Nrrd,
+    pixel::PixelValue,
+    storage::Storage,
+    view::ImageView,
+};
+
+impl Nrrd {
+    /// Region of `sizes` pixels starting at `origin`, fails with `SizesDoNotMatch` if a size is zero
+    pub fn crop(&self, origin: &[usize], sizes: &[usize]) -> Result<Nrrd, AxisErr> {
+        let old_sizes = self.checked_sizes()?;
+        check_region(&old_sizes, origin, sizes)?;
+        if sizes.contains(&0) {
+            return Err(AxisErr::SizesDoNotMatch);
+        }
+
+        let strides = contiguous_strides(&old_sizes);
+        let start = origin
+            .iter()
+            .zip(&strides)
+            .map(|(&o, &stride)| o as isize * stride)
+            .sum();
+        let buffer = self.gather(start, &strides, sizes);
+
+        let mut fields = AxisFields::read(self);
+        for (axis, &size) in sizes.iter().enumerate() {
+            fields.move_range(axis, old_sizes[axis], origin[axis] as isize, size);
+        }
+
+        Ok(fields.apply(self.with_axes(sizes, buffer)))
+    }
+
+    /// Adds `lower` pixels before and `upper` pixels after the existing ones along each axis.
+    /// New pixels are read according to `fill`, whose constant is one pixel as stored in the buffer.
+    pub fn pad(
+        &self,
+        lower: &[usize],
+        upper: &[usize],
+        fill: &Boundary<&[u8]>,
+    ) -> Result<Nrrd, AxisErr> {
+        let old_sizes = self.checked_sizes()?;
+        if lower.len() != old_sizes.len() || upper.len() != old_sizes.len() {
+            return Err(AxisErr::DimensionsDoNotMatch);
+        }
+
+        let pixel_size = self.pixel_type().size();
+        if let Boundary::Constant(value) = fill {
+            if value.len() != pixel_size {
+                return Err(AxisErr::PixelTypesDoNotMatch);
+            }
+        }
+        check_fill(&old_sizes, fill)?;
+
+        let sizes = padded_sizes(&old_sizes, lower, upper);
+        let strides = contiguous_strides(&old_sizes);
+        let count = sizes.iter().product::<usize>();
+        let mut buffer = Vec::with_capacity(count * pixel_size);
+
+        let mut index = vec![0; sizes.len()];
+        for _ in 0..count {
+            let mut offset = 0;
+            let mut constant = None;
+            for axis in 0..sizes.len() {
+                let i = index[axis] as isize - lower[axis] as isize;
+                match fill.resolve(i, old_sizes[axis]) {
+                    Ok(i) => offset += i * strides[axis] as usize,
+                    Err(value) => {
+                        constant = Some(*value);
+                        break;
+                    }
+                }
+            }
+
+            match constant {
+                Some(value) => buffer.extend_from_slice(value),
+                None => {
+                    let start = offset * pixel_size;
+                    buffer.extend_from_slice(&self.buffer()[start..start + pixel_size]);
+                }
+            }
+
+            advance(&mut index, &sizes);
+        }
+
+        let mut fields = AxisFields::read(self);
+        for (axis, &size) in sizes.iter().enumerate() {
+            fields.move_range(axis, old_sizes[axis], -(lower[axis] as isize), size);
+        }
+
+        Ok(fields.apply(self.with_axes(&sizes, buffer)))
+    }
+
+    /// Crops to the pixels for which `is_background` is false, with `margin` more pixels
+    /// around them as far as the image reaches. Pixels are passed as stored in the buffer.
+    pub fn autocrop(
+        &self,
+        is_background: impl Fn(&[u8]) -> bool,
+        margin: usize,
+    ) -> Result<Nrrd, AxisErr> {
+        let sizes = self.checked_sizes()?;
+        let pixels = self
+            .buffer()
+            .chunks_exact(self.pixel_type().size())
+            .map(|pixel| !is_background(pixel));
+        let (origin, region) = bounding_box(&sizes, pixels).ok_or(AxisErr::AllBackground)?;
+        let (origin, region) = with_margin(&sizes, &origin, &region, margin);
+
+        self.crop(&origin, &region)
+    }
+}
+
+impl<T: PixelValue, const D: usize> ImageView<'_, T, D> {
+    /// Region of `sizes` pixels starting at `origin`, fails with `SizesDoNotMatch` if a size is zero
+    pub fn crop(&self, origin: [usize; D], sizes: [usize; D]) -> Result<Image<T, D>, AxisErr> {
+        if sizes.contains(&0) {
+            return Err(AxisErr::SizesDoNotMatch);
+        }
+        let region = self
+            .region(origin, sizes)
+            .ok_or(AxisErr::IndexOutOfBounds)?;
+        Ok(region.to_image())
+    }
+
+    /// Adds `lower` pixels before and `upper` pixels after the existing ones along each axis.
+    /// New pixels are read according to `fill`.
+    pub fn pad(
+        &self,
+        lower: [usize; D],
+        upper: [usize; D],
+        fill: &Boundary<T>,
+    ) -> Result<Image<T, D>, AxisErr> {
+        check_fill(self.sizes(), fill)?;
+        let sizes = padded_sizes(self.sizes(), &lower, &upper)
+            .try_into()
+            .expect("same dimension");
+
+        Ok(Image::from_fn(sizes, |index| {
+            let mut shifted = [0; D];
+            for ((s, &i), &l) in shifted.iter_mut().zip(&index).zip(&lower) {
+                *s = i as isize - l as isize;
+            }
+            self.get_with_boundary(&shifted, fill).clone()
+        }))
+    }
+
+    /// Origin and sizes of the smallest region holding every pixel for which `is_background` is false,
+    /// `None` if there is no such pixel
+    pub fn bounding_box(
+        &self,
+        is_background: impl Fn(&T) -> bool,
+    ) -> Option<([usize; D], [usize; D])> {
+        let pixels = self.iter().map(|pixel| !is_background(pixel));
+        let (origin, sizes) = bounding_box(self.sizes(), pixels)?;
+        Some((
+            origin.try_into().expect("same dimension"),
+            sizes.try_into().expect("same dimension"),
+        ))
+    }
+
+    /// Crops to the pixels for which `is_background` is false, with `margin` more pixels
+    /// around them as far as the image reaches. Returns the cropped image and where it starts.
+    pub fn autocrop(
+        &self,
+        is_background: impl Fn(&T) -> bool,
+        margin: usize,
+    ) -> Result<(Image<T, D>, [usize; D]), AxisErr> {
+        let (origin, sizes) = self
+            .bounding_box(is_background)
+            .ok_or(AxisErr::AllBackground)?;
+        let (origin, sizes) = with_margin(self.sizes(), &origin, &sizes, margin);
+        let origin = origin.try_into().expect("same dimension");
+
+        let cropped = self.crop(origin, sizes.try_into().expect("same dimension"))?;
+        Ok((cropped, origin))
+    }
+}
+
+impl<T: PixelValue, const D: usize, S: Storage<T>> Image<T, D, S> {
+    /// Same as `ImageView::crop`
+    #[inline]
+    pub fn crop(&self, origin: [usize; D], sizes: [usize; D]) -> Result<Image<T, D>, AxisErr> {
+        self.view().crop(origin, sizes)
+    }
+
+    /// Same as `ImageView::pad`
+    #[inline]
+    pub fn pad(
+        &self,
+        lower: [usize; D],
+        upper: [usize; D],
+        fill: &Boundary<T>,
+    ) -> Result<Image<T, D>, AxisErr> {
+        self.view().pad(lower, upper, fill)
+    }
+
+    /// Same as `ImageView::bounding_box`
+    #[inline]
+    pub fn bounding_box(
+        &self,
+        is_background: impl Fn(&T) -> bool,
+    ) -> Option<([usize; D], [usize; D])> {
+        self.view().bounding_box(is_background)
+    }
+
+    /// Same as `ImageView::autocrop`
+    #[inline]
+    pub fn autocrop(
+        &self,
+        is_background: impl Fn(&T) -> bool,
+        margin: usize,
+    ) -> Result<(Image<T, D>, [usize; D]), AxisErr> {
+        self.view().autocrop(is_background, margin)
+    }
+}
+
+/// Only a constant can fill pixels next to an axis without any
+fn check_fill<T>(sizes: &[usize], fill: &Boundary<T>) -> Result<(), AxisErr> {
+    match sizes.contains(&0) && !matches!(fill, Boundary::Constant(_)) {
+        true => Err(AxisErr::EmptyAxis),
+        false => Ok(()),
+    }
+}
+
+fn check_region(image_sizes: &[usize], origin: &[usize], sizes: &[usize]) -> Result<(), AxisErr> {
+    if origin.len() != image_sizes.len() || sizes.len() != image_sizes.len() {
+        return Err(AxisErr::DimensionsDoNotMatch);
+    }
+
+    let fits = image_sizes
+        .iter()
+        .zip(origin)
+        .zip(sizes)
+        .all(|((&image_size, &o), &size)| o.checked_add(size).is_some_and(|end| end <= image_size));
+    match fits {
+        true => Ok(()),
+        false => Err(AxisErr::IndexOutOfBounds),
+    }
+}
+
+fn padded_sizes(sizes: &[usize], lower: &[usize], upper: &[usize]) -> Vec<usize> {
+    sizes
+        .iter()
+        .zip(lower)
+        .zip(upper)
+        .map(|((&size, &l), &u)| l + size + u)
+        .collect()
+}
+
+/// Steps `index` to the next pixel, first axis is the fastest
+#[inline]
+fn advance(index: &mut [usize], sizes: &[usize]) {
+    for (i, &size) in index.iter_mut().zip(sizes) {
+        *i += 1;
+        if *i < size {
+            return;
+        }
+        *i = 0;
+    }
+}
+
+/// Origin and sizes of the region holding every `true`, pixels are in storage order
+fn bounding_box(
+    sizes: &[usize],
+    is_foreground: impl Iterator<Item = bool>,
+) -> Option<(Vec<usize>, Vec<usize>)> {
+    let mut lowest = vec![usize::MAX; sizes.len()];
+    let mut highest = vec![0; sizes.len()];
+    let mut found = false;
+
+    let mut index = vec![0; sizes.len()];
+    for is_foreground in is_foreground {
+        if is_foreground {
+            found = true;
+            for ((low, high), &i) in lowest.iter_mut().zip(&mut highest).zip(&index) {
+                *low = (*low).min(i);
+                *high = (*high).max(i);
+            }
+        }
+        advance(&mut index, sizes);
+    }
+
+    found.then(|| {
+        let sizes = lowest
+            .iter()
+            .zip(&highest)
+            .map(|(low, high)| high - low + 1)
+            .collect();
+        (lowest, sizes)
+    })
+}
+
+/// Region grown by `margin` pixels on every side, clamped to the image
+fn with_margin(
+    image_sizes: &[usize],
+    origin: &[usize],
+    sizes: &[usize],
+    margin: usize,
+) -> (Vec<usize>, Vec<usize>) {
+    image_sizes
+        .iter()
+        .zip(origin)
+        .zip(sizes)
+        .map(|((&image_size, &o), &size)| {
+            let start = o.saturating_sub(margin);
+            let end = (o + size).saturating_add(margin).min(image_size);
+            (start, end - start)
+        })
+        .unzip()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{axes::AxisErr, component::Rgb, image::Image, neighbourhood::Boundary, nrrd::Nrrd};
+
+    fn image() -> Image<u16, 2> {
+        Image::from_fn([4, 3], |[x, y]| (x + 10 * y) as u16)
+    }
+
+    fn nrrd() -> Nrrd {
+        let mut nrrd = Nrrd::from(&image());
+        nrrd.set_field("axis mins", "0 0".to_string());
+        nrrd.set_field("axis maxs", "4 6".to_string());
+        nrrd.set_field("space origin", "(10,20)".to_string());
+        nrrd.set_field("space directions", "(2,0) (0,3)".to_string());
+        nrrd
+    }
+
+    fn pixels(nrrd: &Nrrd) -> Vec<u16> {
+        Image::<u16, 2>::try_from(nrrd).unwrap().into_vec()
+    }
+
+    /// Pixels with two foreground pixels at `[2, 1]` and `[3, 3]`
+    fn spots() -> Image<u8, 2> {
+        Image::from_fn([6, 5], |index| match index {
+            [2, 1] | [3, 3] => 1,
+            _ => 0,
+        })
+    }
+
+    #[test]
+    fn crop_keeps_component_axis() {
+        let image = Image::<Rgb<u8>, 3>::from_fn([4, 3, 2], |[x, y, z]| Rgb {
+            r: x as u8,
+            g: y as u8,
+            b: z as u8,
+        });
+        let nrrd = Nrrd::from(&image);
+
+        let cropped = nrrd.crop(&[0, 1, 1, 0], &[3, 2, 2, 2]).unwrap();
+        assert_eq!(nrrd.field("kinds"), cropped.field("kinds"));
+
+        let read = Image::<Rgb<u8>, 3>::try_from(&cropped).unwrap();
+        let expected = image.crop([1, 1, 0], [2, 2, 2]).unwrap();
+        assert_eq!(read.pixels(), expected.pixels());
+    }
+
+    #[test]
+    fn crop_moves_origin_and_axis_bounds() {
+        let cropped = nrrd().crop(&[1, 2], &[2, 1]).unwrap();
+        assert_eq!(cropped.sizes(), &[2, 1]);
+        assert_eq!(cropped.field("space origin"), Some("(12,26)"));
+        assert_eq!(cropped.field("space directions"), Some("(2,0) (0,3)"));
+        assert_eq!(cropped.field("axis mins"), Some("1 4"));
+        assert_eq!(cropped.field("axis maxs"), Some("3 6"));
+        assert_eq!(pixels(&cropped), &[21, 22]);
+
+        let expected = image().crop([1, 2], [2, 1]).unwrap();
+        assert_eq!(expected.pixels(), &[21, 22]);
+    }
+
+    #[test]
+    fn crop_rejects_empty_and_outside_regions() {
+        assert!(matches!(
+            nrrd().crop(&[0, 0], &[0, 1]),
+            Err(AxisErr::SizesDoNotMatch)
+        ));
+        assert!(matches!(
+            image().crop([0, 0], [0, 1]),
+            Err(AxisErr::SizesDoNotMatch)
+        ));
+        assert!(matches!(
+            image().view().crop([1, 1], [1, 0]),
+            Err(AxisErr::SizesDoNotMatch)
+        ));
+
+        assert!(matches!(
+            nrrd().crop(&[3, 0], &[2, 1]),
+            Err(AxisErr::IndexOutOfBounds)
+        ));
+        assert!(matches!(
+            image().crop([3, 0], [2, 1]),
+            Err(AxisErr::IndexOutOfBounds)
+        ));
+        assert!(matches!(
+            nrrd().crop(&[0], &[1]),
+            Err(AxisErr::DimensionsDoNotMatch)
+        ));
+    }
+
+    #[test]
+    fn pad_moves_origin_back_and_reads_boundary() {
+        let padded = nrrd()
+            .pad(&[2, 0], &[1, 1], &Boundary::Constant(&[0, 0]))
+            .unwrap();
+        assert_eq!(padded.sizes(), &[7, 4]);
+        assert_eq!(padded.field("space origin"), Some("(6,20)"));
+        assert_eq!(padded.field("axis mins"), Some("-2 0"));
+        assert_eq!(padded.field("axis maxs"), Some("5 8"));
+
+        let read = Image::<u16, 2>::try_from(&padded).unwrap();
+        assert_eq!(read[[2, 0]], 0);
+        assert_eq!(read[[3, 1]], 11);
+        assert_eq!(read[[5, 2]], 23);
+        assert_eq!(read[[1, 1]], 0);
+        assert_eq!(read[[6, 2]], 0);
+        assert_eq!(read[[3, 3]], 0);
+
+        assert!(matches!(
+            nrrd().pad(&[1, 0], &[0, 0], &Boundary::Constant(&[0])),
+            Err(AxisErr::PixelTypesDoNotMatch)
+        ));
+        assert!(matches!(
+            nrrd().pad(&[1], &[0], &Boundary::Clamp),
+            Err(AxisErr::DimensionsDoNotMatch)
+        ));
+    }
+
+    #[test]
+    fn pad_follows_every_boundary() {
+        let line = Image::<u8, 1>::from_vec([3], vec![1, 2, 3]).unwrap();
+        let cases: [(Boundary<u8>, Boundary<&[u8]>, _); 3] = [
+            (Boundary::Clamp, Boundary::Clamp, [1, 1, 1, 2, 3, 3, 3]),
+            (Boundary::Mirror, Boundary::Mirror, [3, 2, 1, 2, 3, 2, 1]),
+            (
+                Boundary::Periodic,
+                Boundary::Periodic,
+                [2, 3, 1, 2, 3, 1, 2],
+            ),
+        ];
+
+        for (boundary, fill, expected) in cases {
+            let padded = line.pad([2], [2], &boundary).unwrap();
+            assert_eq!(padded.pixels(), &expected, "{boundary:?}");
+
+            let padded = Nrrd::from(&line).pad(&[2], &[2], &fill).unwrap();
+            assert_eq!(padded.buffer(), &expected, "{fill:?}");
+        }
+    }
+
+    #[test]
+    fn autocrop_keeps_foreground_with_margin() {
+        let image = spots();
+        assert_eq!(image.bounding_box(|&v| v == 0), Some(([2, 1], [2, 3])));
+
+        let (cropped, origin) = image.autocrop(|&v| v == 0, 0).unwrap();
+        assert_eq!(origin, [2, 1]);
+        assert_eq!(cropped.sizes(), &[2, 3]);
+        assert_eq!(cropped.pixels(), &[1, 0, 0, 0, 0, 1]);
+
+        let (cropped, origin) = image.autocrop(|&v| v == 0, 1).unwrap();
+        assert_eq!(origin, [1, 0]);
+        assert_eq!(cropped.sizes(), &[4, 5]);
+
+        // Margin stops at the edges of the image
+        let (cropped, origin) = image.autocrop(|&v| v == 0, 10).unwrap();
+        assert_eq!(origin, [0, 0]);
+        assert_eq!(cropped.sizes(), image.sizes());
+
+        let empty = Image::<u8, 2>::new(0, [6, 5]);
+        assert_eq!(empty.bounding_box(|&v| v == 0), None);
+        assert!(matches!(
+            empty.autocrop(|&v| v == 0, 1),
+            Err(AxisErr::AllBackground)
+        ));
+    }
+
+    #[test]
+    fn nrrd_autocrop_moves_origin() {
+        let mut nrrd = Nrrd::from(&spots());
+        nrrd.set_field("space origin", "(0,0)".to_string());
+        nrrd.set_field("space directions", "(1,0) (0,2)".to_string());
+
+        let cropped = nrrd.autocrop(|pixel| pixel == [0], 1).unwrap();
+        assert_eq!(cropped.sizes(), &[4, 5]);
+        assert_eq!(cropped.field("space origin"), Some("(1,0)"));
+
+        let empty = Nrrd::from(&Image::<u8, 2>::new(0, [6, 5]));
+        assert!(matches!(
+            empty.autocrop(|pixel| pixel == [0], 1),
+            Err(AxisErr::AllBackground)
+        ));
+    }
+
+    #[test]
+    fn pad_of_empty_axis_needs_constant() {
+        let image = Image::<u8, 2>::new(0, [0, 3]);
+        assert!(image.pad([1, 0], [0, 0], &Boundary::Clamp).is_err());
+
+        let padded = image.pad([1, 0], [0, 0], &Boundary::Constant(7)).unwrap();
+        assert_eq!(padded.pixels(), &[7, 7, 7]);
+    }
+}
@@ -0,0 +1,344 @@
+use crate::{
+    image::{decode_pixels, image_sizes, linear_offset, Image, ImageFromNrrdErr},
+    nrrd::{reader::read_nrrd, Nrrd},
+    pixel::PixelValue,
+    storage::Storage,
+    view::{ImageView, ImageViewMut},
+};
+use std::{
+    io::Read,
+    ops::{Index, IndexMut},
+    slice,
+};
+
+/// Image whose dimension is known only at runtime
+#[derive(Debug, Clone)]
+pub struct DynImage<TPixel: PixelValue> {
+    buffer: Vec<TPixel>,
+    sizes: Vec<usize>,
+}
+
+impl<T: PixelValue> DynImage<T> {
+    pub fn new(background: T, sizes: &[usize]) -> Self {
+        Self {
+            buffer: vec![background; sizes.iter().product()],
+            sizes: sizes.to_vec(),
+        }
+    }
+
+    #[inline]
+    pub fn dimension(&self) -> usize {
+        self.sizes.len()
+    }
+
+    #[inline]
+    pub fn pixels_count(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Panics if index does not have `dimension()` components
+    #[inline]
+    pub fn get(&self, index: &[usize]) -> &T {
+        &self.buffer[self.offset(index)]
+    }
+
+    /// Panics if index does not have `dimension()` components
+    #[inline]
+    pub fn get_mut(&mut self, index: &[usize]) -> &mut T {
+        let offset = self.offset(index);
+        &mut self.buffer[offset]
+    }
+
+    /// Returns `None` if index is out of bounds or does not have `dimension()` components
+    #[inline]
+    pub fn try_get(&self, index: &[usize]) -> Option<&T> {
+        match self.contains(index) {
+            true => Some(self.get(index)),
+            false => None,
+        }
+    }
+
+    /// Returns `None` if index is out of bounds or does not have `dimension()` components
+    #[inline]
+    pub fn try_get_mut(&mut self, index: &[usize]) -> Option<&mut T> {
+        match self.contains(index) {
+            true => Some(self.get_mut(index)),
+            false => None,
+        }
+    }
+
+    /// Iterates over `(index, &pixel)` with the first axis being the fastest
+    #[inline]
+    pub fn indexed_iter(&self) -> DynIndexedIter<'_, slice::Iter<'_, T>> {
+        DynIndexedIter::new(self.buffer.iter(), &self.sizes)
+    }
+
+    /// Iterates over `(index, &mut pixel)` with the first axis being the fastest
+    #[inline]
+    pub fn indexed_iter_mut(&mut self) -> DynIndexedIter<'_, slice::IterMut<'_, T>> {
+        DynIndexedIter::new(self.buffer.iter_mut(), &self.sizes)
+    }
+
+    /// Borrows pixels as a view of dimension `D`, which gives regions, lines and the rest
+    /// of the view API. Returns `None` if `D` is not `dimension()`.
+    pub fn view<const D: usize>(&self) -> Option<ImageView<'_, T, D>> {
+        let sizes = <[usize; D]>::try_from(self.sizes.as_slice()).ok()?;
+        ImageView::new(&self.buffer, sizes)
+    }
+
+    /// Mutable version of `view`
+    pub fn view_mut<const D: usize>(&mut self) -> Option<ImageViewMut<'_, T, D>> {
+        let sizes = <[usize; D]>::try_from(self.sizes.as_slice()).ok()?;
+        ImageViewMut::new(&mut self.buffer, sizes)
+    }
+
+    /// Applies `f` to every pixel
+    pub fn map<U: PixelValue>(&self, f: impl FnMut(&T) -> U) -> DynImage<U> {
+        DynImage {
+            buffer: self.buffer.iter().map(f).collect(),
+            sizes: self.sizes.clone(),
+        }
+    }
+
+    pub fn try_read_nrrd<TRead: Read>(reader: TRead) -> Result<Self, ImageFromNrrdErr> {
+        let nrrd = read_nrrd(reader)?;
+        Self::try_from(&nrrd)
+    }
+
+    #[inline]
+    pub fn pixels(&self) -> &[T] {
+        &self.buffer
+    }
+
+    #[inline]
+    pub fn pixels_mut(&mut self) -> &mut [T] {
+        &mut self.buffer
+    }
+
+    /// Takes pixels out, first axis is the fastest
+    #[inline]
+    pub fn into_vec(self) -> Vec<T> {
+        self.buffer
+    }
+
+    #[inline]
+    pub fn sizes(&self) -> &[usize] {
+        &self.sizes
+    }
+
+    #[inline]
+    fn contains(&self, index: &[usize]) -> bool {
+        index.len() == self.sizes.len() && index.iter().zip(&self.sizes).all(|(&i, &s)| i < s)
+    }
+
+    #[inline]
+    fn offset(&self, index: &[usize]) -> usize {
+        assert_eq!(index.len(), self.sizes.len(), "index dimension mismatch");
+        linear_offset(index, &self.sizes)
+    }
+}
+
+/// Iterator over `(index, pixel)` of a `DynImage`, returned by `indexed_iter` and `indexed_iter_mut`
+pub struct DynIndexedIter<'a, I> {
+    pixels: I,
+    sizes: &'a [usize],
+    index: Vec<usize>,
+}
+
+impl<'a, I> DynIndexedIter<'a, I> {
+    #[inline]
+    fn new(pixels: I, sizes: &'a [usize]) -> Self {
+        Self {
+            pixels,
+            sizes,
+            index: vec![0; sizes.len()],
+        }
+    }
+}
+
+impl<I: Iterator> Iterator for DynIndexedIter<'_, I> {
+    type Item = (Vec<usize>, I::Item);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let pixel = self.pixels.next()?;
+        let index = self.index.clone();
+
+        for (i, &size) in self.index.iter_mut().zip(self.sizes) {
+            *i += 1;
+            if *i < size {
+                break;
+            }
+            *i = 0;
+        }
+
+        Some((index, pixel))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.pixels.size_hint()
+    }
+}
+
+impl<I: ExactSizeIterator> ExactSizeIterator for DynIndexedIter<'_, I> {}
+
+impl<T: PixelValue, const D: usize> From<Image<T, D>> for DynImage<T> {
+    #[inline]
+    fn from(image: Image<T, D>) -> Self {
+        let sizes = image.sizes().to_vec();
+        Self {
+            buffer: image.into_vec(),
+            sizes,
+        }
+    }
+}
+
+impl<T: PixelValue, const D: usize, S: Storage<T>> From<&Image<T, D, S>> for DynImage<T> {
+    #[inline]
+    fn from(image: &Image<T, D, S>) -> Self {
+        Self {
+            buffer: image.pixels().to_vec(),
+            sizes: image.sizes().to_vec(),
+        }
+    }
+}
+
+/// Fails with the original image if dimensions do not match
+impl<T: PixelValue, const D: usize> TryFrom<DynImage<T>> for Image<T, D> {
+    type Error = DynImage<T>;
+
+    fn try_from(image: DynImage<T>) -> Result<Self, Self::Error> {
+        match <[usize; D]>::try_from(image.sizes.as_slice()) {
+            Ok(sizes) => Ok(Image::from_parts(image.buffer, sizes)),
+            Err(_) => Err(image),
+        }
+    }
+}
+
+impl<T: PixelValue> TryFrom<&Nrrd> for DynImage<T> {
+    type Error = ImageFromNrrdErr;
+
+    fn try_from(nrrd: &Nrrd) -> Result<Self, Self::Error> {
+        let sizes = image_sizes::<T>(nrrd.pixel_type(), nrrd.sizes(), nrrd.field("kinds"))?;
+        let sizes: Vec<usize> = sizes.iter().map(|&s| s as usize).collect();
+        let buffer = decode_pixels(sizes.iter().product(), nrrd.buffer(), nrrd.endian())?;
+
+        Ok(Self { buffer, sizes })
+    }
+}
+
+impl<T: PixelValue> From<&DynImage<T>> for Nrrd {
+    #[inline]
+    fn from(image: &DynImage<T>) -> Self {
+        Nrrd::from_pixels(image.pixels().iter(), image.sizes())
+    }
+}
+
+impl<T: PixelValue> Index<&[usize]> for DynImage<T> {
+    type Output = T;
+
+    #[inline]
+    fn index(&self, index: &[usize]) -> &Self::Output {
+        self.get(index)
+    }
+}
+
+impl<T: PixelValue, const D: usize> Index<[usize; D]> for DynImage<T> {
+    type Output = T;
+
+    #[inline]
+    fn index(&self, index: [usize; D]) -> &Self::Output {
+        self.get(&index)
+    }
+}
+
+impl<T: PixelValue> IndexMut<&[usize]> for DynImage<T> {
+    #[inline]
+    fn index_mut(&mut self, index: &[usize]) -> &mut Self::Output {
+        self.get_mut(index)
+    }
+}
+
+impl<T: PixelValue, const D: usize> IndexMut<[usize; D]> for DynImage<T> {
+    #[inline]
+    fn index_mut(&mut self, index: [usize; D]) -> &mut Self::Output {
+        self.get_mut(&index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DynImage;
+    use crate::{component::Rgb, image::Image, nrrd::Nrrd};
+
+    #[test]
+    fn round_trips_with_image_and_nrrd() {
+        let image = Image::<u16, 3>::from_fn([4, 3, 2], |[x, y, z]| (x + 10 * y + 100 * z) as u16);
+
+        let dyn_image = DynImage::from(&image);
+        assert_eq!(dyn_image.dimension(), 3);
+        assert_eq!(dyn_image[[3, 2, 1]], image[[3, 2, 1]]);
+
+        let nrrd = Nrrd::from(&dyn_image);
+        assert_eq!(nrrd.buffer(), Nrrd::from(&image).buffer());
+
+        let read = DynImage::<u16>::try_from(&nrrd).unwrap();
+        assert_eq!(read.sizes(), &[4, 3, 2]);
+
+        let back = Image::<u16, 3>::try_from(read).unwrap();
+        assert_eq!(back.pixels(), image.pixels());
+
+        let wrong = Image::<u16, 2>::try_from(DynImage::from(image)).unwrap_err();
+        assert_eq!(wrong.dimension(), 3);
+    }
+
+    #[test]
+    fn round_trips_component_pixels() {
+        let image = Image::<Rgb<u8>, 2>::from_fn([3, 2], |[x, y]| Rgb {
+            r: x as u8,
+            g: y as u8,
+            b: 9,
+        });
+        let nrrd = Nrrd::from(&image);
+
+        let read = DynImage::<Rgb<u8>>::try_from(&nrrd).unwrap();
+        assert_eq!(read.sizes(), &[3, 2]);
+        assert_eq!(read.pixels(), image.pixels());
+    }
+
+    #[test]
+    fn indexing_and_iteration_match_image() {
+        let image = Image::<u32, 3>::from_fn([3, 2, 2], |[x, y, z]| (x + 3 * y + 6 * z) as u32);
+        let mut dyn_image = DynImage::from(&image);
+
+        let indexed = dyn_image
+            .indexed_iter()
+            .map(|(index, &p)| (index, p))
+            .collect::<Vec<_>>();
+        let expected = image
+            .indexed_iter()
+            .map(|(index, &p)| (index.to_vec(), p))
+            .collect::<Vec<_>>();
+        assert_eq!(indexed, expected);
+
+        for (index, pixel) in dyn_image.indexed_iter_mut() {
+            *pixel += index[2] as u32 * 100;
+        }
+        assert_eq!(dyn_image[[2, 1, 1]], 111);
+
+        assert_eq!(dyn_image.try_get(&[2, 1, 1]), Some(&111));
+        assert_eq!(dyn_image.try_get(&[3, 0, 0]), None);
+        assert_eq!(dyn_image.try_get(&[0, 0]), None);
+        *dyn_image.try_get_mut(&[0, 0, 0]).unwrap() = 7;
+        dyn_image.pixels_mut()[1] = 8;
+        assert_eq!(&dyn_image.pixels()[..2], &[7, 8]);
+
+        let view = dyn_image.view::<3>().unwrap();
+        assert_eq!(view.lines(0).len(), 4);
+        assert!(dyn_image.view::<2>().is_none());
+
+        let doubled = dyn_image.map(|&p| p as u64 * 2);
+        assert_eq!(doubled[[2, 1, 1]], 222);
+        assert_eq!(doubled.into_vec().len(), 12);
+    }
+}
@@ -1,42 +1,78 @@
 use crate::{
     nrrd::{
         reader::{read_nrrd, ReadNrrdErr},
-        Encoding, Nrrd,
+        Encoding, Endian, Nrrd, PixelType,
     },
-    pixel::PixelValue,
+    pixel::{PixelValue, PodPixel},
+    storage::{Storage, StorageMut},
+    view::{ImageView, ImageViewMut},
 };
 use std::{
     io::Read,
+    marker::PhantomData,
+    mem,
     ops::{Index, IndexMut},
 };
 
-pub struct Image<TPixel: PixelValue, const D: usize> {
-    buffer: Vec<TPixel>,
+/// Image with pixels held in `TStorage`, a `Vec` unless chosen otherwise
+#[derive(Debug, Clone)]
+pub struct Image<TPixel: PixelValue, const D: usize, TStorage: Storage<TPixel> = Vec<TPixel>> {
+    buffer: TStorage,
     sizes: [usize; D],
+    pixel: PhantomData<TPixel>,
 }
 
 impl<T: PixelValue, const D: usize> Image<T, D> {
     pub fn new(background: T, sizes: [usize; D]) -> Self {
-        Self {
-            sizes,
-            buffer: vec![background; sizes.iter().product()],
+        Self::from_parts(vec![background; sizes.iter().product()], sizes)
+    }
+
+    /// Image with pixels computed from their indices, first axis is the fastest
+    pub fn from_fn(sizes: [usize; D], mut f: impl FnMut([usize; D]) -> T) -> Self {
+        let count = sizes.iter().product();
+        let mut buffer = Vec::with_capacity(count);
+        let mut index = [0; D];
+
+        for _ in 0..count {
+            buffer.push(f(index));
+
+            for (i, &size) in index.iter_mut().zip(&sizes) {
+                *i += 1;
+                if *i < size {
+                    break;
+                }
+                *i = 0;
+            }
         }
+
+        Self::from_parts(buffer, sizes)
     }
 
+    /// Adopts pixels of `buffer`, returns `None` if their number does not match `sizes`
     #[inline]
-    pub fn pixels_count(&self) -> usize {
-        self.sizes.iter().product()
+    pub fn from_vec(sizes: [usize; D], buffer: Vec<T>) -> Option<Self> {
+        Self::from_storage(buffer, sizes)
     }
 
+    /// Image of the same sizes as `other`, filled with `background`
     #[inline]
-    pub fn get(&self, index: &[usize; D]) -> &T {
-        &self.buffer[self.offset(index)]
+    pub fn new_like<U: PixelValue, S: Storage<U>>(other: &Image<U, D, S>, background: T) -> Self {
+        Self::new(background, other.sizes)
     }
 
+    /// Image of the same sizes as `other`, with pixels computed from their indices
     #[inline]
-    pub fn get_mut(&mut self, index: &[usize; D]) -> &mut T {
-        let offset = self.offset(index);
-        &mut self.buffer[offset]
+    pub fn from_fn_like<U: PixelValue, S: Storage<U>>(
+        other: &Image<U, D, S>,
+        f: impl FnMut([usize; D]) -> T,
+    ) -> Self {
+        Self::from_fn(other.sizes, f)
+    }
+
+    /// Takes pixels out, first axis is the fastest
+    #[inline]
+    pub fn into_vec(self) -> Vec<T> {
+        self.buffer
     }
 
     pub fn try_read_nrrd<TRead: Read>(reader: TRead) -> Result<Self, ImageFromNrrdErr> {
@@ -44,28 +80,281 @@ impl<T: PixelValue, const D: usize> Image<T, D> {
         Self::try_from(&nrrd)
     }
 
+    /// Buffer length must match `sizes`
     #[inline]
-    pub fn pixels(&self) -> &[T] {
+    pub(crate) fn from_parts(buffer: Vec<T>, sizes: [usize; D]) -> Self {
+        debug_assert_eq!(buffer.len(), sizes.iter().product::<usize>());
+        Self {
+            buffer,
+            sizes,
+            pixel: PhantomData,
+        }
+    }
+
+    /// Decodes pixels from raw buffer, which must hold at least `sizes` pixels
+    pub(crate) fn from_bytes(
+        sizes: [usize; D],
+        bytes: &[u8],
+        endian: Endian,
+    ) -> Result<Self, ImageFromNrrdErr> {
+        let buffer = decode_pixels(sizes.iter().product(), bytes, endian)?;
+        Ok(Self::from_parts(buffer, sizes))
+    }
+}
+
+impl<T: PixelValue, const D: usize, S: Storage<T>> Image<T, D, S> {
+    /// Image over pixels held in `storage`, returns `None` if their number does not match `sizes`
+    pub fn from_storage(storage: S, sizes: [usize; D]) -> Option<Self> {
+        if storage.as_slice().len() != sizes.iter().product::<usize>() {
+            return None;
+        }
+
+        Some(Self {
+            buffer: storage,
+            sizes,
+            pixel: PhantomData,
+        })
+    }
+
+    #[inline]
+    pub fn storage(&self) -> &S {
         &self.buffer
     }
 
+    #[inline]
+    pub fn into_storage(self) -> S {
+        self.buffer
+    }
+
+    /// Copies pixels into an image backed by `Vec`
+    pub fn to_owned_image(&self) -> Image<T, D> {
+        Image::from_parts(self.pixels().to_vec(), self.sizes)
+    }
+
+    #[inline]
+    pub fn pixels_count(&self) -> usize {
+        self.sizes.iter().product()
+    }
+
+    #[inline]
+    pub fn get(&self, index: &[usize; D]) -> &T {
+        &self.pixels()[self.offset(index)]
+    }
+
+    /// Returns `None` if index is out of bounds
+    #[inline]
+    pub fn try_get(&self, index: &[usize; D]) -> Option<&T> {
+        match self.contains(index) {
+            true => Some(self.get(index)),
+            false => None,
+        }
+    }
+
+    #[inline]
+    pub fn pixels(&self) -> &[T] {
+        self.buffer.as_slice()
+    }
+
     #[inline]
     pub fn sizes(&self) -> &[usize; D] {
         &self.sizes
     }
 
+    #[inline]
+    pub fn view(&self) -> ImageView<'_, T, D> {
+        ImageView::new(self.pixels(), self.sizes).expect("buffer length matches sizes")
+    }
+
+    /// Applies `f` to every pixel
+    #[inline]
+    pub fn map<U: PixelValue>(&self, f: impl FnMut(&T) -> U) -> Image<U, D> {
+        self.view().map(f)
+    }
+
+    /// Applies `f` to pixels at the same index of both images, panics if sizes differ.
+    /// `other` is an `Image` or a view.
+    #[inline]
+    pub fn zip_map<'b, U: PixelValue + 'b, V: PixelValue>(
+        &self,
+        other: impl Into<ImageView<'b, U, D>>,
+        f: impl FnMut(&T, &U) -> V,
+    ) -> Image<V, D> {
+        self.view().zip_map(other, f)
+    }
+
+    #[inline]
+    fn contains(&self, index: &[usize; D]) -> bool {
+        index.iter().zip(&self.sizes).all(|(&i, &size)| i < size)
+    }
+
     #[inline]
     fn offset(&self, index: &[usize; D]) -> usize {
-        let mut offset = 0;
-        let mut stride = 1;
+        linear_offset(index, &self.sizes)
+    }
+}
+
+impl<T: PixelValue, const D: usize, S: StorageMut<T>> Image<T, D, S> {
+    #[inline]
+    pub fn get_mut(&mut self, index: &[usize; D]) -> &mut T {
+        let offset = self.offset(index);
+        &mut self.pixels_mut()[offset]
+    }
+
+    /// Returns `None` if index is out of bounds
+    #[inline]
+    pub fn try_get_mut(&mut self, index: &[usize; D]) -> Option<&mut T> {
+        match self.contains(index) {
+            true => Some(self.get_mut(index)),
+            false => None,
+        }
+    }
+
+    #[inline]
+    pub fn pixels_mut(&mut self) -> &mut [T] {
+        self.buffer.as_mut_slice()
+    }
+
+    #[inline]
+    pub fn view_mut(&mut self) -> ImageViewMut<'_, T, D> {
+        let sizes = self.sizes;
+        ImageViewMut::new(self.pixels_mut(), sizes).expect("buffer length matches sizes")
+    }
+
+    /// Replaces every pixel with the result of `f`
+    pub fn map_in_place(&mut self, mut f: impl FnMut(&T) -> T) {
+        for pixel in self.pixels_mut() {
+            *pixel = f(pixel);
+        }
+    }
+
+    /// Replaces every pixel with the result of `f` applied to it and the pixel of `other`
+    /// at the same index, panics if sizes differ. `other` is an `Image` or a view.
+    #[inline]
+    pub fn zip_map_in_place<'b, U: PixelValue + 'b>(
+        &mut self,
+        other: impl Into<ImageView<'b, U, D>>,
+        f: impl FnMut(&T, &U) -> T,
+    ) {
+        self.view_mut().zip_map_in_place(other, f);
+    }
+}
+
+/// Sizes of image axes for pixels of type `T`, without the component axis of non-scalar pixels
+pub(crate) fn image_sizes<'s, T: PixelValue>(
+    pixel_type: PixelType,
+    sizes: &'s [i32],
+    kinds: Option<&str>,
+) -> Result<&'s [i32], ImageFromNrrdErr> {
+    if T::pixel_type() != pixel_type {
+        return Err(ImageFromNrrdErr::PixelTypesDoNotMatch);
+    }
+
+    if T::kind().is_none() {
+        return Ok(sizes);
+    }
+
+    match sizes.first() {
+        Some(&size) if size as usize == T::components() => (),
+        _ => return Err(ImageFromNrrdErr::ComponentsDoNotMatch),
+    };
 
-        for i in 0..D {
-            offset += index[i] * stride;
-            stride *= self.sizes[i];
+    // Files without kinds are trusted to store what was asked for
+    if let Some(kind) = kinds.and_then(|k| k.split_whitespace().next()) {
+        if !T::accepts_kind(kind) {
+            return Err(ImageFromNrrdErr::KindDoesNotMatch);
         }
+    }
+
+    Ok(&sizes[1..])
+}
+
+#[inline]
+pub(crate) fn fixed_sizes<const D: usize>(sizes: &[i32]) -> Result<[usize; D], ImageFromNrrdErr> {
+    if sizes.len() != D {
+        return Err(ImageFromNrrdErr::DimensionsDoNotMatch);
+    }
+
+    let mut fixed = [0; D];
+    for (size, &nrrd_size) in fixed.iter_mut().zip(sizes) {
+        *size = nrrd_size as usize;
+    }
+
+    Ok(fixed)
+}
+
+/// Checks that `bytes` can be reinterpreted as exactly `count` pixels without decoding
+pub(crate) fn check_pod_layout<T: PodPixel>(
+    encoding: &Encoding,
+    endian: Endian,
+    bytes: &[u8],
+    count: usize,
+) -> Result<(), ImageFromNrrdErr> {
+    if *encoding != Encoding::Raw {
+        return Err(ImageFromNrrdErr::UnsupportedEncoding);
+    }
+
+    let is_native = match endian {
+        Endian::Little => cfg!(target_endian = "little"),
+        Endian::Big => cfg!(target_endian = "big"),
+    };
+    let is_swapped = match T::pixel_type() {
+        PixelType::Block(_) => false,
+        pixel_type => pixel_type.size() > 1,
+    };
+    if !is_native && is_swapped {
+        return Err(ImageFromNrrdErr::EndianDoesNotMatch);
+    }
+
+    if bytes.as_ptr().align_offset(mem::align_of::<T>()) != 0 {
+        return Err(ImageFromNrrdErr::UnalignedBuffer);
+    }
+
+    if bytes.len() != count * mem::size_of::<T>() {
+        let err = ReadNrrdErr::Malformed("Buffer size mismatch".to_string());
+        return Err(ImageFromNrrdErr::CannotReadNrrd(err));
+    }
+
+    Ok(())
+}
+
+/// Decodes `count` pixels from the start of raw buffer, which is malformed if it holds fewer
+pub(crate) fn decode_pixels<T: PixelValue>(
+    count: usize,
+    bytes: &[u8],
+    endian: Endian,
+) -> Result<Vec<T>, ImageFromNrrdErr> {
+    let pixel_size = T::pixel_size();
+    if count
+        .checked_mul(pixel_size)
+        .is_none_or(|len| len > bytes.len())
+    {
+        let err = ReadNrrdErr::Malformed("Buffer size mismatch".to_string());
+        return Err(ImageFromNrrdErr::CannotReadNrrd(err));
+    }
+
+    let mut buffer = vec![T::default(); count];
+    let mut offset = 0;
+
+    for pixel in buffer.iter_mut() {
+        *pixel = T::from_bytes(&bytes[offset..], endian);
+        offset += pixel_size;
+    }
+
+    Ok(buffer)
+}
 
-        offset
+/// Offset of pixel in a buffer where the first axis is the fastest
+#[inline]
+pub(crate) fn linear_offset(index: &[usize], sizes: &[usize]) -> usize {
+    let mut offset = 0;
+    let mut stride = 1;
+
+    for (&i, &size) in index.iter().zip(sizes) {
+        offset += i * stride;
+        stride *= size;
     }
+
+    offset
 }
 
 #[derive(Debug)]
@@ -73,7 +362,14 @@ pub enum ImageFromNrrdErr {
     DimensionsDoNotMatch,
     PixelTypesDoNotMatch,
     CannotReadNrrd(ReadNrrdErr),
+    /// Zero-copy access needs raw pixel data, buffers of a `Nrrd` in memory are always decoded
     UnsupportedEncoding,
+    IndexOutOfBounds,
+    EndianDoesNotMatch,
+    UnalignedBuffer,
+    ValueNotRepresentable,
+    ComponentsDoNotMatch,
+    KindDoesNotMatch,
 }
 
 impl From<ReadNrrdErr> for ImageFromNrrdErr {
@@ -87,39 +383,23 @@ impl<T: PixelValue, const D: usize> TryFrom<&Nrrd> for Image<T, D> {
     type Error = ImageFromNrrdErr;
 
     fn try_from(nrrd: &Nrrd) -> Result<Self, Self::Error> {
-        if nrrd.dimension() as usize != D {
-            return Err(ImageFromNrrdErr::DimensionsDoNotMatch);
-        }
-
-        if T::pixel_type() != nrrd.pixel_type() {
-            return Err(ImageFromNrrdErr::PixelTypesDoNotMatch);
-        }
-
-        if *nrrd.encoding() != Encoding::Raw {
-            return Err(ImageFromNrrdErr::UnsupportedEncoding);
-        }
-
-        let mut sizes = [0; D];
-        for i in 0..D {
-            sizes[i] = nrrd.sizes()[i] as usize;
-        }
-
-        let pixels = sizes.iter().product();
-        let mut buffer = vec![T::default(); pixels];
-
-        let pixel_size = T::pixel_type().size();
-        let mut offset = 0;
+        let sizes = image_sizes::<T>(nrrd.pixel_type(), nrrd.sizes(), nrrd.field("kinds"))?;
+        let sizes = fixed_sizes(sizes)?;
 
-        for i in 0..pixels {
-            buffer[i] = T::from_bytes(&nrrd.buffer()[offset..], nrrd.endian());
-            offset += pixel_size;
-        }
+        Self::from_bytes(sizes, nrrd.buffer(), nrrd.endian())
+    }
+}
 
-        Ok(Self { buffer, sizes })
+/// Collects pixels into a 1D image
+impl<T: PixelValue> FromIterator<T> for Image<T, 1> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let buffer: Vec<T> = iter.into_iter().collect();
+        let sizes = [buffer.len()];
+        Self::from_parts(buffer, sizes)
     }
 }
 
-impl<T: PixelValue, const D: usize> Index<&[usize; D]> for Image<T, D> {
+impl<T: PixelValue, const D: usize, S: Storage<T>> Index<&[usize; D]> for Image<T, D, S> {
     type Output = T;
 
     #[inline]
@@ -128,7 +408,7 @@ impl<T: PixelValue, const D: usize> Index<&[usize; D]> for Image<T, D> {
     }
 }
 
-impl<T: PixelValue, const D: usize> Index<[usize; D]> for Image<T, D> {
+impl<T: PixelValue, const D: usize, S: Storage<T>> Index<[usize; D]> for Image<T, D, S> {
     type Output = T;
 
     #[inline]
@@ -137,15 +417,69 @@ impl<T: PixelValue, const D: usize> Index<[usize; D]> for Image<T, D> {
     }
 }
 
-impl<T: PixelValue, const D: usize> IndexMut<&[usize; D]> for Image<T, D> {
+impl<T: PixelValue, const D: usize, S: StorageMut<T>> IndexMut<&[usize; D]> for Image<T, D, S> {
     #[inline]
     fn index_mut(&mut self, index: &[usize; D]) -> &mut Self::Output {
         self.get_mut(index)
     }
 }
 
-impl<T: PixelValue, const D: usize> IndexMut<[usize; D]> for Image<T, D> {
+impl<T: PixelValue, const D: usize, S: StorageMut<T>> IndexMut<[usize; D]> for Image<T, D, S> {
     fn index_mut(&mut self, index: [usize; D]) -> &mut Self::Output {
         self.get_mut(&index)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Image;
+
+    #[test]
+    fn from_fn_visits_first_axis_fastest() {
+        let image = Image::<u16, 3>::from_fn([2, 3, 2], |[x, y, z]| (x + 10 * y + 100 * z) as u16);
+        assert_eq!(&image.pixels()[..4], &[0, 1, 10, 11]);
+        assert_eq!(image[[1, 2, 1]], 121);
+        assert_eq!(image.pixels_count(), 12);
+
+        let mut calls = 0;
+        let empty = Image::<u8, 2>::from_fn([3, 0], |_| {
+            calls += 1;
+            0
+        });
+        assert_eq!((empty.pixels_count(), calls), (0, 0));
+    }
+
+    #[test]
+    fn from_vec_checks_length_and_into_vec_gives_it_back() {
+        assert!(Image::<i32, 2>::from_vec([2, 2], vec![1, 2, 3]).is_none());
+        assert!(Image::<i32, 2>::from_vec([2, 2], vec![0; 5]).is_none());
+
+        let mut image = Image::<i32, 2>::from_vec([2, 2], vec![1, 2, 3, 4]).unwrap();
+        assert_eq!(image[[0, 1]], 3);
+        image.pixels_mut()[3] = -4;
+        assert_eq!(image.into_vec(), [1, 2, 3, -4]);
+    }
+
+    #[test]
+    fn copies_sizes_from_another_image() {
+        let source = Image::<f64, 2>::new(0.5, [4, 3]);
+
+        let mask = Image::<u8, 2>::new_like(&source, 1);
+        assert_eq!(mask.sizes(), source.sizes());
+        assert!(mask.pixels().iter().all(|&p| p == 1));
+
+        let products = Image::<u32, 2>::from_fn_like(&source, |[x, y]| (x * y) as u32);
+        assert_eq!(products.sizes(), &[4, 3]);
+        assert_eq!(products[[3, 2]], 6);
+    }
+
+    #[test]
+    fn collects_into_1d_image() {
+        let image: Image<u64, 1> = (0..5).map(|i| i * i).collect();
+        assert_eq!(image.sizes(), &[5]);
+        assert_eq!(image[[4]], 16);
+
+        let empty: Image<u8, 1> = std::iter::empty().collect();
+        assert_eq!(empty.sizes(), &[0]);
+    }
+}
@@ -0,0 +1,808 @@
+//! Iterators over pixels together with their indices, over regions and over lines along an axis.
+//!
+//! Indices are `[usize; D]` with the first axis being the fastest, in the order pixels are visited.
+//! Region iterators report indices of the whole image, line iterators visit lines in index order
+//! of their first pixel. With the `parallel` feature, `par_` versions split the work with rayon.
+
+use crate::{
+    image::Image,
+    pixel::PixelValue,
+    storage::{Storage, StorageMut},
+    view::{ImageView, ImageViewMut, Layout},
+};
+use std::{
+    marker::PhantomData,
+    ops::{Index, IndexMut},
+};
+
+/// Iterator over `(index, &pixel)`, returned by `indexed_iter` and `region_iter`
+pub struct IndexedIter<'a, T, const D: usize> {
+    pixels: &'a [T],
+    layout: Layout<D>,
+    origin: [usize; D],
+    index: [usize; D],
+    remaining: usize,
+}
+
+impl<'a, T, const D: usize> IndexedIter<'a, T, D> {
+    #[inline]
+    fn new(pixels: &'a [T], layout: Layout<D>, origin: [usize; D]) -> Self {
+        Self {
+            pixels,
+            layout,
+            origin,
+            index: [0; D],
+            remaining: layout.pixels_count(),
+        }
+    }
+}
+
+impl<'a, T, const D: usize> Iterator for IndexedIter<'a, T, D> {
+    type Item = ([usize; D], &'a T);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let index = self.index;
+        let pixel = &self.pixels[self.layout.offset(&index)];
+        self.layout.advance(&mut self.index);
+        self.remaining -= 1;
+
+        Some((shifted(index, &self.origin), pixel))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T, const D: usize> ExactSizeIterator for IndexedIter<'_, T, D> {}
+
+/// Iterator over `(index, &mut pixel)`, returned by `indexed_iter_mut` and `region_iter_mut`
+pub struct IndexedIterMut<'a, T, const D: usize> {
+    pixels: *mut T,
+    layout: Layout<D>,
+    origin: [usize; D],
+    index: [usize; D],
+    remaining: usize,
+    lifetime: PhantomData<&'a mut T>,
+}
+
+impl<'a, T, const D: usize> IndexedIterMut<'a, T, D> {
+    #[inline]
+    fn new(pixels: &'a mut [T], layout: Layout<D>, origin: [usize; D]) -> Self {
+        Self {
+            pixels: pixels.as_mut_ptr(),
+            layout,
+            origin,
+            index: [0; D],
+            remaining: layout.pixels_count(),
+            lifetime: PhantomData,
+        }
+    }
+}
+
+// SAFETY: iterator hands out distinct `&mut T` like `slice::IterMut` does
+unsafe impl<T: Send, const D: usize> Send for IndexedIterMut<'_, T, D> {}
+unsafe impl<T: Sync, const D: usize> Sync for IndexedIterMut<'_, T, D> {}
+
+impl<'a, T, const D: usize> Iterator for IndexedIterMut<'a, T, D> {
+    type Item = ([usize; D], &'a mut T);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let index = self.index;
+        let offset = self.layout.offset(&index);
+        self.layout.advance(&mut self.index);
+        self.remaining -= 1;
+
+        // SAFETY: the layout came from a view over the borrowed slice, so the offset is in bounds,
+        // and every index is visited once and maps to its own pixel, so references never alias
+        let pixel = unsafe { &mut *self.pixels.add(offset) };
+        Some((shifted(index, &self.origin), pixel))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T, const D: usize> ExactSizeIterator for IndexedIterMut<'_, T, D> {}
+
+#[inline]
+fn shifted<const D: usize>(mut index: [usize; D], origin: &[usize; D]) -> [usize; D] {
+    for (i, &o) in index.iter_mut().zip(origin) {
+        *i += o;
+    }
+    index
+}
+
+/// Lines of a layout along one axis, each one given by its first pixel and a 1D layout
+#[derive(Debug, Clone, Copy)]
+struct LineLayout<const D: usize> {
+    /// Layout of first pixels of lines
+    firsts: Layout<D>,
+    stride: isize,
+    len: usize,
+}
+
+impl<const D: usize> LineLayout<D> {
+    fn new(layout: &Layout<D>, axis: usize) -> Self {
+        assert!(axis < D, "axis out of bounds");
+
+        let mut firsts = *layout;
+        firsts.sizes[axis] = 1;
+
+        Self {
+            firsts,
+            stride: layout.strides[axis],
+            len: layout.sizes[axis],
+        }
+    }
+
+    #[inline]
+    fn count(&self) -> usize {
+        // Lines of an empty axis still exist, they are just empty
+        match self.firsts.sizes.contains(&0) {
+            true => 0,
+            false => self.firsts.pixels_count(),
+        }
+    }
+
+    /// Layout of the `n`-th line, `n` must be less than `count`
+    #[inline]
+    fn line(&self, n: usize) -> Layout<1> {
+        Layout {
+            start: self.firsts.offset(&self.firsts.unravel(n)),
+            strides: [self.stride],
+            sizes: [self.len],
+        }
+    }
+}
+
+/// Iterator over lines along an axis as 1D views, returned by `lines`
+pub struct Lines<'a, T: PixelValue, const D: usize> {
+    pixels: &'a [T],
+    lines: LineLayout<D>,
+    next: usize,
+}
+
+impl<'a, T: PixelValue, const D: usize> Iterator for Lines<'a, T, D> {
+    type Item = ImageView<'a, T, 1>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next == self.lines.count() {
+            return None;
+        }
+
+        let layout = self.lines.line(self.next);
+        self.next += 1;
+
+        Some(ImageView {
+            pixels: self.pixels,
+            layout,
+        })
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.lines.count() - self.next;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<T: PixelValue, const D: usize> ExactSizeIterator for Lines<'_, T, D> {}
+
+/// Iterator over lines along an axis, returned by `lines_mut`
+pub struct LinesMut<'a, T, const D: usize> {
+    pixels: *mut T,
+    lines: LineLayout<D>,
+    next: usize,
+    lifetime: PhantomData<&'a mut T>,
+}
+
+// SAFETY: lines never share pixels, so they can be handed out like `slice::ChunksMut` does
+unsafe impl<T: Send, const D: usize> Send for LinesMut<'_, T, D> {}
+unsafe impl<T: Sync, const D: usize> Sync for LinesMut<'_, T, D> {}
+
+impl<'a, T, const D: usize> Iterator for LinesMut<'a, T, D> {
+    type Item = LineMut<'a, T>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next == self.lines.count() {
+            return None;
+        }
+
+        let layout = self.lines.line(self.next);
+        self.next += 1;
+
+        // SAFETY: distinct lines of a view never share pixels
+        Some(unsafe { LineMut::new(self.pixels, layout) })
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.lines.count() - self.next;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<T, const D: usize> ExactSizeIterator for LinesMut<'_, T, D> {}
+
+/// Mutably borrowed line of pixels along one axis.
+///
+/// Lines along slower axes interleave in memory, so unlike `ImageViewMut`
+/// this never borrows more of the buffer than the pixels of the line.
+pub struct LineMut<'a, T> {
+    pixels: *mut T,
+    start: usize,
+    stride: isize,
+    len: usize,
+    lifetime: PhantomData<&'a mut T>,
+}
+
+// SAFETY: line behaves like `&mut [T]` over its own pixels
+unsafe impl<T: Send> Send for LineMut<'_, T> {}
+unsafe impl<T: Sync> Sync for LineMut<'_, T> {}
+
+impl<'a, T> LineMut<'a, T> {
+    /// Caller must ensure that the layout is in bounds of `pixels`
+    /// and that no other reference to its pixels exists for `'a`
+    #[inline]
+    unsafe fn new(pixels: *mut T, layout: Layout<1>) -> Self {
+        Self {
+            pixels,
+            start: layout.start,
+            stride: layout.strides[0],
+            len: layout.sizes[0],
+            lifetime: PhantomData,
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Distance in pixels between neighbours of the line
+    #[inline]
+    pub fn stride(&self) -> isize {
+        self.stride
+    }
+
+    #[inline]
+    fn pointer(&self, i: usize) -> *mut T {
+        assert!(i < self.len, "index out of bounds");
+        line_pointer(self.pixels, self.start, self.stride, i)
+    }
+
+    #[inline]
+    pub fn get(&self, i: usize) -> &T {
+        // SAFETY: pixel belongs to this line, which is borrowed
+        unsafe { &*self.pointer(i) }
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, i: usize) -> &mut T {
+        // SAFETY: pixel belongs to this line, which is borrowed exclusively
+        unsafe { &mut *self.pointer(i) }
+    }
+
+    /// Pixels from the first one on
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &T> + '_ {
+        (0..self.len).map(|i| self.get(i))
+    }
+
+    /// Pixels from the first one on
+    pub fn iter_mut(&mut self) -> impl ExactSizeIterator<Item = &mut T> + '_ {
+        let (pixels, start, stride) = (self.pixels, self.start, self.stride);
+        // SAFETY: pixels of the line are distinct and borrowed exclusively
+        (0..self.len).map(move |i| unsafe { &mut *line_pointer(pixels, start, stride, i) })
+    }
+
+    /// Copies pixels from `source`, panics if lengths differ
+    pub fn copy_from_slice(&mut self, source: &[T])
+    where
+        T: Clone,
+    {
+        assert_eq!(self.len, source.len(), "lengths do not match");
+
+        for (pixel, value) in self.iter_mut().zip(source) {
+            *pixel = value.clone();
+        }
+    }
+
+    /// Copies pixels into a `Vec`
+    pub fn to_vec(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        self.iter().cloned().collect()
+    }
+}
+
+/// Pointer to the `i`-th pixel of a line, which must be within the buffer
+#[inline]
+fn line_pointer<T>(pixels: *mut T, start: usize, stride: isize, i: usize) -> *mut T {
+    pixels.wrapping_offset(start as isize + i as isize * stride)
+}
+
+impl<T> Index<usize> for LineMut<'_, T> {
+    type Output = T;
+
+    #[inline]
+    fn index(&self, i: usize) -> &Self::Output {
+        self.get(i)
+    }
+}
+
+impl<T> IndexMut<usize> for LineMut<'_, T> {
+    #[inline]
+    fn index_mut(&mut self, i: usize) -> &mut Self::Output {
+        self.get_mut(i)
+    }
+}
+
+impl<'a, T: PixelValue, const D: usize> ImageView<'a, T, D> {
+    /// Pixels with their indices, first axis is the fastest
+    #[inline]
+    pub fn indexed_iter(&self) -> IndexedIter<'a, T, D> {
+        IndexedIter::new(self.pixels, self.layout, [0; D])
+    }
+
+    /// Lines along `axis` as 1D views, panics if `axis` is out of bounds
+    #[inline]
+    pub fn lines(&self, axis: usize) -> Lines<'a, T, D> {
+        Lines {
+            pixels: self.pixels,
+            lines: LineLayout::new(&self.layout, axis),
+            next: 0,
+        }
+    }
+}
+
+impl<T: PixelValue, const D: usize> ImageViewMut<'_, T, D> {
+    /// Pixels with their indices, first axis is the fastest
+    #[inline]
+    pub fn indexed_iter_mut(&mut self) -> IndexedIterMut<'_, T, D> {
+        IndexedIterMut::new(self.pixels, self.layout, [0; D])
+    }
+
+    /// Lines along `axis`, panics if `axis` is out of bounds
+    #[inline]
+    pub fn lines_mut(&mut self, axis: usize) -> LinesMut<'_, T, D> {
+        LinesMut {
+            pixels: self.pixels.as_mut_ptr(),
+            lines: LineLayout::new(&self.layout, axis),
+            next: 0,
+            lifetime: PhantomData,
+        }
+    }
+}
+
+impl<T: PixelValue, const D: usize, S: Storage<T>> Image<T, D, S> {
+    /// Pixels with their indices, first axis is the fastest
+    #[inline]
+    pub fn indexed_iter(&self) -> IndexedIter<'_, T, D> {
+        self.view().indexed_iter()
+    }
+
+    /// Pixels of the region starting at `origin` with their indices in this image,
+    /// returns `None` if the region does not fit
+    pub fn region_iter(
+        &self,
+        origin: [usize; D],
+        sizes: [usize; D],
+    ) -> Option<IndexedIter<'_, T, D>> {
+        let region = self.view().region(origin, sizes)?;
+        Some(IndexedIter::new(region.pixels, region.layout, origin))
+    }
+
+    /// Lines along `axis` as 1D views, panics if `axis` is out of bounds
+    #[inline]
+    pub fn lines(&self, axis: usize) -> Lines<'_, T, D> {
+        self.view().lines(axis)
+    }
+}
+
+impl<T: PixelValue, const D: usize, S: StorageMut<T>> Image<T, D, S> {
+    /// Pixels with their indices, first axis is the fastest
+    #[inline]
+    pub fn indexed_iter_mut(&mut self) -> IndexedIterMut<'_, T, D> {
+        let view = self.view_mut();
+        IndexedIterMut::new(view.pixels, view.layout, [0; D])
+    }
+
+    /// Pixels of the region starting at `origin` with their indices in this image,
+    /// returns `None` if the region does not fit
+    pub fn region_iter_mut(
+        &mut self,
+        origin: [usize; D],
+        sizes: [usize; D],
+    ) -> Option<IndexedIterMut<'_, T, D>> {
+        let region = self.view_mut().into_region(origin, sizes)?;
+        Some(IndexedIterMut::new(region.pixels, region.layout, origin))
+    }
+
+    /// Lines along `axis`, panics if `axis` is out of bounds
+    pub fn lines_mut(&mut self, axis: usize) -> LinesMut<'_, T, D> {
+        let view = self.view_mut();
+        LinesMut {
+            pixels: view.pixels.as_mut_ptr(),
+            lines: LineLayout::new(&view.layout, axis),
+            next: 0,
+            lifetime: PhantomData,
+        }
+    }
+}
+
+#[cfg(feature = "parallel")]
+mod parallel {
+    use super::{shifted, LineLayout, LineMut};
+    use crate::{
+        image::Image,
+        pixel::PixelValue,
+        storage::{Storage, StorageMut},
+        view::{ImageView, ImageViewMut, Layout},
+    };
+    use rayon::prelude::*;
+
+    /// Pointer to pixels shared by rayon workers, each of which only touches its own pixels
+    struct SharedPixels<T>(*mut T);
+
+    impl<T> Clone for SharedPixels<T> {
+        #[inline]
+        fn clone(&self) -> Self {
+            *self
+        }
+    }
+
+    impl<T> Copy for SharedPixels<T> {}
+
+    // SAFETY: workers never access the same pixel
+    unsafe impl<T: Send> Send for SharedPixels<T> {}
+    unsafe impl<T: Send> Sync for SharedPixels<T> {}
+
+    impl<T> SharedPixels<T> {
+        /// Method instead of field access, so closures capture the whole `Send` wrapper
+        #[inline]
+        fn get(self) -> *mut T {
+            self.0
+        }
+    }
+
+    fn par_indexed<T: Sync, const D: usize>(
+        pixels: &[T],
+        layout: Layout<D>,
+        origin: [usize; D],
+    ) -> impl IndexedParallelIterator<Item = ([usize; D], &T)> {
+        (0..layout.pixels_count()).into_par_iter().map(move |n| {
+            let index = layout.unravel(n);
+            (shifted(index, &origin), &pixels[layout.offset(&index)])
+        })
+    }
+
+    fn par_indexed_mut<T: Send, const D: usize>(
+        pixels: &mut [T],
+        layout: Layout<D>,
+        origin: [usize; D],
+    ) -> impl IndexedParallelIterator<Item = ([usize; D], &mut T)> {
+        let pixels = SharedPixels(pixels.as_mut_ptr());
+        (0..layout.pixels_count()).into_par_iter().map(move |n| {
+            let index = layout.unravel(n);
+            let offset = layout.offset(&index);
+            // SAFETY: offset is in bounds, and distinct `n` map to distinct pixels
+            (shifted(index, &origin), unsafe {
+                &mut *pixels.get().add(offset)
+            })
+        })
+    }
+
+    fn par_lines<'a, T: PixelValue + Sync, const D: usize>(
+        pixels: &'a [T],
+        layout: &Layout<D>,
+        axis: usize,
+    ) -> impl IndexedParallelIterator<Item = ImageView<'a, T, 1>> {
+        let lines = LineLayout::new(layout, axis);
+        (0..lines.count()).into_par_iter().map(move |n| ImageView {
+            pixels,
+            layout: lines.line(n),
+        })
+    }
+
+    fn par_lines_mut<'a, T: Send, const D: usize>(
+        pixels: &'a mut [T],
+        layout: &Layout<D>,
+        axis: usize,
+    ) -> impl IndexedParallelIterator<Item = LineMut<'a, T>> {
+        let lines = LineLayout::new(layout, axis);
+        let pixels = SharedPixels(pixels.as_mut_ptr());
+        (0..lines.count())
+            .into_par_iter()
+            // SAFETY: distinct lines of a view never share pixels
+            .map(move |n| unsafe { LineMut::new(pixels.get(), lines.line(n)) })
+    }
+
+    impl<'a, T: PixelValue + Sync, const D: usize> ImageView<'a, T, D> {
+        /// Parallel version of `indexed_iter`
+        pub fn par_indexed_iter(&self) -> impl IndexedParallelIterator<Item = ([usize; D], &'a T)> {
+            par_indexed(self.pixels, self.layout, [0; D])
+        }
+
+        /// Parallel version of `lines`
+        pub fn par_lines(
+            &self,
+            axis: usize,
+        ) -> impl IndexedParallelIterator<Item = ImageView<'a, T, 1>> {
+            par_lines(self.pixels, &self.layout, axis)
+        }
+    }
+
+    impl<T: PixelValue + Send, const D: usize> ImageViewMut<'_, T, D> {
+        /// Parallel version of `indexed_iter_mut`
+        pub fn par_indexed_iter_mut(
+            &mut self,
+        ) -> impl IndexedParallelIterator<Item = ([usize; D], &mut T)> {
+            par_indexed_mut(self.pixels, self.layout, [0; D])
+        }
+
+        /// Parallel version of `lines_mut`
+        pub fn par_lines_mut(
+            &mut self,
+            axis: usize,
+        ) -> impl IndexedParallelIterator<Item = LineMut<'_, T>> {
+            par_lines_mut(self.pixels, &self.layout, axis)
+        }
+    }
+
+    impl<T: PixelValue + Sync, const D: usize, S: Storage<T>> Image<T, D, S> {
+        /// Parallel version of `indexed_iter`
+        pub fn par_indexed_iter(&self) -> impl IndexedParallelIterator<Item = ([usize; D], &T)> {
+            let view = self.view();
+            par_indexed(view.pixels, view.layout, [0; D])
+        }
+
+        /// Parallel version of `region_iter`
+        pub fn par_region_iter(
+            &self,
+            origin: [usize; D],
+            sizes: [usize; D],
+        ) -> Option<impl IndexedParallelIterator<Item = ([usize; D], &T)>> {
+            let region = self.view().region(origin, sizes)?;
+            Some(par_indexed(region.pixels, region.layout, origin))
+        }
+
+        /// Parallel version of `lines`
+        pub fn par_lines(
+            &self,
+            axis: usize,
+        ) -> impl IndexedParallelIterator<Item = ImageView<'_, T, 1>> {
+            let view = self.view();
+            par_lines(view.pixels, &view.layout, axis)
+        }
+    }
+
+    impl<T: PixelValue + Send, const D: usize, S: StorageMut<T>> Image<T, D, S> {
+        /// Parallel version of `indexed_iter_mut`
+        pub fn par_indexed_iter_mut(
+            &mut self,
+        ) -> impl IndexedParallelIterator<Item = ([usize; D], &mut T)> {
+            let view = self.view_mut();
+            par_indexed_mut(view.pixels, view.layout, [0; D])
+        }
+
+        /// Parallel version of `region_iter_mut`
+        pub fn par_region_iter_mut(
+            &mut self,
+            origin: [usize; D],
+            sizes: [usize; D],
+        ) -> Option<impl IndexedParallelIterator<Item = ([usize; D], &mut T)>> {
+            let region = self.view_mut().into_region(origin, sizes)?;
+            Some(par_indexed_mut(region.pixels, region.layout, origin))
+        }
+
+        /// Parallel version of `lines_mut`
+        pub fn par_lines_mut(
+            &mut self,
+            axis: usize,
+        ) -> impl IndexedParallelIterator<Item = LineMut<'_, T>> {
+            let view = self.view_mut();
+            par_lines_mut(view.pixels, &view.layout, axis)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{image::Image, view::ImageViewMut};
+
+    /// Added to every pixel written through a mutable iterator
+    const MARK: u32 = 1000;
+
+    type Shape = fn(ImageViewMut<'_, u32, 3>) -> ImageViewMut<'_, u32, 3>;
+
+    fn whole(view: ImageViewMut<'_, u32, 3>) -> ImageViewMut<'_, u32, 3> {
+        view
+    }
+
+    fn flipped(view: ImageViewMut<'_, u32, 3>) -> ImageViewMut<'_, u32, 3> {
+        view.into_flipped(1).into_flipped(2)
+    }
+
+    fn permuted(view: ImageViewMut<'_, u32, 3>) -> ImageViewMut<'_, u32, 3> {
+        view.into_permuted([2, 0, 1])
+    }
+
+    fn stepped(view: ImageViewMut<'_, u32, 3>) -> ImageViewMut<'_, u32, 3> {
+        view.into_stepped(0, 2).into_stepped(1, 3)
+    }
+
+    fn combined(view: ImageViewMut<'_, u32, 3>) -> ImageViewMut<'_, u32, 3> {
+        view.into_region([1, 1, 0], [4, 3, 2])
+            .unwrap()
+            .into_flipped(0)
+            .into_permuted([1, 2, 0])
+            .into_stepped(2, 2)
+    }
+
+    fn empty(view: ImageViewMut<'_, u32, 3>) -> ImageViewMut<'_, u32, 3> {
+        view.into_region([1, 2, 0], [3, 0, 2]).unwrap()
+    }
+
+    const SHAPES: [Shape; 6] = [whole, flipped, permuted, stepped, combined, empty];
+
+    fn image() -> Image<u32, 3> {
+        Image::from_fn([5, 4, 2], |[x, y, z]| (x + 5 * y + 20 * z) as u32)
+    }
+
+    fn marked(image: &Image<u32, 3>) -> usize {
+        image.pixels().iter().filter(|&&p| p >= MARK).count()
+    }
+
+    #[test]
+    fn indexed_iter_mut_visits_each_pixel_once() {
+        for shape in SHAPES {
+            let mut image = image();
+            let mut view = shape(image.view_mut());
+            let expected = view
+                .as_view()
+                .indexed_iter()
+                .map(|(index, &p)| (index, p))
+                .collect::<Vec<_>>();
+            let count = view.pixels_count();
+
+            // Every reference is alive at once, as callers may keep them
+            let pixels = view.indexed_iter_mut().collect::<Vec<_>>();
+            assert_eq!(pixels.len(), expected.len());
+            for ((index, pixel), &(expected_index, value)) in pixels.into_iter().zip(&expected) {
+                assert_eq!((index, *pixel), (expected_index, value));
+                *pixel += MARK;
+            }
+
+            assert_eq!(marked(&image), count);
+        }
+    }
+
+    #[test]
+    fn iter_mut_follows_index_order() {
+        for shape in SHAPES {
+            let mut image = image();
+            let mut view = shape(image.view_mut());
+            let expected = view.as_view().iter().copied().collect::<Vec<_>>();
+            let count = view.pixels_count();
+
+            let pixels = view.iter_mut().collect::<Vec<_>>();
+            assert_eq!(pixels.iter().map(|p| **p).collect::<Vec<_>>(), expected);
+            for pixel in pixels {
+                *pixel += MARK;
+            }
+
+            assert_eq!(marked(&image), count);
+        }
+    }
+
+    #[test]
+    fn lines_mut_match_lines() {
+        for shape in SHAPES {
+            for axis in 0..3 {
+                let mut image = image();
+                let mut view = shape(image.view_mut());
+                let expected = view
+                    .as_view()
+                    .lines(axis)
+                    .map(|line| line.iter().copied().collect::<Vec<_>>())
+                    .collect::<Vec<_>>();
+                let count = view.pixels_count();
+
+                let mut lines = view.lines_mut(axis).collect::<Vec<_>>();
+                let actual = lines.iter().map(|line| line.to_vec()).collect::<Vec<_>>();
+                assert_eq!(actual, expected);
+
+                for line in &mut lines {
+                    for pixel in line.iter_mut() {
+                        *pixel += MARK;
+                    }
+                }
+
+                assert_eq!(marked(&image), count);
+            }
+        }
+    }
+
+    #[test]
+    fn image_lines_mut_index_pixels() {
+        let mut image = image();
+        for (y, mut line) in image.lines_mut(1).enumerate() {
+            assert_eq!(line.len(), 4);
+            assert_eq!(line.stride(), 5);
+            line[3] = y as u32;
+        }
+
+        assert_eq!(image[[2, 3, 0]], 2);
+        assert_eq!(image[[4, 3, 1]], 9);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn par_iter_mut_match_sequential() {
+        use rayon::prelude::*;
+
+        for shape in SHAPES {
+            let mut image = image();
+            let mut view = shape(image.view_mut());
+            let expected = view
+                .as_view()
+                .indexed_iter()
+                .map(|(index, &p)| (index, p))
+                .collect::<Vec<_>>();
+            let count = view.pixels_count();
+
+            let pixels = view.par_indexed_iter_mut().collect::<Vec<_>>();
+            for ((index, pixel), &(expected_index, value)) in pixels.into_iter().zip(&expected) {
+                assert_eq!((index, *pixel), (expected_index, value));
+                *pixel += MARK;
+            }
+            assert_eq!(marked(&image), count);
+
+            for axis in 0..3 {
+                let mut image = self::image();
+                let mut view = shape(image.view_mut());
+                let expected = view
+                    .as_view()
+                    .lines(axis)
+                    .map(|line| line.iter().copied().collect::<Vec<_>>())
+                    .collect::<Vec<_>>();
+
+                view.par_lines_mut(axis).for_each(|mut line| {
+                    for pixel in line.iter_mut() {
+                        *pixel += MARK;
+                    }
+                });
+
+                let actual = view
+                    .as_view()
+                    .lines(axis)
+                    .map(|line| line.iter().map(|p| p - MARK).collect::<Vec<_>>())
+                    .collect::<Vec<_>>();
+                assert_eq!(actual, expected);
+                assert_eq!(marked(&image), count);
+            }
+        }
+    }
+}
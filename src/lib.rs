@@ -1,7 +1,37 @@
+pub mod any_image;
+pub mod axes;
+pub mod block;
+pub mod component;
+pub mod convert;
+mod crop;
+pub mod dyn_image;
 pub mod image;
+pub mod iter;
+pub mod neighbourhood;
 pub mod nrrd;
+mod ops;
 pub mod pixel;
+pub mod resample;
+pub mod storage;
+pub mod view;
 
+pub use any_image::*;
+pub use axes::*;
+pub use block::*;
+pub use component::*;
+pub use convert::*;
+pub use dyn_image::*;
 pub use image::*;
-pub use nrrd::{reader::*, writer::*, *};
+pub use iter::*;
+pub use neighbourhood::*;
+pub use nrrd::{append::*, codec::*, file::*, nrrd_ref::*, progress::*, reader::*, writer::*, *};
 pub use pixel::*;
+pub use resample::*;
+pub use storage::*;
+pub use view::*;
+
+#[cfg(feature = "async")]
+pub use nrrd::async_io::*;
+
+#[cfg(feature = "derive")]
+pub use rusty_nrrd_derive::PixelValue;
@@ -0,0 +1,266 @@
+//! Access to pixels around a centre, including ones outside of the image.
+//!
+//! A `Neighbourhood` gives offsets from the centre, computed once with `offsets` and reused
+//! for every pixel. Offsets that fall outside of the image are resolved with a `Boundary`.
+
+use crate::{image::Image, pixel::PixelValue, storage::Storage, view::ImageView};
+
+/// How pixels outside of an image are read
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Boundary<T> {
+    /// Every pixel outside has this value
+    Constant(T),
+    /// Nearest pixel on the edge
+    Clamp,
+    /// Image reflected about its edge pixels, which are not repeated: `c b | a b c | b a`
+    Mirror,
+    /// Image repeated along every axis
+    Periodic,
+}
+
+impl<T> Boundary<T> {
+    /// Index inside of an axis of `size` pixels, or the constant to read instead.
+    /// Panics if the axis is empty unless the boundary is `Constant`.
+    #[inline]
+    pub(crate) fn resolve(&self, i: isize, size: usize) -> Result<usize, &T> {
+        if (0..size as isize).contains(&i) {
+            return Ok(i as usize);
+        }
+
+        let last = size as isize - 1;
+        let resolved = match self {
+            Self::Constant(value) => return Err(value),
+            _ if size == 0 => panic!("image is empty"),
+            Self::Clamp => i.clamp(0, last),
+            Self::Mirror if last == 0 => 0,
+            Self::Mirror => {
+                let period = 2 * last;
+                let i = i.rem_euclid(period);
+                match i > last {
+                    true => period - i,
+                    false => i,
+                }
+            }
+            Self::Periodic => i.rem_euclid(size as isize),
+        };
+
+        Ok(resolved as usize)
+    }
+}
+
+/// Shape of the pixels around a centre
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Neighbourhood<const D: usize> {
+    /// Window reaching `radius[axis]` pixels to both sides along each axis, centre included
+    Box([usize; D]),
+    /// Pixels reachable in at most `radius` steps between pixels sharing a face, centre excluded
+    FaceConnected(usize),
+    /// Pixels at most `radius` away along every axis, centre excluded
+    FullConnected(usize),
+}
+
+impl<const D: usize> Neighbourhood<D> {
+    /// Offsets from the centre, first axis is the fastest
+    pub fn offsets(&self) -> Vec<[isize; D]> {
+        let radius = match self {
+            Self::Box(radius) => *radius,
+            Self::FaceConnected(radius) | Self::FullConnected(radius) => [*radius; D],
+        };
+
+        let mut offsets = Vec::new();
+        let mut offset = radius.map(|r| -(r as isize));
+        let count = radius.iter().map(|r| 2 * r + 1).product::<usize>();
+
+        for _ in 0..count {
+            let steps = offset.iter().map(|o| o.unsigned_abs()).sum::<usize>();
+            let is_included = match self {
+                Self::Box(_) => true,
+                Self::FaceConnected(radius) => steps > 0 && steps <= *radius,
+                Self::FullConnected(_) => steps > 0,
+            };
+            if is_included {
+                offsets.push(offset);
+            }
+
+            for (o, &r) in offset.iter_mut().zip(&radius) {
+                *o += 1;
+                if *o <= r as isize {
+                    break;
+                }
+                *o = -(r as isize);
+            }
+        }
+
+        offsets
+    }
+}
+
+/// Iterator over `(offset, &pixel)` around a centre, returned by `neighbours`
+pub struct Neighbours<'a, T: PixelValue, const D: usize> {
+    view: ImageView<'a, T, D>,
+    centre: [usize; D],
+    offsets: std::slice::Iter<'a, [isize; D]>,
+    boundary: &'a Boundary<T>,
+}
+
+impl<'a, T: PixelValue, const D: usize> Iterator for Neighbours<'a, T, D> {
+    type Item = ([isize; D], &'a T);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = *self.offsets.next()?;
+
+        let mut index = [0; D];
+        for ((i, &c), &o) in index.iter_mut().zip(&self.centre).zip(&offset) {
+            *i = c as isize + o;
+        }
+
+        Some((offset, self.view.get_with_boundary(&index, self.boundary)))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.offsets.size_hint()
+    }
+}
+
+impl<T: PixelValue, const D: usize> ExactSizeIterator for Neighbours<'_, T, D> {}
+
+impl<'a, T: PixelValue, const D: usize> ImageView<'a, T, D> {
+    /// Pixel at `index`, which may lie outside of the view and is then read according to `boundary`
+    pub fn get_with_boundary<'b>(&self, index: &[isize; D], boundary: &'b Boundary<T>) -> &'b T
+    where
+        'a: 'b,
+    {
+        let mut resolved = [0; D];
+        for ((r, &i), &size) in resolved.iter_mut().zip(index).zip(self.sizes()) {
+            match boundary.resolve(i, size) {
+                Ok(i) => *r = i,
+                Err(value) => return value,
+            }
+        }
+
+        self.get(&resolved)
+    }
+
+    /// Pixels at `offsets` from `centre`, ones outside of the view are read according to `boundary`
+    pub fn neighbours<'b>(
+        &self,
+        centre: &[usize; D],
+        offsets: &'b [[isize; D]],
+        boundary: &'b Boundary<T>,
+    ) -> Neighbours<'b, T, D>
+    where
+        'a: 'b,
+    {
+        Neighbours {
+            view: *self,
+            centre: *centre,
+            offsets: offsets.iter(),
+            boundary,
+        }
+    }
+}
+
+impl<T: PixelValue, const D: usize, S: Storage<T>> Image<T, D, S> {
+    /// Pixel at `index`, which may lie outside of the image and is then read according to `boundary`
+    #[inline]
+    pub fn get_with_boundary<'b>(&'b self, index: &[isize; D], boundary: &'b Boundary<T>) -> &'b T {
+        self.view().get_with_boundary(index, boundary)
+    }
+
+    /// Pixels at `offsets` from `centre`, ones outside of the image are read according to `boundary`
+    #[inline]
+    pub fn neighbours<'b>(
+        &'b self,
+        centre: &[usize; D],
+        offsets: &'b [[isize; D]],
+        boundary: &'b Boundary<T>,
+    ) -> Neighbours<'b, T, D> {
+        self.view().neighbours(centre, offsets, boundary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Boundary, Neighbourhood};
+    use crate::image::Image;
+
+    /// Indices `-5..9` of an axis of 4 pixels
+    fn resolve_all(boundary: &Boundary<i32>) -> Vec<Result<usize, i32>> {
+        (-5..9)
+            .map(|i| boundary.resolve(i, 4).map_err(|&value| value))
+            .collect()
+    }
+
+    #[test]
+    fn resolves_both_edges() {
+        let outside = Err(-1);
+        assert_eq!(
+            resolve_all(&Boundary::Constant(-1)),
+            [vec![outside; 5], (0..4).map(Ok).collect(), vec![outside; 5]].concat()
+        );
+        assert_eq!(
+            resolve_all(&Boundary::Clamp),
+            [0, 0, 0, 0, 0, 0, 1, 2, 3, 3, 3, 3, 3, 3].map(Ok)
+        );
+        assert_eq!(
+            resolve_all(&Boundary::Mirror),
+            [1, 2, 3, 2, 1, 0, 1, 2, 3, 2, 1, 0, 1, 2].map(Ok)
+        );
+        assert_eq!(
+            resolve_all(&Boundary::Periodic),
+            [3, 0, 1, 2, 3, 0, 1, 2, 3, 0, 1, 2, 3, 0].map(Ok)
+        );
+    }
+
+    #[test]
+    fn resolves_single_pixel_and_empty_axes() {
+        for boundary in [Boundary::<u8>::Clamp, Boundary::Mirror, Boundary::Periodic] {
+            assert_eq!(boundary.resolve(-3, 1), Ok(0));
+            assert_eq!(boundary.resolve(2, 1), Ok(0));
+        }
+        assert_eq!(Boundary::Constant(5).resolve(0, 0), Err(&5));
+    }
+
+    #[test]
+    #[should_panic(expected = "image is empty")]
+    fn empty_axes_need_a_constant() {
+        let _ = Boundary::<u8>::Mirror.resolve(0, 0);
+    }
+
+    #[test]
+    fn neighbourhood_offsets() {
+        assert_eq!(
+            Neighbourhood::Box([1, 0]).offsets(),
+            [[-1, 0], [0, 0], [1, 0]]
+        );
+        assert_eq!(
+            Neighbourhood::<2>::FaceConnected(1).offsets(),
+            [[0, -1], [-1, 0], [1, 0], [0, 1]]
+        );
+        assert_eq!(Neighbourhood::<2>::FullConnected(1).offsets().len(), 8);
+        assert_eq!(Neighbourhood::<3>::FaceConnected(2).offsets().len(), 24);
+    }
+
+    #[test]
+    fn reads_neighbours_across_edges() {
+        let image = Image::<u8, 2>::from_fn([3, 2], |[x, y]| (x + 3 * y) as u8);
+        let offsets = Neighbourhood::<2>::FaceConnected(1).offsets();
+
+        let values = |boundary| {
+            image
+                .neighbours(&[0, 0], &offsets, &boundary)
+                .map(|(_, &p)| p)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(values(Boundary::Constant(9)), [9, 9, 1, 3]);
+        assert_eq!(values(Boundary::Clamp), [0, 0, 1, 3]);
+        assert_eq!(values(Boundary::Mirror), [3, 1, 1, 3]);
+        assert_eq!(values(Boundary::Periodic), [3, 2, 1, 3]);
+
+        let view = image.view().flip(0);
+        assert_eq!(*view.get_with_boundary(&[3, -1], &Boundary::Periodic), 5);
+        assert_eq!(*image.get_with_boundary(&[-2, 2], &Boundary::Clamp), 3);
+    }
+}
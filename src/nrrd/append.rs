@@ -0,0 +1,299 @@
+use crate::{
+    image::Image,
+    nrrd::{
+        reader::{data_file_path, expected_buffer_size, read_header, ReadNrrdErr},
+        writer::write_header,
+        Endian, Nrrd,
+    },
+    pixel::PixelValue,
+    storage::Storage,
+};
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufReader, Seek, SeekFrom, Write},
+    marker::PhantomData,
+    path::Path,
+};
+
+/// Width reserved for the slowest axis size, enough for any `i32`
+const SLAB_COUNT_WIDTH: usize = 10;
+
+#[derive(Debug)]
+pub enum AppendNrrdErr {
+    SizesDoNotMatch,
+    TooManySlabs,
+    CannotReadNrrd(ReadNrrdErr),
+    IOError(std::io::Error),
+}
+
+impl From<std::io::Error> for AppendNrrdErr {
+    #[inline]
+    fn from(err: std::io::Error) -> Self {
+        AppendNrrdErr::IOError(err)
+    }
+}
+
+impl From<ReadNrrdErr> for AppendNrrdErr {
+    #[inline]
+    fn from(err: ReadNrrdErr) -> Self {
+        AppendNrrdErr::CannotReadNrrd(err)
+    }
+}
+
+/// Writes a raw NRRD that grows along its slowest axis.
+///
+/// Each appended slab is an `Image<T, D>` and the file on disk has dimension `D + 1`.
+/// The file is created with its first slab, so it is a valid NRRD from the start.
+/// Pixel data is written before the header, and only the `sizes` field is rewritten in place,
+/// so the header never describes more slabs than the data holds.
+///
+/// A crash between writing a slab and rewriting `sizes` leaves data past the last slab
+/// the header describes, which `read_nrrd` rejects as malformed.
+/// To recover, truncate the data file, or the header file when data is attached,
+/// to the end of the last whole slab counted by `sizes`, as `recover_appended` does.
+pub struct NrrdAppender<T: PixelValue, const D: usize> {
+    header: File,
+    data: Option<File>, // None when data is attached to the header
+    sizes_offset: u64,
+    slab_sizes: [usize; D],
+    slabs: usize,
+    buffer: Vec<u8>,
+    pixel: PhantomData<T>,
+}
+
+impl<T: PixelValue, const D: usize> NrrdAppender<T, D> {
+    /// Creates a single file with the data attached after the header, holding `first`
+    pub fn create_attached<P: AsRef<Path>, S: Storage<T>>(
+        path: P,
+        first: &Image<T, D, S>,
+    ) -> Result<Self, AppendNrrdErr> {
+        let header = File::create(path)?;
+        Self::create(header, None, None, first)
+    }
+
+    /// Creates a header file that refers to a separate raw data file via `data file`, holding `first`.
+    /// Relative data paths are resolved against the directory of the header.
+    pub fn create_detached<P: AsRef<Path>, Q: AsRef<Path>, S: Storage<T>>(
+        header_path: P,
+        data_path: Q,
+        first: &Image<T, D, S>,
+    ) -> Result<Self, AppendNrrdErr> {
+        let header_path = header_path.as_ref();
+        let data_path = data_path.as_ref();
+        let header_dir = header_path.parent().unwrap_or(Path::new(""));
+
+        let header = File::create(header_path)?;
+        let data = File::create(header_dir.join(data_path))?;
+        let data_file = data_path.to_string_lossy().into_owned();
+
+        Self::create(header, Some(data), Some(data_file), first)
+    }
+
+    fn create<S: Storage<T>>(
+        header: File,
+        data: Option<File>,
+        data_file: Option<String>,
+        first: &Image<T, D, S>,
+    ) -> Result<Self, AppendNrrdErr> {
+        let slab_sizes = *first.sizes();
+        let sizes: Vec<usize> = slab_sizes.iter().copied().chain([1]).collect();
+        let mut nrrd = Nrrd::raw_header::<T>(&sizes);
+
+        // Pad the slab count so that it can be rewritten in place, keeping a component axis if any
+        let (_, axes) = nrrd
+            .sizes()
+            .split_last()
+            .expect("slab axis is always present");
+        let sizes_prefix: String = axes.iter().map(|s| format!("{} ", s)).collect();
+        nrrd.set_field("sizes", format!("{}{:<SLAB_COUNT_WIDTH$}", sizes_prefix, 1));
+
+        if let Some(data_file) = data_file {
+            nrrd.set_field("data file", data_file);
+        }
+
+        let mut appender = Self {
+            header,
+            data,
+            sizes_offset: 0,
+            slab_sizes,
+            slabs: 1,
+            buffer: Vec::new(),
+            pixel: PhantomData,
+        };
+        appender.encode(first);
+
+        let mut bytes = Vec::new();
+        appender.sizes_offset = write_header(&nrrd, None, &mut bytes)? + sizes_prefix.len() as u64;
+
+        match &mut appender.data {
+            Some(data) => {
+                data.write_all(&appender.buffer)?;
+                data.sync_data()?;
+            }
+            None => bytes.extend_from_slice(&appender.buffer),
+        }
+        appender.header.write_all(&bytes)?;
+        appender.header.sync_data()?;
+
+        Ok(appender)
+    }
+
+    /// Appends one slab and updates `sizes` to include it
+    pub fn append<S: Storage<T>>(&mut self, slab: &Image<T, D, S>) -> Result<(), AppendNrrdErr> {
+        if *slab.sizes() != self.slab_sizes {
+            return Err(AppendNrrdErr::SizesDoNotMatch);
+        }
+
+        let slabs = self.slabs + 1;
+        if slabs > i32::MAX as usize {
+            return Err(AppendNrrdErr::TooManySlabs);
+        }
+
+        self.encode(slab);
+
+        let data = self.data.as_mut().unwrap_or(&mut self.header);
+        data.seek(SeekFrom::End(0))?;
+        data.write_all(&self.buffer)?;
+        data.sync_data()?;
+
+        self.header.seek(SeekFrom::Start(self.sizes_offset))?;
+        write!(self.header, "{:<SLAB_COUNT_WIDTH$}", slabs)?;
+        self.header.sync_data()?;
+
+        self.slabs = slabs;
+        Ok(())
+    }
+
+    /// Fills the buffer with `slab` as raw little endian data
+    fn encode<S: Storage<T>>(&mut self, slab: &Image<T, D, S>) {
+        let pixel_size = T::pixel_size();
+        self.buffer.resize(slab.pixels_count() * pixel_size, 0);
+
+        for (pixel, bytes) in slab
+            .pixels()
+            .iter()
+            .zip(self.buffer.chunks_exact_mut(pixel_size))
+        {
+            pixel.to_bytes(bytes, Endian::Little);
+        }
+    }
+
+    /// Number of slabs appended so far
+    #[inline]
+    pub fn slabs(&self) -> usize {
+        self.slabs
+    }
+
+    #[inline]
+    pub fn slab_sizes(&self) -> &[usize; D] {
+        &self.slab_sizes
+    }
+}
+
+/// Drops data past the slabs counted by `sizes` of a file written by `NrrdAppender`,
+/// left behind when appending was interrupted before the header was rewritten
+pub fn recover_appended<P: AsRef<Path>>(header_path: P) -> Result<(), AppendNrrdErr> {
+    let header_path = header_path.as_ref();
+    let mut reader = BufReader::new(File::open(header_path)?);
+    let nrrd = read_header(&mut reader)?;
+    let expected = expected_buffer_size(&nrrd)? as u64;
+
+    let (data_path, data_len) = match data_file_path(&nrrd, header_path) {
+        Some(data_path) => (data_path, expected),
+        None => (
+            header_path.to_path_buf(),
+            reader.stream_position()? + expected,
+        ),
+    };
+
+    let data = OpenOptions::new().write(true).open(data_path)?;
+    if data.metadata()?.len() > data_len {
+        data.set_len(data_len)?;
+        data.sync_data()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{recover_appended, NrrdAppender};
+    use crate::{
+        component::Rgb,
+        image::Image,
+        nrrd::{
+            reader::{read_nrrd_file, ReadNrrdErr},
+            test_util::temp_path,
+        },
+    };
+    use std::{fs::OpenOptions, io::Write};
+
+    #[test]
+    fn appends_scalar_slabs() {
+        let path = temp_path("append_scalar.nrrd");
+        let slab = |t: u16| Image::<u16, 2>::from_fn([3, 2], |[x, y]| (x + 3 * y) as u16 + 100 * t);
+
+        let mut appender = NrrdAppender::create_attached(&path, &slab(0)).unwrap();
+        for t in 1..12 {
+            appender.append(&slab(t)).unwrap();
+        }
+        assert_eq!(appender.slabs(), 12);
+
+        let nrrd = read_nrrd_file(&path).unwrap();
+        assert_eq!(nrrd.sizes(), &[3, 2, 12]);
+
+        let image = Image::<u16, 3>::try_from(&nrrd).unwrap();
+        assert_eq!(image[[2, 1, 11]], slab(11)[[2, 1]]);
+        assert_eq!(image[[0, 1, 4]], slab(4)[[0, 1]]);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn appends_component_slabs() {
+        let header = temp_path("append_rgb.nhdr");
+        let data = temp_path("append_rgb.raw");
+        let slab = |t: u8| {
+            Image::<Rgb<u8>, 2>::from_fn([2, 2], |[x, y]| Rgb {
+                r: x as u8,
+                g: y as u8,
+                b: t,
+            })
+        };
+
+        let mut appender = NrrdAppender::create_detached(&header, &data, &slab(0)).unwrap();
+        appender.append(&slab(1)).unwrap();
+        appender.append(&slab(2)).unwrap();
+
+        let nrrd = read_nrrd_file(&header).unwrap();
+        assert_eq!(nrrd.sizes(), &[3, 2, 2, 3]);
+        assert_eq!(nrrd.field("kinds"), Some("RGB-color domain domain domain"));
+
+        let image = Image::<Rgb<u8>, 3>::try_from(&nrrd).unwrap();
+        assert_eq!(image[[1, 0, 2]], Rgb { r: 1, g: 0, b: 2 });
+
+        std::fs::remove_file(header).unwrap();
+        std::fs::remove_file(data).unwrap();
+    }
+
+    #[test]
+    fn recovers_interrupted_append() {
+        let path = temp_path("append_interrupted.nrrd");
+        let slab = Image::<u8, 1>::from_fn([4], |[x]| x as u8);
+        NrrdAppender::create_attached(&path, &slab).unwrap();
+
+        // Data of the next slab is written but the header is not rewritten
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[9, 9]).unwrap();
+        drop(file);
+
+        let err = read_nrrd_file(&path).unwrap_err();
+        assert!(matches!(err, ReadNrrdErr::Malformed(_)));
+
+        recover_appended(&path).unwrap();
+        let nrrd = read_nrrd_file(&path).unwrap();
+        assert_eq!(nrrd.buffer(), &[0, 1, 2, 3]);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,261 @@
+use crate::nrrd::{
+    reader::{read_buffer, HeaderParser, ReadNrrdErr, ReadOptions},
+    writer::{write_nrrd_with_options, WriteNrrdErr, WriteOptions},
+    Nrrd,
+};
+use std::io::{self, Read, Write};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    runtime::Handle,
+    sync::mpsc,
+    task::JoinHandle,
+};
+
+/// Bytes read from the async reader at once
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Chunks queued between async code and the blocking thread pool, bounds memory of both directions
+const CHUNKS_IN_FLIGHT: usize = 4;
+
+/// Async version of `read_nrrd`
+pub async fn read_nrrd_async<T: AsyncRead + Unpin>(reader: T) -> Result<Nrrd, ReadNrrdErr> {
+    read_nrrd_async_with_options(reader, &ReadOptions::default()).await
+}
+
+/// Async version of `read_nrrd_with_options`.
+/// The payload is streamed to the decoder on the blocking thread pool of tokio,
+/// which bounds decoded data by the size the header implies like the sync reader does.
+pub async fn read_nrrd_async_with_options<T: AsyncRead + Unpin>(
+    reader: T,
+    options: &ReadOptions,
+) -> Result<Nrrd, ReadNrrdErr> {
+    let mut buf_reader = BufReader::new(reader);
+
+    let mut line = String::new();
+    buf_reader.read_line(&mut line).await?;
+    let mut parser = HeaderParser::new(&mut line)?;
+
+    loop {
+        line.clear();
+        buf_reader.read_line(&mut line).await?;
+
+        if !parser.parse_line(&mut line)? {
+            break;
+        }
+    }
+
+    let mut nrrd = parser.finish()?;
+    let progress = options.progress.clone();
+    let (sender, receiver) = mpsc::channel(CHUNKS_IN_FLIGHT);
+
+    let decoder = spawn_blocking(move || {
+        let mut payload = ChunkReader::new(receiver);
+        read_buffer(&mut nrrd, &mut payload, &progress).map(|_| nrrd)
+    })?;
+
+    loop {
+        let mut chunk = vec![0; CHUNK_SIZE];
+        let chunk = match buf_reader.read(&mut chunk).await {
+            Ok(0) => break,
+            Ok(read) => {
+                chunk.truncate(read);
+                Ok(chunk)
+            }
+            Err(err) => Err(err),
+        };
+
+        // Sending fails once the decoder has stopped, e.g. on data past the expected size
+        let failed = chunk.is_err();
+        if sender.send(chunk).await.is_err() || failed {
+            break;
+        }
+    }
+
+    drop(sender);
+    join(decoder).await
+}
+
+/// Async version of `write_nrrd`, see `write_nrrd_async_with_options` for why it takes the `Nrrd`
+pub async fn write_nrrd_async<T: AsyncWrite + Unpin>(
+    nrrd: Nrrd,
+    writer: T,
+) -> Result<(), WriteNrrdErr> {
+    write_nrrd_async_with_options(nrrd, writer, &WriteOptions::default()).await
+}
+
+/// Async version of `write_nrrd_with_options`.
+/// Unlike `write_nrrd` it takes the `Nrrd` by value, because encoding runs on the blocking
+/// thread pool of tokio and has to own the buffer, clone the `Nrrd` to keep it.
+/// Encoded data is streamed to `writer` as it is produced.
+pub async fn write_nrrd_async_with_options<T: AsyncWrite + Unpin>(
+    nrrd: Nrrd,
+    mut writer: T,
+    options: &WriteOptions,
+) -> Result<(), WriteNrrdErr> {
+    let options = options.clone();
+    let (sender, mut receiver) = mpsc::channel(CHUNKS_IN_FLIGHT);
+
+    let encoder = spawn_blocking(move || {
+        write_nrrd_with_options(&nrrd, ChunkWriter { chunks: sender }, &options)
+    })?;
+
+    while let Some(chunk) = receiver.recv().await {
+        if let Err(err) = writer.write_all(&chunk).await {
+            // Encoder stops at its next write once nothing receives chunks
+            drop(receiver);
+            let _ = join(encoder).await;
+            return Err(err.into());
+        }
+    }
+
+    join(encoder).await?;
+    Ok(writer.flush().await?)
+}
+
+/// Spawns `f` on the blocking thread pool, fails instead of panicking outside of a tokio runtime
+fn spawn_blocking<R, F>(f: F) -> io::Result<JoinHandle<R>>
+where
+    R: Send + 'static,
+    F: FnOnce() -> R + Send + 'static,
+{
+    let handle = Handle::try_current().map_err(io::Error::other)?;
+    Ok(handle.spawn_blocking(f))
+}
+
+async fn join<R, E: From<io::Error>>(task: JoinHandle<Result<R, E>>) -> Result<R, E> {
+    task.await.map_err(io::Error::other)?
+}
+
+/// Reads chunks sent from async code, the sender being dropped ends the data
+struct ChunkReader {
+    chunks: mpsc::Receiver<io::Result<Vec<u8>>>,
+    chunk: Vec<u8>,
+    position: usize,
+}
+
+impl ChunkReader {
+    fn new(chunks: mpsc::Receiver<io::Result<Vec<u8>>>) -> Self {
+        Self {
+            chunks,
+            chunk: Vec::new(),
+            position: 0,
+        }
+    }
+}
+
+impl Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.chunk.len() {
+            match self.chunks.blocking_recv() {
+                Some(chunk) => {
+                    self.chunk = chunk?;
+                    self.position = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+
+        let read = buf.len().min(self.chunk.len() - self.position);
+        buf[..read].copy_from_slice(&self.chunk[self.position..self.position + read]);
+        self.position += read;
+        Ok(read)
+    }
+}
+
+/// Sends everything written to async code, fails once nothing receives it
+struct ChunkWriter {
+    chunks: mpsc::Sender<Vec<u8>>,
+}
+
+impl Write for ChunkWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.chunks
+            .blocking_send(buf.to_vec())
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_nrrd_async, read_nrrd_async_with_options, write_nrrd_async};
+    use crate::{
+        image::Image,
+        nrrd::{
+            progress::{CancellationToken, Progress},
+            reader::{ReadNrrdErr, ReadOptions},
+            Encoding, Nrrd,
+        },
+    };
+    use std::sync::{Arc, Mutex};
+
+    fn sample() -> Nrrd {
+        let image = Image::<u16, 3>::from_fn([64, 64, 200], |[x, y, z]| (x * y + z) as u16);
+        Nrrd::from(&image)
+    }
+
+    #[tokio::test]
+    async fn round_trip() {
+        for encoding in [Encoding::Raw, Encoding::GZip] {
+            let mut nrrd = sample();
+            nrrd.set_encoding(encoding.clone());
+
+            let mut file = Vec::new();
+            write_nrrd_async(nrrd.clone(), &mut file).await.unwrap();
+
+            let read = read_nrrd_async(file.as_slice()).await.unwrap();
+            assert_eq!(read.encoding(), &encoding);
+            assert_eq!(read.buffer(), nrrd.buffer());
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_data_past_sizes() {
+        let mut file = b"NRRD0005\ntype: uint8\ndimension: 1\nsizes: 4\nencoding: raw\n\n".to_vec();
+        file.extend_from_slice(&[0; 1_000_000]);
+
+        let err = read_nrrd_async(file.as_slice()).await.unwrap_err();
+        assert!(matches!(err, ReadNrrdErr::Malformed(_)));
+    }
+
+    #[tokio::test]
+    async fn reports_progress_and_cancels() {
+        let nrrd = sample();
+        let mut file = Vec::new();
+        write_nrrd_async(nrrd.clone(), &mut file).await.unwrap();
+
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let callback_reports = reports.clone();
+        let options = ReadOptions {
+            progress: Progress {
+                callback: Some(Arc::new(move |done, total| {
+                    callback_reports.lock().unwrap().push((done, total))
+                })),
+                cancellation: None,
+            },
+        };
+        read_nrrd_async_with_options(file.as_slice(), &options)
+            .await
+            .unwrap();
+
+        let total = nrrd.buffer().len() as u64;
+        assert_eq!(reports.lock().unwrap().last(), Some(&(total, total)));
+
+        let token = CancellationToken::new();
+        token.cancel();
+        let options = ReadOptions {
+            progress: Progress {
+                callback: None,
+                cancellation: Some(token),
+            },
+        };
+        let err = read_nrrd_async_with_options(file.as_slice(), &options)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ReadNrrdErr::Cancelled));
+    }
+}
@@ -0,0 +1,332 @@
+use crate::nrrd::{gzip, Encoding};
+use std::{
+    error::Error,
+    fmt,
+    io::{self, Read, Write},
+    sync::{Arc, LazyLock, PoisonError, RwLock},
+};
+
+/// Encoding of pixel data, looked up by the name in the `encoding` field
+pub trait Codec: Send + Sync {
+    /// Names accepted in the `encoding` field
+    fn names(&self) -> &[&str];
+
+    /// Decodes `input` until its end, writing raw pixel data to `output`.
+    /// `size` is the number of raw bytes the header implies, output beyond it is rejected
+    /// by the reader, so decoders can stop early instead of inflating the rest.
+    fn decode(&self, input: &mut dyn Read, output: &mut dyn Write, size: usize) -> io::Result<()>;
+
+    /// Encodes raw pixel data read from `input` until its end
+    fn encode(&self, input: &mut dyn Read, output: &mut dyn Write) -> io::Result<()>;
+
+    /// Encodings that are not part of the NRRD format are written only when explicitly allowed.
+    /// By default a codec is standard when one of its names is an encoding the format defines.
+    fn is_standard(&self) -> bool {
+        self.names().iter().any(|name| {
+            STANDARD_NAMES
+                .iter()
+                .any(|standard| standard.eq_ignore_ascii_case(name))
+        })
+    }
+}
+
+/// Values of the `encoding` field defined by the NRRD format
+const STANDARD_NAMES: &[&str] = &[
+    "raw", "ascii", "text", "txt", "hex", "gzip", "gz", "bzip2", "bz2",
+];
+
+static REGISTRY: LazyLock<RwLock<Vec<Arc<dyn Codec>>>> = LazyLock::new(|| {
+    let codecs: Vec<Arc<dyn Codec>> = vec![
+        Arc::new(GzipCodec),
+        #[cfg(feature = "zstd")]
+        Arc::new(ZstdCodec::default()),
+    ];
+
+    RwLock::new(codecs)
+});
+
+/// Makes codec available to the reader and writer.
+/// Codecs registered later take precedence for names they share with earlier ones.
+pub fn register_codec<C: Codec + 'static>(codec: C) {
+    REGISTRY
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .push(Arc::new(codec));
+}
+
+/// Finds codec for encoding, `None` for raw data or when nothing is registered under its name
+pub fn find_codec(encoding: &Encoding) -> Option<Arc<dyn Codec>> {
+    if *encoding == Encoding::Raw {
+        return None;
+    }
+
+    let aliases = encoding_names(encoding);
+    let codecs = REGISTRY.read().unwrap_or_else(PoisonError::into_inner);
+
+    codecs
+        .iter()
+        .rev()
+        .find(|codec| {
+            codec
+                .names()
+                .iter()
+                .any(|n| aliases.iter().any(|a| a.eq_ignore_ascii_case(n)))
+        })
+        .cloned()
+}
+
+/// Every name the `encoding` field may use for `encoding`
+fn encoding_names(encoding: &Encoding) -> Vec<&str> {
+    match encoding {
+        Encoding::Raw => vec!["raw"],
+        Encoding::Ascii => vec!["ascii", "text", "txt"],
+        Encoding::GZip => vec!["gzip", "gz"],
+        Encoding::BZip2 => vec!["bzip2", "bz2"],
+        Encoding::Other(name) => vec![name.as_str()],
+    }
+}
+
+/// Error inside `std::io::Error` returned when decoded data is larger than the header implies
+#[derive(Debug)]
+pub(crate) struct Oversized;
+
+impl fmt::Display for Oversized {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Decoded pixel data is larger than sizes imply")
+    }
+}
+
+impl Error for Oversized {}
+
+impl Oversized {
+    #[inline]
+    pub(crate) fn error() -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, Oversized)
+    }
+
+    pub(crate) fn is(err: &io::Error) -> bool {
+        err.get_ref().is_some_and(|e| e.is::<Oversized>())
+    }
+}
+
+/// Writer that fails with `Oversized` once more than `remaining` bytes are written to it
+pub(crate) struct Limited<W> {
+    inner: W,
+    remaining: usize,
+}
+
+impl<W> Limited<W> {
+    #[inline]
+    pub(crate) fn new(inner: W, limit: usize) -> Self {
+        Self {
+            inner,
+            remaining: limit,
+        }
+    }
+}
+
+impl<W: Write> Write for Limited<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.len() > self.remaining {
+            return Err(Oversized::error());
+        }
+
+        let written = self.inner.write(buf)?;
+        self.remaining -= written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+struct GzipCodec;
+
+impl Codec for GzipCodec {
+    fn names(&self) -> &[&str] {
+        &["gzip", "gz"]
+    }
+
+    fn decode(&self, input: &mut dyn Read, output: &mut dyn Write, size: usize) -> io::Result<()> {
+        gzip::decode(input, output, size)
+    }
+
+    fn encode(&self, input: &mut dyn Read, output: &mut dyn Write) -> io::Result<()> {
+        gzip::encode(input, output)
+    }
+}
+
+/// Zstandard encoding, which is not part of the NRRD format and is understood only by some forks.
+/// Register a configured instance to change compression settings.
+#[cfg(feature = "zstd")]
+#[derive(Debug, Clone)]
+pub struct ZstdCodec {
+    level: i32,
+    workers: u32,
+}
+
+#[cfg(feature = "zstd")]
+impl ZstdCodec {
+    pub fn new(level: i32) -> Self {
+        Self { level, workers: 0 }
+    }
+
+    /// Compresses on `workers` background threads, 0 compresses on the calling thread
+    pub fn with_workers(mut self, workers: u32) -> Self {
+        self.workers = workers;
+        self
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl Default for ZstdCodec {
+    fn default() -> Self {
+        Self::new(zstd::DEFAULT_COMPRESSION_LEVEL)
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl Codec for ZstdCodec {
+    fn names(&self) -> &[&str] {
+        &["zstd", "zst"]
+    }
+
+    fn decode(&self, input: &mut dyn Read, output: &mut dyn Write, _: usize) -> io::Result<()> {
+        zstd::stream::copy_decode(input, output)
+    }
+
+    fn encode(&self, input: &mut dyn Read, output: &mut dyn Write) -> io::Result<()> {
+        let mut encoder = zstd::stream::write::Encoder::new(output, self.level)?;
+        if self.workers > 0 {
+            encoder.multithread(self.workers)?;
+        }
+
+        io::copy(input, &mut encoder)?;
+        encoder.finish()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_codec, register_codec, Codec};
+    use crate::{
+        image::Image,
+        nrrd::{
+            reader::read_nrrd,
+            writer::{write_nrrd, write_nrrd_with_options, WriteNrrdErr, WriteOptions},
+            Encoding, Nrrd,
+        },
+    };
+    use std::io::{self, Read, Write};
+
+    /// Flips every bit, registered only by the test that uses it
+    struct InvertCodec;
+
+    impl Codec for InvertCodec {
+        fn names(&self) -> &[&str] {
+            &["invert-test"]
+        }
+
+        fn decode(&self, input: &mut dyn Read, output: &mut dyn Write, _: usize) -> io::Result<()> {
+            self.encode(input, output)
+        }
+
+        fn encode(&self, input: &mut dyn Read, output: &mut dyn Write) -> io::Result<()> {
+            let mut data = Vec::new();
+            input.read_to_end(&mut data)?;
+            data.iter_mut().for_each(|byte| *byte = !*byte);
+            output.write_all(&data)
+        }
+    }
+
+    fn sample() -> Nrrd {
+        let image = Image::<u16, 2>::from_fn([5, 4], |[x, y]| (x * 300 + y) as u16);
+        Nrrd::from(&image)
+    }
+
+    #[test]
+    fn finds_codecs_by_alias() {
+        assert!(find_codec(&Encoding::Raw).is_none());
+
+        let gzip = find_codec(&Encoding::GZip).unwrap();
+        assert!(gzip.is_standard());
+        for name in ["gz", "GZip"] {
+            let codec = find_codec(&Encoding::Other(name.to_string())).unwrap();
+            assert_eq!(codec.names(), gzip.names());
+        }
+
+        assert!(find_codec(&Encoding::BZip2).is_none());
+        assert!(find_codec(&Encoding::Other("unknown".to_string())).is_none());
+    }
+
+    #[test]
+    fn custom_codec_round_trip() {
+        let nrrd = sample();
+        let mut encoded = nrrd.clone();
+        encoded.set_encoding(Encoding::Other("invert-test".to_string()));
+
+        assert!(matches!(
+            write_nrrd(&encoded, Vec::new()),
+            Err(WriteNrrdErr::UnsupportedEncoding(_))
+        ));
+
+        register_codec(InvertCodec);
+        assert!(!find_codec(encoded.encoding()).unwrap().is_standard());
+        assert!(matches!(
+            write_nrrd(&encoded, Vec::new()),
+            Err(WriteNrrdErr::NonStandardEncoding(_))
+        ));
+
+        let options = WriteOptions {
+            allow_non_standard_encoding: true,
+            ..Default::default()
+        };
+        let mut file = Vec::new();
+        write_nrrd_with_options(&encoded, &mut file, &options).unwrap();
+
+        let read = read_nrrd(file.as_slice()).unwrap();
+        assert_eq!(read.encoding(), encoded.encoding());
+        assert_eq!(read.buffer(), nrrd.buffer());
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn zstd_round_trip() {
+        let nrrd = sample();
+        let mut encoded = nrrd.clone();
+        encoded.set_encoding(Encoding::Other("zstd".to_string()));
+
+        assert!(matches!(
+            write_nrrd(&encoded, Vec::new()),
+            Err(WriteNrrdErr::NonStandardEncoding(_))
+        ));
+
+        let options = WriteOptions {
+            allow_non_standard_encoding: true,
+            ..Default::default()
+        };
+        let mut file = Vec::new();
+        write_nrrd_with_options(&encoded, &mut file, &options).unwrap();
+        assert!(file.starts_with(b"NRRD0005\n# encoding 'zstd' is not part of NRRD format\n"));
+
+        let read = read_nrrd(file.as_slice()).unwrap();
+        assert_eq!(read.encoding(), &Encoding::Other("zstd".to_string()));
+        assert_eq!(read.buffer(), nrrd.buffer());
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn zstd_output_is_bounded_by_sizes() {
+        let mut file =
+            b"NRRD0005\ntype: uint8\ndimension: 1\nsizes: 10\nencoding: zst\n\n".to_vec();
+        file.extend(zstd::encode_all(&[7u8; 11][..], 0).unwrap());
+
+        let err = read_nrrd(file.as_slice()).unwrap_err();
+        assert!(matches!(
+            err,
+            crate::nrrd::reader::ReadNrrdErr::Malformed(_)
+        ));
+    }
+}
@@ -0,0 +1,349 @@
+#[cfg(feature = "mmap")]
+use crate::{image::check_pod_layout, pixel::PodPixel, storage::MmapStorage};
+use crate::{
+    image::{fixed_sizes, image_sizes, Image, ImageFromNrrdErr},
+    nrrd::{
+        gzip_index::GzipIndex,
+        reader::{data_file_path, expected_buffer_size, read_header, ReadNrrdErr},
+        Encoding, Nrrd,
+    },
+    pixel::PixelValue,
+};
+use std::{
+    fs::File,
+    io::{BufReader, ErrorKind, Read, Seek, SeekFrom},
+    path::Path,
+    time::UNIX_EPOCH,
+};
+
+/// NRRD opened for random access.
+///
+/// Only the header is read on open, pixel data is read on demand.
+/// Gzip payloads are accessed through an index that is built on first use,
+/// or loaded from a sidecar file with `open_with_index_cache`.
+pub struct NrrdFile {
+    header: Nrrd,
+    data: File,
+    data_offset: u64,
+    data_size: u64,
+    index: Option<GzipIndex>,
+}
+
+impl NrrdFile {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, ReadNrrdErr> {
+        let path = path.as_ref();
+        let mut buf_reader = BufReader::new(File::open(path)?);
+        let header = read_header(&mut buf_reader)?;
+
+        let (mut data, data_offset) = match data_file_path(&header, path) {
+            Some(data_path) => (File::open(data_path)?, 0),
+            None => {
+                let offset = buf_reader.stream_position()?;
+                (buf_reader.into_inner(), offset)
+            }
+        };
+
+        let data_size = data.seek(SeekFrom::End(0))? - data_offset;
+
+        match header.encoding() {
+            Encoding::Raw | Encoding::GZip => (),
+            other => return Err(ReadNrrdErr::UnsupportedEncoding(other.clone())),
+        };
+
+        Ok(Self {
+            header,
+            data,
+            data_offset,
+            data_size,
+            index: None,
+        })
+    }
+
+    /// Opens file and loads gzip index from `index_path`.
+    /// Index is built and saved there when the file is missing or was made for other data.
+    pub fn open_with_index_cache<P: AsRef<Path>, Q: AsRef<Path>>(
+        path: P,
+        index_path: Q,
+    ) -> Result<Self, ReadNrrdErr> {
+        let mut file = Self::open(path)?;
+        if *file.header.encoding() != Encoding::GZip {
+            return Ok(file);
+        }
+
+        // Missing and stale sidecars are rebuilt, other errors are reported
+        let index_path = index_path.as_ref();
+        match file.load_index(index_path) {
+            Ok(true) => return Ok(file),
+            Ok(false) => (),
+            Err(ReadNrrdErr::IOError(err)) if err.kind() == ErrorKind::NotFound => (),
+            Err(err) => return Err(err),
+        }
+
+        file.save_index(index_path)?;
+        Ok(file)
+    }
+
+    /// Header of the file for its fields, sizes and pixel type.
+    /// Pixel data is not part of it: its buffer is always empty, so converting it to an image
+    /// fails with a buffer size mismatch. Use `read_slice` or `read_bytes` for pixels.
+    #[inline]
+    pub fn header(&self) -> &Nrrd {
+        &self.header
+    }
+
+    /// Builds gzip index if it is not built yet, does nothing for raw files
+    pub fn build_index(&mut self) -> Result<(), ReadNrrdErr> {
+        if self.index.is_none() && *self.header.encoding() == Encoding::GZip {
+            self.data.seek(SeekFrom::Start(self.data_offset))?;
+            let modified = modified_time(&self.data)?;
+            self.index = Some(GzipIndex::build(&mut self.data, self.data_size, modified)?);
+        }
+
+        Ok(())
+    }
+
+    /// Writes gzip index to a sidecar file, building it first if needed
+    pub fn save_index<P: AsRef<Path>>(&mut self, path: P) -> Result<(), ReadNrrdErr> {
+        self.build_index()?;
+
+        if let Some(index) = &self.index {
+            let mut file = File::create(path)?;
+            index.write_to(&mut file)?;
+        }
+
+        Ok(())
+    }
+
+    /// Loads gzip index from a sidecar file.
+    /// Returns `false` and keeps the current index if the sidecar was made for other data
+    /// or for a different version of it.
+    pub fn load_index<P: AsRef<Path>>(&mut self, path: P) -> Result<bool, ReadNrrdErr> {
+        let index = GzipIndex::read_from(&mut BufReader::new(File::open(path)?))?;
+
+        let modified = modified_time(&self.data)?;
+        self.data.seek(SeekFrom::Start(self.data_offset))?;
+        if !index.matches(&mut self.data, self.data_size, modified)? {
+            return Ok(false);
+        }
+
+        self.index = Some(index);
+        Ok(true)
+    }
+
+    /// Fills `buffer` with decoded pixel data starting at byte `offset`
+    pub fn read_bytes(&mut self, offset: u64, buffer: &mut [u8]) -> Result<(), ReadNrrdErr> {
+        let size = expected_buffer_size(&self.header)? as u64;
+        let end = offset.checked_add(buffer.len() as u64);
+        if end.is_none_or(|end| end > size) {
+            return Err(ReadNrrdErr::Malformed(
+                "Read past the end of buffer".to_string(),
+            ));
+        }
+
+        self.build_index()?;
+        self.data.seek(SeekFrom::Start(self.data_offset))?;
+
+        match &self.index {
+            Some(index) => index.read(&mut self.data, offset, buffer)?,
+            None => {
+                self.data.seek(SeekFrom::Current(offset as i64))?;
+                self.data.read_exact(buffer)?;
+            }
+        };
+
+        Ok(())
+    }
+
+    /// Reads one slice along the slowest axis
+    pub fn read_slice<T: PixelValue, const D: usize>(
+        &mut self,
+        index: usize,
+    ) -> Result<Image<T, D>, ImageFromNrrdErr> {
+        let nrrd_sizes = image_sizes::<T>(
+            self.header.pixel_type(),
+            self.header.sizes(),
+            self.header.field("kinds"),
+        )?;
+        let (&slices, nrrd_sizes) = nrrd_sizes
+            .split_last()
+            .ok_or(ImageFromNrrdErr::DimensionsDoNotMatch)?;
+        let sizes = fixed_sizes(nrrd_sizes)?;
+
+        if index >= slices as usize {
+            return Err(ImageFromNrrdErr::IndexOutOfBounds);
+        }
+
+        let slice_size = sizes.iter().product::<usize>() * T::pixel_size();
+        let mut bytes = vec![0; slice_size];
+        self.read_bytes((index * slice_size) as u64, &mut bytes)?;
+
+        Image::from_bytes(sizes, &bytes, self.header.endian())
+    }
+
+    /// Maps pixel data into memory instead of reading it.
+    /// Data must be raw, in native byte order and aligned for `T` within the file.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while the image is alive,
+    /// otherwise its pixels may change underneath or become inaccessible.
+    #[cfg(feature = "mmap")]
+    pub unsafe fn map_image<T: PodPixel, const D: usize>(
+        &self,
+    ) -> Result<Image<T, D, MmapStorage<T>>, ImageFromNrrdErr> {
+        let sizes = image_sizes::<T>(
+            self.header.pixel_type(),
+            self.header.sizes(),
+            self.header.field("kinds"),
+        )?;
+        let sizes = fixed_sizes(sizes)?;
+
+        if *self.header.encoding() != Encoding::Raw {
+            return Err(ImageFromNrrdErr::UnsupportedEncoding);
+        }
+
+        let pixels_count = sizes.iter().product::<usize>();
+        let len = pixels_count * std::mem::size_of::<T>();
+        if (len as u64) > self.data_size {
+            let err = ReadNrrdErr::Malformed("Buffer size mismatch".to_string());
+            return Err(ImageFromNrrdErr::CannotReadNrrd(err));
+        }
+
+        // SAFETY: caller guarantees the file is not modified while mapped
+        let map = unsafe { memmap2::Mmap::map(&self.data) }.map_err(ReadNrrdErr::from)?;
+        let offset = self.data_offset as usize;
+        check_pod_layout::<T>(
+            self.header.encoding(),
+            self.header.endian(),
+            &map[offset..offset + len],
+            pixels_count,
+        )?;
+
+        // SAFETY: pixels were checked to be within the map and aligned
+        let storage = unsafe { MmapStorage::new(map, offset, pixels_count) };
+        Ok(Image::from_storage(storage, sizes).expect("pixels count matches sizes"))
+    }
+}
+
+/// Modification time of `file` in nanoseconds since the epoch, 0 where the platform has none
+fn modified_time(file: &File) -> Result<u64, ReadNrrdErr> {
+    let modified = file.metadata()?.modified().ok();
+    let since_epoch = modified.and_then(|time| time.duration_since(UNIX_EPOCH).ok());
+    Ok(since_epoch.map_or(0, |duration| duration.as_nanos() as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NrrdFile;
+    use crate::{
+        image::{Image, ImageFromNrrdErr},
+        nrrd::{reader::ReadNrrdErr, test_util::temp_path, writer::write_nrrd, Encoding, Nrrd},
+    };
+    use flate2::{write::GzEncoder, Compression};
+    use std::{fs::File, io::Write, path::PathBuf};
+
+    /// Slices large enough for several access points per file
+    fn volume() -> Image<u32, 3> {
+        Image::from_fn([300, 200, 16], |[x, y, z]| (x ^ (y * 7) ^ (z * 131)) as u32)
+    }
+
+    fn slice(image: &Image<u32, 3>, z: usize) -> Vec<u32> {
+        image.slice::<2>(2, z).unwrap().into_vec()
+    }
+
+    fn write_gzip(path: &PathBuf, image: &Image<u32, 3>) {
+        let mut nrrd = Nrrd::from(image);
+        nrrd.set_encoding(Encoding::GZip);
+        write_nrrd(&nrrd, File::create(path).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn reads_slices_of_gzip_members() {
+        let path = temp_path("file_members.nrrd");
+        let image = volume();
+        write_gzip(&path, &image);
+
+        let mut file = NrrdFile::open(&path).unwrap();
+        for z in [15, 0, 7] {
+            let read = file.read_slice::<u32, 2>(z).unwrap();
+            assert_eq!(read.into_vec(), slice(&image, z));
+        }
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn reads_slices_of_foreign_gzip() {
+        let path = temp_path("file_foreign.nrrd");
+        let image = volume();
+        let nrrd = Nrrd::from(&image);
+
+        let mut file = File::create(&path).unwrap();
+        let header = "NRRD0004\ntype: uint32\ndimension: 3\nsizes: 300 200 16\n";
+        write!(file, "{header}endian: little\nencoding: gzip\n\n").unwrap();
+        let mut encoder = GzEncoder::new(file, Compression::default());
+        encoder.write_all(nrrd.buffer()).unwrap();
+        encoder.finish().unwrap();
+
+        let mut file = NrrdFile::open(&path).unwrap();
+        for z in [12, 3] {
+            let read = file.read_slice::<u32, 2>(z).unwrap();
+            assert_eq!(read.into_vec(), slice(&image, z));
+        }
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn index_sidecar_round_trip() {
+        let path = temp_path("file_sidecar.nrrd");
+        let index_path = temp_path("file_sidecar.nrrd.gzi");
+        let image = volume();
+        write_gzip(&path, &image);
+
+        // The first open builds and saves the index, the next one loads it
+        NrrdFile::open_with_index_cache(&path, &index_path).unwrap();
+        assert!(index_path.exists());
+
+        let mut file = NrrdFile::open(&path).unwrap();
+        assert!(file.load_index(&index_path).unwrap());
+        let read = file.read_slice::<u32, 2>(9).unwrap();
+        assert_eq!(read.into_vec(), slice(&image, 9));
+
+        // Sidecars of other data are not used
+        write_gzip(&path, &volume().map(|p| p + 1));
+        let mut file = NrrdFile::open(&path).unwrap();
+        assert!(!file.load_index(&index_path).unwrap());
+
+        std::fs::remove_file(path).unwrap();
+        std::fs::remove_file(index_path).unwrap();
+    }
+
+    #[test]
+    fn rejects_reads_past_the_buffer() {
+        let path = temp_path("file_bounds.nrrd");
+        let image = Image::<u16, 2>::from_fn([4, 3], |[x, y]| (x + y) as u16);
+        write_nrrd(&Nrrd::from(&image), File::create(&path).unwrap()).unwrap();
+
+        let mut file = NrrdFile::open(&path).unwrap();
+        let mut buffer = [0; 4];
+        assert!(file.read_bytes(u64::MAX - 1, &mut buffer).is_err());
+        assert!(file.read_bytes(22, &mut buffer).is_err());
+        file.read_bytes(20, &mut buffer).unwrap();
+        assert_eq!(buffer, [4, 0, 5, 0]);
+
+        assert!(matches!(
+            file.read_slice::<u16, 1>(3),
+            Err(ImageFromNrrdErr::IndexOutOfBounds)
+        ));
+
+        // The header has no pixels
+        let err = Image::<u16, 2>::try_from(file.header()).unwrap_err();
+        assert!(matches!(
+            err,
+            ImageFromNrrdErr::CannotReadNrrd(ReadNrrdErr::Malformed(_))
+        ));
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
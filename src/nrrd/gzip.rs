@@ -0,0 +1,310 @@
+//! Gzip payload encoding.
+//!
+//! Payloads are written as a sequence of gzip members, one per `MEMBER_SIZE` bytes of input.
+//! Every member carries its own compressed size in an `RN` extra subfield, so members can be
+//! located without inflating and decoded independently. Any gzip reader accepts the result,
+//! since multi-member streams are part of the gzip format and unknown subfields are skipped.
+
+use crate::nrrd::codec::Oversized;
+use flate2::{
+    read::{GzDecoder, MultiGzDecoder},
+    write::DeflateEncoder,
+    Compression, Crc,
+};
+use std::io::{self, Read, Write};
+
+/// Uncompressed bytes per gzip member
+pub(crate) const MEMBER_SIZE: usize = 1024 * 1024;
+
+const HEADER_SIZE: usize = 10;
+const EXTRA_SIZE: usize = 2 + 8; // XLEN + one subfield with 4 byte payload
+const TRAILER_SIZE: usize = 8;
+const SUBFIELD_ID: [u8; 2] = *b"RN";
+const FLAG_EXTRA: u8 = 0x04;
+
+pub(crate) fn encode(input: &mut dyn Read, output: &mut dyn Write) -> io::Result<()> {
+    let batch_size = (batch_members() * MEMBER_SIZE) as u64;
+    let mut data = Vec::new();
+
+    loop {
+        data.clear();
+        input.take(batch_size).read_to_end(&mut data)?;
+
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        for member in encode_members(&data)? {
+            output.write_all(&member)?;
+        }
+    }
+}
+
+#[cfg(feature = "parallel")]
+fn encode_members(data: &[u8]) -> io::Result<Vec<Vec<u8>>> {
+    use rayon::prelude::*;
+
+    data.par_chunks(MEMBER_SIZE).map(encode_member).collect()
+}
+
+#[cfg(not(feature = "parallel"))]
+fn encode_members(data: &[u8]) -> io::Result<Vec<Vec<u8>>> {
+    data.chunks(MEMBER_SIZE).map(encode_member).collect()
+}
+
+fn encode_member(chunk: &[u8]) -> io::Result<Vec<u8>> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(chunk)?;
+    let deflated = encoder.finish()?;
+
+    let member_size = HEADER_SIZE + EXTRA_SIZE + deflated.len() + TRAILER_SIZE;
+    let mut crc = Crc::new();
+    crc.update(chunk);
+
+    let mut member = Vec::with_capacity(member_size);
+    // ID1, ID2, CM = deflate, FLG, MTIME = 0, XFL = 0, OS = unknown
+    member.extend_from_slice(&[0x1f, 0x8b, 8, FLAG_EXTRA, 0, 0, 0, 0, 0, 255]);
+    member.extend_from_slice(&8u16.to_le_bytes());
+    member.extend_from_slice(&SUBFIELD_ID);
+    member.extend_from_slice(&4u16.to_le_bytes());
+    member.extend_from_slice(&(member_size as u32).to_le_bytes());
+    member.extend_from_slice(&deflated);
+    member.extend_from_slice(&crc.sum().to_le_bytes());
+    member.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+
+    Ok(member)
+}
+
+/// Decodes members that carry their size in batches, falls back to sequential
+/// decoding of the rest of the stream at the first member that does not.
+/// Sizes stored in the stream are trusted only up to the `size` raw bytes the header implies.
+pub(crate) fn decode(input: &mut dyn Read, output: &mut dyn Write, size: usize) -> io::Result<()> {
+    let mut batch = Vec::new();
+    // Raw bytes not claimed yet by members read so far
+    let mut remaining = size;
+
+    loop {
+        let mut member = Vec::new();
+        input
+            .take(HEADER_SIZE as u64 + 2)
+            .read_to_end(&mut member)?;
+
+        if member.is_empty() {
+            return decode_members(&batch, output);
+        }
+
+        if member.len() == HEADER_SIZE + 2 {
+            let extra_len = u16::from_le_bytes([member[10], member[11]]) as u64;
+            input.take(extra_len).read_to_end(&mut member)?;
+        }
+
+        match member_size(&member) {
+            Some(size) if size >= member.len() + TRAILER_SIZE => {
+                // Reading through `take` allocates only for data that is actually there
+                let rest = (size - member.len()) as u64;
+                input.take(rest).read_to_end(&mut member)?;
+                if member.len() != size {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "gzip member is truncated",
+                    ));
+                }
+
+                remaining = remaining
+                    .checked_sub(decoded_size(&member))
+                    .ok_or_else(Oversized::error)?;
+                batch.push(member);
+
+                if batch.len() == batch_members() {
+                    decode_members(&batch, output)?;
+                    batch.clear();
+                }
+            }
+            _ => {
+                decode_members(&batch, output)?;
+                let rest = MultiGzDecoder::new(member.as_slice().chain(input));
+                let decoded = io::copy(&mut rest.take(remaining as u64 + 1), output)?;
+                if decoded > remaining as u64 {
+                    return Err(Oversized::error());
+                }
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(feature = "parallel")]
+fn decode_members(members: &[Vec<u8>], output: &mut dyn Write) -> io::Result<()> {
+    use rayon::prelude::*;
+
+    let decoded = members
+        .par_iter()
+        .map(|m| decode_member(m))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    for data in decoded {
+        output.write_all(&data)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(not(feature = "parallel"))]
+fn decode_members(members: &[Vec<u8>], output: &mut dyn Write) -> io::Result<()> {
+    for member in members {
+        output.write_all(&decode_member(member)?)?;
+    }
+
+    Ok(())
+}
+
+/// Uncompressed size from the trailer of a complete member
+#[inline]
+fn decoded_size(member: &[u8]) -> usize {
+    let isize_bytes = member[member.len() - 4..].try_into().unwrap();
+    u32::from_le_bytes(isize_bytes) as usize
+}
+
+fn decode_member(member: &[u8]) -> io::Result<Vec<u8>> {
+    let expected = decoded_size(member);
+    let mut data = Vec::with_capacity(expected.min(MEMBER_SIZE));
+
+    // Reading to the end verifies the trailer, one byte more detects members larger than declared
+    GzDecoder::new(member)
+        .take(expected as u64 + 1)
+        .read_to_end(&mut data)?;
+
+    if data.len() != expected {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "gzip member size does not match its trailer",
+        ));
+    }
+
+    Ok(data)
+}
+
+/// Members processed at once, one per thread
+fn batch_members() -> usize {
+    #[cfg(feature = "parallel")]
+    return rayon::current_num_threads();
+
+    #[cfg(not(feature = "parallel"))]
+    return 1;
+}
+
+/// Size of the member from its size subfield, `data` must start with the member header.
+/// Returns `None` if there is no such subfield, e.g. when the payload was written by another tool.
+pub(crate) fn member_size(data: &[u8]) -> Option<usize> {
+    let header = data.get(..HEADER_SIZE + 2)?;
+    if header[..3] != [0x1f, 0x8b, 8] || header[3] & FLAG_EXTRA == 0 {
+        return None;
+    }
+
+    let extra_len = u16::from_le_bytes([header[10], header[11]]) as usize;
+    let mut extra = data.get(HEADER_SIZE + 2..HEADER_SIZE + 2 + extra_len)?;
+
+    while extra.len() >= 4 {
+        let len = u16::from_le_bytes([extra[2], extra[3]]) as usize;
+        let payload = extra.get(4..4 + len)?;
+
+        if extra[..2] == SUBFIELD_ID && len == 4 {
+            return Some(u32::from_le_bytes(payload.try_into().ok()?) as usize);
+        }
+
+        extra = &extra[4 + len..];
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode, member_size, MEMBER_SIZE};
+    use crate::{
+        image::Image,
+        nrrd::{reader::read_nrrd, writer::write_nrrd, Encoding, Nrrd},
+    };
+    use flate2::{read::MultiGzDecoder, write::GzEncoder, Compression};
+    use std::io::{Read, Write};
+
+    fn sample(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i / 7 % 251) as u8).collect()
+    }
+
+    fn encoded(data: &[u8]) -> Vec<u8> {
+        let mut output = Vec::new();
+        encode(&mut &data[..], &mut output).unwrap();
+        output
+    }
+
+    #[test]
+    fn round_trip_of_several_members() {
+        let data = sample(2 * MEMBER_SIZE + 1000);
+        let encoded = encoded(&data);
+
+        // Members follow each other at the sizes they record
+        let mut members = 0;
+        let mut rest = encoded.as_slice();
+        while !rest.is_empty() {
+            let size = member_size(rest).unwrap();
+            rest = &rest[size..];
+            members += 1;
+        }
+        assert_eq!(members, 3);
+
+        let mut decoded = Vec::new();
+        decode(&mut encoded.as_slice(), &mut decoded, data.len()).unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn other_gzip_readers_accept_members() {
+        let data = sample(MEMBER_SIZE + 10);
+        let encoded = encoded(&data);
+
+        let mut decoded = Vec::new();
+        MultiGzDecoder::new(encoded.as_slice())
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn decodes_members_without_size() {
+        let data = sample(5000);
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&data).unwrap();
+        let mut encoded = encoded(&data[..100]);
+        encoded.extend(encoder.finish().unwrap());
+
+        let mut decoded = Vec::new();
+        decode(&mut encoded.as_slice(), &mut decoded, 5100).unwrap();
+        assert_eq!(decoded[..100], data[..100]);
+        assert_eq!(decoded[100..], data);
+    }
+
+    #[test]
+    fn rejects_more_data_than_header_implies() {
+        let data = sample(1000);
+        let encoded = encoded(&data);
+
+        let mut decoded = Vec::new();
+        assert!(decode(&mut encoded.as_slice(), &mut decoded, 999).is_err());
+    }
+
+    #[test]
+    fn round_trip_through_nrrd() {
+        let image = Image::<u32, 2>::from_fn([700, 500], |[x, y]| (x * y) as u32);
+        let mut nrrd = Nrrd::from(&image);
+        nrrd.set_encoding(Encoding::GZip);
+
+        let mut file = Vec::new();
+        write_nrrd(&nrrd, &mut file).unwrap();
+        assert!(file.len() < nrrd.buffer().len());
+
+        let read = read_nrrd(file.as_slice()).unwrap();
+        assert_eq!(read.encoding(), &Encoding::GZip);
+        assert_eq!(read.buffer(), nrrd.buffer());
+    }
+}
@@ -0,0 +1,379 @@
+use crate::nrrd::{
+    gzip,
+    inflate::{inflate_block, BitReader, WINDOW_SIZE},
+};
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use std::io::{self, Error, ErrorKind, Read, Seek, SeekFrom, Write};
+
+/// Uncompressed distance between access points when the payload has to be scanned
+const SPAN: u64 = 1024 * 1024;
+
+/// History is trimmed back to `WINDOW_SIZE` once it grows past this
+const HISTORY_LIMIT: usize = 8 * WINDOW_SIZE;
+
+const MAGIC: &[u8; 8] = b"NRRDGZI2";
+
+const FLAG_HCRC: u8 = 0x02;
+const FLAG_EXTRA: u8 = 0x04;
+const FLAG_NAME: u8 = 0x08;
+const FLAG_COMMENT: u8 = 0x10;
+
+/// Position in the gzip payload where decoding can start without inflating anything before it
+#[derive(Debug, Clone)]
+struct AccessPoint {
+    bit_offset: u64, // Start of a deflate block
+    decoded_offset: u64,
+    window: Vec<u8>, // Output preceding the point, empty at the start of a member
+}
+
+/// Zran-style index of access points into a gzip payload
+#[derive(Debug, Clone)]
+pub(crate) struct GzipIndex {
+    points: Vec<AccessPoint>,
+    payload_size: u64,
+    modified: u64, // Modification time of the data, nanoseconds since the epoch or 0 if unknown
+    trailer: [u8; 8], // Last CRC and ISIZE, used to detect stale sidecar files
+}
+
+impl GzipIndex {
+    /// Builds an index of the payload that starts at the current position of `source`,
+    /// whose data was last modified at `modified`.
+    /// Payloads written by this crate are indexed from member headers alone,
+    /// others are inflated once from start to end.
+    pub fn build<R: Read + Seek>(
+        source: &mut R,
+        payload_size: u64,
+        modified: u64,
+    ) -> io::Result<Self> {
+        let start = source.stream_position()?;
+        let trailer = read_trailer(source, start, payload_size)?;
+
+        source.seek(SeekFrom::Start(start))?;
+        let points = match member_points(source, start, payload_size)? {
+            Some(points) => points,
+            None => {
+                source.seek(SeekFrom::Start(start))?;
+                scan_points(source.take(payload_size))?
+            }
+        };
+
+        Ok(Self {
+            points,
+            payload_size,
+            modified,
+            trailer,
+        })
+    }
+
+    /// Returns `true` if the index was built for the payload that starts at the current position of `source`
+    pub fn matches<R: Read + Seek>(
+        &self,
+        source: &mut R,
+        payload_size: u64,
+        modified: u64,
+    ) -> io::Result<bool> {
+        let start = source.stream_position()?;
+        Ok(self.payload_size == payload_size
+            && self.modified == modified
+            && read_trailer(source, start, payload_size)? == self.trailer)
+    }
+
+    /// Fills `buffer` with decoded bytes starting at `offset`.
+    /// `source` must be positioned at the start of the payload.
+    pub fn read<R: Read + Seek>(
+        &self,
+        source: &mut R,
+        offset: u64,
+        buffer: &mut [u8],
+    ) -> io::Result<()> {
+        if buffer.is_empty() {
+            return Ok(());
+        }
+
+        let start = source.stream_position()?;
+        let point_idx = self
+            .points
+            .partition_point(|p| p.decoded_offset <= offset)
+            .saturating_sub(1);
+        let point = self.points.get(point_idx).ok_or_else(|| {
+            Error::new(
+                ErrorKind::UnexpectedEof,
+                "offset is past the end of payload",
+            )
+        })?;
+
+        source.seek(SeekFrom::Start(start + point.bit_offset / 8))?;
+        let remaining = self.payload_size - point.bit_offset / 8;
+        let mut reader = BitReader::new(source.take(remaining), point.bit_offset)?;
+
+        let mut history = point.window.clone();
+        let mut history_start = point.decoded_offset - history.len() as u64;
+        let end = offset + buffer.len() as u64;
+        let mut in_member = true;
+
+        loop {
+            let decoded = history_start + history.len() as u64;
+            if decoded >= end {
+                return Ok(());
+            }
+
+            if !in_member {
+                if !read_member_header(&mut reader)? {
+                    return Err(Error::new(
+                        ErrorKind::UnexpectedEof,
+                        "offset is past the end of payload",
+                    ));
+                }
+                in_member = true;
+            }
+
+            let is_final = inflate_block(&mut reader, &mut history)?;
+
+            // Copy the part of new output that falls into the requested range
+            let new_end = history_start + history.len() as u64;
+            let from = decoded.max(offset);
+            let to = new_end.min(end);
+            if from < to {
+                let src = &history[(from - history_start) as usize..(to - history_start) as usize];
+                buffer[(from - offset) as usize..(to - offset) as usize].copy_from_slice(src);
+            }
+
+            trim_history(&mut history, &mut history_start);
+
+            if is_final {
+                skip_member_trailer(&mut reader)?;
+                in_member = false;
+            }
+        }
+    }
+
+    /// Writes index to a sidecar file
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&self.payload_size.to_le_bytes())?;
+        writer.write_all(&self.modified.to_le_bytes())?;
+        writer.write_all(&self.trailer)?;
+        writer.write_all(&(self.points.len() as u64).to_le_bytes())?;
+
+        for point in &self.points {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&point.window)?;
+            let window = encoder.finish()?;
+
+            writer.write_all(&point.bit_offset.to_le_bytes())?;
+            writer.write_all(&point.decoded_offset.to_le_bytes())?;
+            writer.write_all(&(point.window.len() as u32).to_le_bytes())?;
+            writer.write_all(&(window.len() as u32).to_le_bytes())?;
+            writer.write_all(&window)?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads index written by `write_to`
+    pub fn read_from<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if magic != *MAGIC {
+            return Err(Error::new(ErrorKind::InvalidData, "not a gzip index file"));
+        }
+
+        let payload_size = read_u64(reader)?;
+        let modified = read_u64(reader)?;
+        let mut trailer = [0; 8];
+        reader.read_exact(&mut trailer)?;
+
+        let count = read_u64(reader)?;
+        let mut points = Vec::new();
+
+        for _ in 0..count {
+            let bit_offset = read_u64(reader)?;
+            let decoded_offset = read_u64(reader)?;
+            let window_size = read_u32(reader)? as usize;
+            let compressed_size = read_u32(reader)? as usize;
+
+            if window_size > WINDOW_SIZE || compressed_size > 2 * WINDOW_SIZE {
+                return Err(Error::new(ErrorKind::InvalidData, "window is too large"));
+            }
+
+            let mut compressed = vec![0; compressed_size];
+            reader.read_exact(&mut compressed)?;
+
+            let mut window = vec![0; window_size];
+            DeflateDecoder::new(compressed.as_slice()).read_exact(&mut window)?;
+
+            points.push(AccessPoint {
+                bit_offset,
+                decoded_offset,
+                window,
+            });
+        }
+
+        Ok(Self {
+            points,
+            payload_size,
+            modified,
+            trailer,
+        })
+    }
+}
+
+/// One access point per member, found by following the size subfields written by this crate
+fn member_points<R: Read + Seek>(
+    source: &mut R,
+    start: u64,
+    payload_size: u64,
+) -> io::Result<Option<Vec<AccessPoint>>> {
+    let mut points = Vec::new();
+    let mut offset = 0;
+    let mut decoded_offset = 0;
+
+    while offset < payload_size {
+        source.seek(SeekFrom::Start(start + offset))?;
+
+        let mut header = vec![0; 12];
+        source.read_exact(&mut header)?;
+        let extra_len = u16::from_le_bytes([header[10], header[11]]) as usize;
+        header.resize(12 + extra_len, 0);
+        source.read_exact(&mut header[12..])?;
+
+        let size = match gzip::member_size(&header) {
+            Some(size) if header[3] == FLAG_EXTRA && offset + size as u64 <= payload_size => size,
+            _ => return Ok(None),
+        };
+
+        points.push(AccessPoint {
+            bit_offset: (offset + header.len() as u64) * 8,
+            decoded_offset,
+            window: Vec::new(),
+        });
+
+        source.seek(SeekFrom::Start(start + offset + size as u64 - 4))?;
+        decoded_offset += read_u32(source)? as u64;
+        offset += size as u64;
+    }
+
+    Ok(Some(points))
+}
+
+/// Inflates the whole payload, recording an access point every `SPAN` bytes of output
+fn scan_points<R: Read>(source: R) -> io::Result<Vec<AccessPoint>> {
+    let mut reader = BitReader::new(source, 0)?;
+    let mut points = Vec::new();
+    let mut history = Vec::new();
+    let mut history_start = 0;
+    let mut last_point = None;
+
+    while read_member_header(&mut reader)? {
+        // Back-references never cross members
+        history_start += history.len() as u64;
+        history.clear();
+
+        loop {
+            let decoded = history_start + history.len() as u64;
+            if last_point.is_none_or(|last| decoded - last >= SPAN) {
+                let window_start = history.len().saturating_sub(WINDOW_SIZE);
+                points.push(AccessPoint {
+                    bit_offset: reader.bit_position(),
+                    decoded_offset: decoded,
+                    window: history[window_start..].to_vec(),
+                });
+                last_point = Some(decoded);
+            }
+
+            let is_final = inflate_block(&mut reader, &mut history)?;
+            trim_history(&mut history, &mut history_start);
+
+            if is_final {
+                break;
+            }
+        }
+
+        skip_member_trailer(&mut reader)?;
+    }
+
+    Ok(points)
+}
+
+fn trim_history(history: &mut Vec<u8>, history_start: &mut u64) {
+    if history.len() > HISTORY_LIMIT {
+        let drop = history.len() - WINDOW_SIZE;
+        history.drain(..drop);
+        *history_start += drop as u64;
+    }
+}
+
+/// Skips gzip member header, returns `false` at the end of payload
+fn read_member_header<R: Read>(reader: &mut BitReader<R>) -> io::Result<bool> {
+    reader.align_to_byte();
+    if reader.is_at_end()? {
+        return Ok(false);
+    }
+
+    let id = reader.bits(16)?;
+    let method = reader.bits(8)?;
+    if id == 0 {
+        // Zero padding after the last member
+        return Ok(false);
+    }
+    if id != 0x8b1f || method != 8 {
+        return Err(Error::new(ErrorKind::InvalidData, "invalid gzip header"));
+    }
+
+    let flags = reader.bits(8)? as u8;
+    for _ in 0..6 {
+        reader.bits(8)?; // MTIME, XFL, OS
+    }
+
+    if flags & FLAG_EXTRA != 0 {
+        let extra_len = reader.bits(16)?;
+        for _ in 0..extra_len {
+            reader.bits(8)?;
+        }
+    }
+
+    for flag in [FLAG_NAME, FLAG_COMMENT] {
+        if flags & flag != 0 {
+            while reader.bits(8)? != 0 {}
+        }
+    }
+
+    if flags & FLAG_HCRC != 0 {
+        reader.bits(16)?;
+    }
+
+    Ok(true)
+}
+
+fn skip_member_trailer<R: Read>(reader: &mut BitReader<R>) -> io::Result<()> {
+    reader.align_to_byte();
+    reader.bits(32)?; // CRC32
+    reader.bits(32)?; // ISIZE
+    Ok(())
+}
+
+fn read_trailer<R: Read + Seek>(
+    source: &mut R,
+    start: u64,
+    payload_size: u64,
+) -> io::Result<[u8; 8]> {
+    let mut trailer = [0; 8];
+    if payload_size >= 8 {
+        source.seek(SeekFrom::Start(start + payload_size - 8))?;
+        source.read_exact(&mut trailer)?;
+    }
+    source.seek(SeekFrom::Start(start))?;
+    Ok(trailer)
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
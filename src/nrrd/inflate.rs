@@ -0,0 +1,411 @@
+//! Minimal deflate decoder that can start at any block boundary.
+//!
+//! Used only for random access into gzip payloads, where decoding has to resume from a bit
+//! offset with a primed window, which general purpose inflate implementations do not expose.
+
+use std::io::{Error, ErrorKind, Read, Result};
+
+/// Back-references never reach further than this
+pub(crate) const WINDOW_SIZE: usize = 32 * 1024;
+
+const MAX_BITS: usize = 15;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+fn invalid(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg.to_string())
+}
+
+pub(crate) struct BitReader<R: Read> {
+    source: R,
+    buffer: Box<[u8]>,
+    buffer_pos: usize,
+    buffer_len: usize,
+    start_bits: u64,
+    consumed: u64, // Bytes moved from buffer into bit_buf
+    bit_buf: u64,
+    bit_count: u32,
+}
+
+impl<R: Read> BitReader<R> {
+    /// Starts reading at the given bit offset, `source` must be positioned at its byte
+    pub fn new(source: R, bit_offset: u64) -> Result<Self> {
+        let mut reader = Self {
+            source,
+            buffer: vec![0; 64 * 1024].into_boxed_slice(),
+            buffer_pos: 0,
+            buffer_len: 0,
+            start_bits: bit_offset / 8 * 8,
+            consumed: 0,
+            bit_buf: 0,
+            bit_count: 0,
+        };
+        reader.bits((bit_offset % 8) as u32)?;
+        Ok(reader)
+    }
+
+    /// Offset of the next unread bit
+    #[inline]
+    pub fn bit_position(&self) -> u64 {
+        self.start_bits + self.consumed * 8 - self.bit_count as u64
+    }
+
+    /// Discards bits up to the next byte boundary
+    pub fn align_to_byte(&mut self) {
+        let drop = self.bit_count % 8;
+        self.bit_buf >>= drop;
+        self.bit_count -= drop;
+    }
+
+    /// Returns `true` if there is nothing left to read
+    pub fn is_at_end(&mut self) -> Result<bool> {
+        self.refill()?;
+        Ok(self.bit_count == 0)
+    }
+
+    #[inline]
+    fn refill(&mut self) -> Result<()> {
+        while self.bit_count <= 56 {
+            if self.buffer_pos == self.buffer_len {
+                self.buffer_len = self.source.read(&mut self.buffer)?;
+                self.buffer_pos = 0;
+
+                if self.buffer_len == 0 {
+                    break;
+                }
+            }
+
+            self.bit_buf |= (self.buffer[self.buffer_pos] as u64) << self.bit_count;
+            self.buffer_pos += 1;
+            self.consumed += 1;
+            self.bit_count += 8;
+        }
+
+        Ok(())
+    }
+
+    /// Returns next `count` bits without consuming them, padded with zeros past the end
+    #[inline]
+    fn peek(&mut self, count: u32) -> Result<u64> {
+        if self.bit_count < count {
+            self.refill()?;
+        }
+        Ok(self.bit_buf & ((1 << count) - 1))
+    }
+
+    #[inline]
+    fn consume(&mut self, count: u32) -> Result<()> {
+        if self.bit_count < count {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "deflate data ended"));
+        }
+        self.bit_buf >>= count;
+        self.bit_count -= count;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn bits(&mut self, count: u32) -> Result<u32> {
+        let value = self.peek(count)?;
+        self.consume(count)?;
+        Ok(value as u32)
+    }
+}
+
+struct Huffman {
+    table: Vec<u16>, // (symbol << 4) | code length, indexed by bit reversed code
+    max_len: u32,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self> {
+        let mut count = [0u16; MAX_BITS + 1];
+        for &len in lengths {
+            count[len as usize] += 1;
+        }
+        count[0] = 0;
+
+        let mut left: i32 = 1;
+        for &c in &count[1..] {
+            left = (left << 1) - c as i32;
+            if left < 0 {
+                return Err(invalid("over-subscribed huffman code"));
+            }
+        }
+
+        let max_len = (1..=MAX_BITS).rev().find(|&l| count[l] != 0).unwrap_or(1) as u32;
+        let mut next_code = [0u32; MAX_BITS + 1];
+        let mut code = 0;
+        for len in 1..=MAX_BITS {
+            code = (code + count[len - 1] as u32) << 1;
+            next_code[len] = code;
+        }
+
+        let mut table = vec![0; 1 << max_len];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len == 0 {
+                continue;
+            }
+
+            let len = len as u32;
+            let code = next_code[len as usize];
+            next_code[len as usize] += 1;
+
+            let reversed = code.reverse_bits() >> (32 - len);
+            let entry = ((symbol as u16) << 4) | len as u16;
+            for fill in (reversed as usize..table.len()).step_by(1 << len) {
+                table[fill] = entry;
+            }
+        }
+
+        Ok(Self { table, max_len })
+    }
+
+    #[inline]
+    fn decode<R: Read>(&self, reader: &mut BitReader<R>) -> Result<u16> {
+        let entry = self.table[reader.peek(self.max_len)? as usize];
+        let len = (entry & 0xf) as u32;
+        if len == 0 {
+            return Err(invalid("invalid huffman code"));
+        }
+        reader.consume(len)?;
+        Ok(entry >> 4)
+    }
+}
+
+/// Decodes a single deflate block, appending output to `history`.
+/// `history` must hold at least the last `WINDOW_SIZE` bytes of previous output.
+/// Returns `true` when it was the final block of the stream.
+pub(crate) fn inflate_block<R: Read>(
+    reader: &mut BitReader<R>,
+    history: &mut Vec<u8>,
+) -> Result<bool> {
+    let is_final = reader.bits(1)? == 1;
+
+    match reader.bits(2)? {
+        0 => inflate_stored(reader, history)?,
+        1 => {
+            let (lit_len, dist) = fixed_codes()?;
+            inflate_codes(reader, history, &lit_len, &dist)?;
+        }
+        2 => {
+            let (lit_len, dist) = dynamic_codes(reader)?;
+            inflate_codes(reader, history, &lit_len, &dist)?;
+        }
+        _ => return Err(invalid("invalid deflate block type")),
+    };
+
+    Ok(is_final)
+}
+
+fn inflate_stored<R: Read>(reader: &mut BitReader<R>, history: &mut Vec<u8>) -> Result<()> {
+    reader.align_to_byte();
+    let len = reader.bits(16)?;
+    let nlen = reader.bits(16)?;
+    if len != !nlen & 0xffff {
+        return Err(invalid("stored block length mismatch"));
+    }
+
+    for _ in 0..len {
+        history.push(reader.bits(8)? as u8);
+    }
+
+    Ok(())
+}
+
+fn fixed_codes() -> Result<(Huffman, Huffman)> {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5; 30])?))
+}
+
+fn dynamic_codes<R: Read>(reader: &mut BitReader<R>) -> Result<(Huffman, Huffman)> {
+    let lit_len_count = reader.bits(5)? as usize + 257;
+    let dist_count = reader.bits(5)? as usize + 1;
+    let code_len_count = reader.bits(4)? as usize + 4;
+
+    let mut code_lengths = [0u8; 19];
+    for &i in &CODE_LENGTH_ORDER[..code_len_count] {
+        code_lengths[i] = reader.bits(3)? as u8;
+    }
+    let code_len_huffman = Huffman::new(&code_lengths)?;
+
+    let mut lengths = vec![0u8; lit_len_count + dist_count];
+    let mut i = 0;
+    while i < lengths.len() {
+        let (value, repeat) = match code_len_huffman.decode(reader)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 if i > 0 => (lengths[i - 1], 3 + reader.bits(2)?),
+            16 => return Err(invalid("repeat without previous length")),
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+
+        let end = i + repeat as usize;
+        if end > lengths.len() {
+            return Err(invalid("too many code lengths"));
+        }
+        lengths[i..end].fill(value);
+        i = end;
+    }
+
+    if lengths[256] == 0 {
+        return Err(invalid("missing end of block code"));
+    }
+
+    let lit_len = Huffman::new(&lengths[..lit_len_count])?;
+    let dist = Huffman::new(&lengths[lit_len_count..])?;
+    Ok((lit_len, dist))
+}
+
+fn inflate_codes<R: Read>(
+    reader: &mut BitReader<R>,
+    history: &mut Vec<u8>,
+    lit_len: &Huffman,
+    dist: &Huffman,
+) -> Result<()> {
+    loop {
+        let symbol = lit_len.decode(reader)? as usize;
+
+        if symbol < 256 {
+            history.push(symbol as u8);
+            continue;
+        }
+
+        if symbol == 256 {
+            return Ok(());
+        }
+
+        let symbol = symbol - 257;
+        if symbol >= LENGTH_BASE.len() {
+            return Err(invalid("invalid length code"));
+        }
+        let len = LENGTH_BASE[symbol] as usize + reader.bits(LENGTH_EXTRA[symbol] as u32)? as usize;
+
+        let symbol = dist.decode(reader)? as usize;
+        if symbol >= DIST_BASE.len() {
+            return Err(invalid("invalid distance code"));
+        }
+        let distance =
+            DIST_BASE[symbol] as usize + reader.bits(DIST_EXTRA[symbol] as u32)? as usize;
+
+        if distance > history.len() {
+            return Err(invalid("distance too far back"));
+        }
+
+        let start = history.len() - distance;
+        for k in 0..len {
+            let byte = history[start + k];
+            history.push(byte);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{inflate_block, BitReader, WINDOW_SIZE};
+    use flate2::{write::DeflateEncoder, Compression};
+    use std::io::Write;
+
+    fn deflate(data: &[u8], level: Compression) -> Vec<u8> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), level);
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    /// Inflates until the final block, returning output and the bit offset of every block
+    fn inflate(compressed: &[u8]) -> (Vec<u8>, Vec<(u64, usize)>) {
+        let mut reader = BitReader::new(compressed, 0).unwrap();
+        let mut history = Vec::new();
+        let mut blocks = Vec::new();
+
+        loop {
+            blocks.push((reader.bit_position(), history.len()));
+            if inflate_block(&mut reader, &mut history).unwrap() {
+                return (history, blocks);
+            }
+        }
+    }
+
+    /// Type of the first block, 0 stored, 1 fixed and 2 dynamic
+    fn first_block_type(compressed: &[u8]) -> u8 {
+        (compressed[0] >> 1) & 0b11
+    }
+
+    /// Compressible data with enough variation to need several dynamic blocks
+    fn sample(len: usize) -> Vec<u8> {
+        let mut state = 12345u32;
+        (0..len)
+            .map(|i| {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                match i % 7 {
+                    0 => (state >> 16) as u8,
+                    _ => (i / 13) as u8,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn stored_blocks() {
+        let data = sample(100_000);
+        let compressed = deflate(&data, Compression::none());
+        assert_eq!(first_block_type(&compressed), 0);
+        assert_eq!(inflate(&compressed).0, data);
+    }
+
+    #[test]
+    fn fixed_blocks() {
+        let data = b"abcabcabcabc fixed huffman codes";
+        let compressed = deflate(data, Compression::fast());
+        assert_eq!(first_block_type(&compressed), 1);
+        assert_eq!(inflate(&compressed).0, data);
+    }
+
+    #[test]
+    fn dynamic_blocks() {
+        let data = sample(1_000_000);
+        let compressed = deflate(&data, Compression::best());
+        assert_eq!(first_block_type(&compressed), 2);
+        assert_eq!(inflate(&compressed).0, data);
+    }
+
+    #[test]
+    fn resumes_at_block_boundary() {
+        let data = sample(1_000_000);
+        let compressed = deflate(&data, Compression::default());
+        let (_, blocks) = inflate(&compressed);
+        assert!(blocks.len() > 2);
+
+        // Start from a block in the middle, which is unlikely to be byte aligned
+        let (bit_offset, decoded) = blocks[blocks.len() / 2];
+        let window_start = decoded.saturating_sub(WINDOW_SIZE);
+        let mut history = data[window_start..decoded].to_vec();
+
+        let source = &compressed[(bit_offset / 8) as usize..];
+        let mut reader = BitReader::new(source, bit_offset).unwrap();
+        while !inflate_block(&mut reader, &mut history).unwrap() {}
+
+        assert_eq!(history, &data[window_start..]);
+    }
+}
@@ -1,8 +1,20 @@
+pub mod append;
+#[cfg(feature = "async")]
+pub mod async_io;
+pub mod codec;
+pub mod file;
+mod gzip;
+mod gzip_index;
+mod inflate;
+pub mod nrrd_ref;
+pub mod progress;
 pub mod reader;
+#[cfg(test)]
+mod test_util;
 pub mod writer;
 
-use crate::{image::Image, pixel::PixelValue};
-use std::{collections::HashSet, hash::Hash, str::FromStr};
+use crate::{image::Image, pixel::PixelValue, storage::Storage, view::ImageView};
+use std::{collections::HashSet, fmt, hash::Hash, str::FromStr};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Version {
@@ -75,21 +87,23 @@ pub enum PixelType {
     Block(i32),
 }
 
-impl ToString for PixelType {
-    fn to_string(&self) -> String {
-        match self {
-            PixelType::Int8 => "int8".to_string(),
-            PixelType::UInt8 => "uint8".to_string(),
-            PixelType::Int16 => "int16".to_string(),
-            PixelType::UInt16 => "uint16".to_string(),
-            PixelType::Int32 => "int32".to_string(),
-            PixelType::UInt32 => "uint32".to_string(),
-            PixelType::Int64 => "int64".to_string(),
-            PixelType::UInt64 => "uint64".to_string(),
-            PixelType::Float32 => "float".to_string(),
-            PixelType::Float64 => "double".to_string(),
-            PixelType::Block(_) => "block".to_string(),
-        }
+impl fmt::Display for PixelType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            PixelType::Int8 => "int8",
+            PixelType::UInt8 => "uint8",
+            PixelType::Int16 => "int16",
+            PixelType::UInt16 => "uint16",
+            PixelType::Int32 => "int32",
+            PixelType::UInt32 => "uint32",
+            PixelType::Int64 => "int64",
+            PixelType::UInt64 => "uint64",
+            PixelType::Float32 => "float",
+            PixelType::Float64 => "double",
+            PixelType::Block(_) => "block",
+        };
+
+        f.write_str(name)
     }
 }
 
@@ -123,7 +137,7 @@ impl FromStr for PixelType {
             "float" => Ok(Self::Float32),
             "double" => Ok(Self::Float64),
             "block" => Ok(Self::Block(0)), // Placeholder block size
-            _ => return Err(()),
+            _ => Err(()),
         }
     }
 }
@@ -150,7 +164,19 @@ pub enum Encoding {
     Other(String),
 }
 
-#[derive(Debug, Clone, Copy)]
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Encoding::Raw => f.write_str("raw"),
+            Encoding::Ascii => f.write_str("ascii"),
+            Encoding::GZip => f.write_str("gzip"),
+            Encoding::BZip2 => f.write_str("bzip2"),
+            Encoding::Other(name) => f.write_str(name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Endian {
     Little,
     Big,
@@ -202,11 +228,79 @@ impl Nrrd {
         &self.encoding
     }
 
+    /// Sets encoding used when writing, a codec must be registered for it unless it is raw.
+    /// Buffer is always kept decoded in memory.
+    pub fn set_encoding(&mut self, encoding: Encoding) {
+        self.fields.replace(Field {
+            identifier: "encoding".to_string(),
+            descriptor: encoding.to_string(),
+        });
+        self.encoding = encoding;
+    }
+
     #[inline]
     pub fn fields(&self) -> &HashSet<Field> {
         &self.fields
     }
 
+    /// Descriptor of the field, identifier is compared case-insensitively
+    pub fn field(&self, identifier: &str) -> Option<&str> {
+        let key = Field {
+            identifier: identifier.to_lowercase(),
+            descriptor: String::new(),
+        };
+        self.fields.get(&key).map(|f| f.descriptor.as_str())
+    }
+
+    /// Adds or replaces a field that does not affect how the buffer is interpreted
+    pub(crate) fn set_field(&mut self, identifier: &str, descriptor: String) {
+        self.fields.replace(Field {
+            identifier: identifier.to_lowercase(),
+            descriptor,
+        });
+    }
+
+    /// Removes a field that does not affect how the buffer is interpreted
+    pub(crate) fn remove_field(&mut self, identifier: &str) {
+        self.fields.remove(&Field {
+            identifier: identifier.to_lowercase(),
+            descriptor: String::new(),
+        });
+    }
+
+    /// Copy of the header with other axes and a buffer that holds pixels for them.
+    /// Per-axis fields are copied as they are, fields pointing to detached data are dropped.
+    pub(crate) fn with_axes(&self, sizes: &[usize], buffer: Vec<u8>) -> Self {
+        let mut nrrd = Nrrd {
+            version: self.version,
+            fields: self.fields.clone(),
+            key_values: self.key_values.clone(),
+            dimension: sizes.len() as i32,
+            sizes: sizes.iter().map(|&s| s as i32).collect(),
+            pixel_type: self.pixel_type,
+            encoding: self.encoding.clone(),
+            endian: self.endian,
+            buffer,
+        };
+
+        let sizes = sizes.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        nrrd.set_field("dimension", nrrd.dimension.to_string());
+        nrrd.set_field("sizes", sizes.join(" "));
+
+        for identifier in [
+            "data file",
+            "datafile",
+            "line skip",
+            "lineskip",
+            "byte skip",
+            "byteskip",
+        ] {
+            nrrd.remove_field(identifier);
+        }
+
+        nrrd
+    }
+
     #[inline]
     pub fn key_values(&self) -> &HashSet<KeyValue> {
         &self.key_values
@@ -218,59 +312,124 @@ impl Nrrd {
     }
 }
 
+impl<T: PixelValue, const D: usize, S: Storage<T>> From<&Image<T, D, S>> for Nrrd {
+    #[inline]
+    fn from(image: &Image<T, D, S>) -> Self {
+        Nrrd::from_pixels(image.pixels().iter(), image.sizes())
+    }
+}
 
-impl<T: PixelValue, const D: usize> From<&Image<T, D>> for Nrrd {
-    fn from(image: &Image<T, D>) -> Self {
-        let pixel_size = T::pixel_type().size();
-        let mut buffer = vec![Default::default(); image.pixels_count() * pixel_size];
-        let endian = Endian::Little;
+impl<T: PixelValue, const D: usize> From<ImageView<'_, T, D>> for Nrrd {
+    #[inline]
+    fn from(view: ImageView<'_, T, D>) -> Self {
+        Nrrd::from_pixels(view.iter(), view.sizes())
+    }
+}
+
+impl Nrrd {
+    /// Encodes pixels as raw little endian buffer, first axis is the fastest
+    pub(crate) fn from_pixels<'a, T: PixelValue + 'a>(
+        pixels: impl ExactSizeIterator<Item = &'a T>,
+        sizes: &[usize],
+    ) -> Self {
+        let mut nrrd = Nrrd::raw_header::<T>(sizes);
+        let pixel_size = T::pixel_size();
+        nrrd.buffer = vec![Default::default(); pixels.len() * pixel_size];
 
         let mut offset = 0;
-        for pixel in image.pixels() {
-            pixel.to_bytes(&mut buffer[offset..offset + pixel_size], endian);
+        for pixel in pixels {
+            pixel.to_bytes(&mut nrrd.buffer[offset..offset + pixel_size], nrrd.endian);
             offset += pixel_size;
         }
 
+        nrrd
+    }
+
+    /// Buffer must hold raw little endian pixels as described by the header
+    #[inline]
+    pub(crate) fn set_buffer(&mut self, buffer: Vec<u8>) {
+        self.buffer = buffer;
+    }
+
+    /// Header of a raw little endian image with pixels of type `T` and empty buffer.
+    /// Components of non-scalar pixels get their own fastest axis.
+    pub(crate) fn raw_header<T: PixelValue>(sizes: &[usize]) -> Self {
+        let component_axis = T::kind().map(|kind| (kind, T::components()));
+        Nrrd::raw_header_of(T::pixel_type(), component_axis, sizes)
+    }
+
+    /// Same as `raw_header` for a pixel type known at runtime, `component_axis` is its kind and size
+    pub(crate) fn raw_header_of(
+        pixel_type: PixelType,
+        component_axis: Option<(&str, usize)>,
+        sizes: &[usize],
+    ) -> Self {
+        let endian = Endian::Little;
+        let mut axes = sizes.to_vec();
+        if let Some((_, components)) = component_axis {
+            axes.insert(0, components);
+        }
+
+        let mut fields: HashSet<Field> = [
+            Field {
+                identifier: "type".to_string(),
+                descriptor: pixel_type.to_string(),
+            },
+            Field {
+                identifier: "dimension".to_string(),
+                descriptor: axes.len().to_string(),
+            },
+            Field {
+                identifier: "sizes".to_string(),
+                descriptor: axes
+                    .iter()
+                    .map(|&x| x.to_string())
+                    .collect::<Vec<String>>()
+                    .join(" "),
+            },
+            Field {
+                identifier: "endian".to_string(),
+                descriptor: match endian {
+                    Endian::Little => "little".to_string(),
+                    Endian::Big => "big".to_string(),
+                },
+            },
+            Field {
+                identifier: "encoding".to_string(),
+                descriptor: Encoding::Raw.to_string(),
+            },
+        ]
+        .into_iter()
+        .collect();
+
+        if let PixelType::Block(size) = pixel_type {
+            fields.insert(Field {
+                identifier: "block size".to_string(),
+                descriptor: size.to_string(),
+            });
+        }
+
+        if let Some((kind, _)) = component_axis {
+            let kinds = std::iter::once(kind)
+                .chain(sizes.iter().map(|_| "domain"))
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            fields.insert(Field {
+                identifier: "kinds".to_string(),
+                descriptor: kinds,
+            });
+        }
+
         Nrrd {
             endian,
-            buffer,
-            dimension: D as i32,
-            sizes: image.sizes().iter().map(|&x| x as i32).collect(),
-            pixel_type: T::pixel_type(),
+            buffer: Vec::new(),
+            dimension: axes.len() as i32,
+            sizes: axes.iter().map(|&x| x as i32).collect(),
+            pixel_type,
             encoding: Encoding::Raw,
             version: Version::Nrrd5,
-            fields: [
-                Field {
-                    identifier: "type".to_string(),
-                    descriptor: T::pixel_type().to_string(),
-                },
-                Field {
-                    identifier: "dimension".to_string(),
-                    descriptor: D.to_string(),
-                },
-                Field {
-                    identifier: "sizes".to_string(),
-                    descriptor: image
-                        .sizes()
-                        .iter()
-                        .map(|&x| x.to_string())
-                        .collect::<Vec<String>>()
-                        .join(" "),
-                },
-                Field {
-                    identifier: "endian".to_string(),
-                    descriptor: match endian {
-                        Endian::Little => "little".to_string(),
-                        Endian::Big => "big".to_string(),
-                    },
-                },
-                Field {
-                    identifier: "encoding".to_string(),
-                    descriptor: "raw".to_string(),
-                },
-            ]
-            .into_iter()
-            .collect(),
+            fields,
             key_values: HashSet::new(),
         }
     }
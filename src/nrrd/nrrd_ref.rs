@@ -0,0 +1,324 @@
+use crate::{
+    image::{check_pod_layout, fixed_sizes, image_sizes, ImageFromNrrdErr},
+    nrrd::{
+        progress::Progress,
+        reader::{read_buffer, HeaderLine, LineParser, ReadNrrdErr},
+        Encoding, Endian, Field, KeyValue, Nrrd, PixelType, Version,
+    },
+    pixel::PodPixel,
+    view::ImageView,
+};
+use std::slice;
+
+/// Field borrowed from the header, identifier is kept as written
+#[derive(Debug, Clone, Copy)]
+pub struct FieldRef<'a> {
+    pub identifier: &'a str,
+    pub descriptor: &'a str,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct KeyValueRef<'a> {
+    pub key: &'a str,
+    pub value: &'a str,
+}
+
+/// NRRD parsed from memory without copying header strings or payload
+#[derive(Debug, Clone)]
+pub struct NrrdRef<'a> {
+    version: Version,
+    fields: Vec<FieldRef<'a>>,
+    key_values: Vec<KeyValueRef<'a>>,
+
+    dimension: i32,
+    sizes: Vec<i32>,
+    pixel_type: PixelType,
+    encoding: Encoding,
+    endian: Endian,
+
+    payload: &'a [u8],
+}
+
+impl Nrrd {
+    /// Parses header borrowing from `bytes`, payload is left encoded.
+    /// Detached headers are parsed with an empty payload.
+    pub fn parse_bytes(bytes: &[u8]) -> Result<NrrdRef<'_>, ReadNrrdErr> {
+        let mut rest = bytes;
+        let mut next_line = || -> Result<&str, ReadNrrdErr> {
+            let (line, tail) = match rest.iter().position(|&b| b == b'\n') {
+                Some(end) => (&rest[..end], &rest[end + 1..]),
+                None => (rest, &rest[rest.len()..]),
+            };
+            rest = tail;
+
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            std::str::from_utf8(line)
+                .map_err(|_| ReadNrrdErr::Malformed("Header is not valid UTF-8".to_string()))
+        };
+
+        let mut parser = LineParser::new(next_line()?)?;
+        let mut fields = Vec::new();
+        let mut key_values = Vec::new();
+
+        loop {
+            match parser.parse_line(next_line()?)? {
+                HeaderLine::End => break,
+                HeaderLine::Comment => (),
+                HeaderLine::Field {
+                    identifier,
+                    descriptor,
+                } => fields.push(FieldRef {
+                    identifier,
+                    descriptor,
+                }),
+                HeaderLine::KeyValue { key, value } => key_values.push(KeyValueRef { key, value }),
+            };
+        }
+
+        let header = parser.finish()?;
+
+        Ok(NrrdRef {
+            version: header.version,
+            fields,
+            key_values,
+            dimension: header.dimension,
+            sizes: header.sizes,
+            pixel_type: header.pixel_type,
+            encoding: header.encoding,
+            endian: header.endian,
+            payload: rest,
+        })
+    }
+}
+
+impl<'a> NrrdRef<'a> {
+    /// Pixel data as stored, still encoded
+    #[inline]
+    pub fn payload(&self) -> &'a [u8] {
+        self.payload
+    }
+
+    #[inline]
+    pub fn dimension(&self) -> i32 {
+        self.dimension
+    }
+
+    #[inline]
+    pub fn pixel_type(&self) -> PixelType {
+        self.pixel_type
+    }
+
+    #[inline]
+    pub fn sizes(&self) -> &[i32] {
+        &self.sizes
+    }
+
+    #[inline]
+    pub fn endian(&self) -> Endian {
+        self.endian
+    }
+
+    #[inline]
+    pub fn encoding(&self) -> &Encoding {
+        &self.encoding
+    }
+
+    #[inline]
+    pub fn fields(&self) -> &[FieldRef<'a>] {
+        &self.fields
+    }
+
+    #[inline]
+    pub fn key_values(&self) -> &[KeyValueRef<'a>] {
+        &self.key_values
+    }
+
+    #[inline]
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    /// Descriptor of the field, identifier is compared case-insensitively
+    pub fn field(&self, identifier: &str) -> Option<&'a str> {
+        self.fields
+            .iter()
+            .find(|f| f.identifier.eq_ignore_ascii_case(identifier))
+            .map(|f| f.descriptor)
+    }
+
+    /// Copies header and decodes payload into an owned `Nrrd`
+    pub fn to_nrrd(&self) -> Result<Nrrd, ReadNrrdErr> {
+        let mut nrrd = Nrrd {
+            version: self.version,
+            fields: self
+                .fields
+                .iter()
+                .map(|f| Field {
+                    identifier: f.identifier.to_lowercase(),
+                    descriptor: f.descriptor.to_string(),
+                })
+                .collect(),
+            key_values: self
+                .key_values
+                .iter()
+                .map(|kv| KeyValue {
+                    key: kv.key.to_string(),
+                    value: kv.value.to_string(),
+                })
+                .collect(),
+            dimension: self.dimension,
+            sizes: self.sizes.clone(),
+            pixel_type: self.pixel_type,
+            encoding: self.encoding.clone(),
+            endian: self.endian,
+            buffer: Vec::new(),
+        };
+
+        read_buffer(&mut nrrd, &mut self.payload(), &Progress::default())?;
+        Ok(nrrd)
+    }
+
+    /// Views raw payload as pixels without copying.
+    /// Payload must be raw, in native byte order and aligned for `T`.
+    pub fn image_view<T: PodPixel, const D: usize>(
+        &self,
+    ) -> Result<ImageView<'a, T, D>, ImageFromNrrdErr> {
+        let sizes = image_sizes::<T>(self.pixel_type, &self.sizes, self.field("kinds"))?;
+        let sizes: [usize; D] = fixed_sizes(sizes)?;
+
+        let pixels_count = sizes.iter().product::<usize>();
+        check_pod_layout::<T>(&self.encoding, self.endian, self.payload, pixels_count)?;
+
+        // SAFETY: `T: PodPixel` accepts any bytes, and length and alignment were checked above
+        let pixels =
+            unsafe { slice::from_raw_parts(self.payload.as_ptr().cast::<T>(), pixels_count) };
+
+        Ok(ImageView::new(pixels, sizes).expect("pixels count matches sizes"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        component::Rgb,
+        image::{Image, ImageFromNrrdErr},
+        nrrd::{reader::ReadNrrdErr, writer::write_nrrd, Encoding, Nrrd, PixelType},
+        storage::AlignedBuffer,
+    };
+
+    /// NRRD in a buffer aligned to 8 bytes whose payload starts `shift` bytes past a multiple of 8
+    fn nrrd_bytes(fields: &str, payload: &[u8], shift: usize) -> AlignedBuffer<u8> {
+        let endian = match cfg!(target_endian = "little") {
+            true => "little",
+            false => "big",
+        };
+        let mut header = format!("NRRD0005\n{fields}endian: {endian}\nencoding: raw\n#");
+        while (header.len() + 2) % 8 != shift {
+            header.push('#');
+        }
+        header.push_str("\n\n");
+
+        let mut bytes = header.into_bytes();
+        bytes.extend_from_slice(payload);
+        AlignedBuffer::from_slice(&bytes, 8)
+    }
+
+    #[test]
+    fn image_view_reinterprets_payload() {
+        let values = [0u16, 1, 2, 300, 400, 65535];
+        let payload = values
+            .iter()
+            .flat_map(|v| v.to_ne_bytes())
+            .collect::<Vec<_>>();
+        let bytes = nrrd_bytes("type: uint16\ndimension: 2\nsizes: 3 2\n", &payload, 0);
+
+        let nrrd = Nrrd::parse_bytes(&bytes).unwrap();
+        let view = nrrd.image_view::<u16, 2>().unwrap();
+        assert_eq!(view.as_slice().unwrap(), &values);
+        assert_eq!(view[[0, 1]], 300);
+    }
+
+    #[test]
+    fn image_view_reinterprets_components() {
+        let payload = [1, 2, 3, 4, 5, 6];
+        let fields = "type: uint8\ndimension: 2\nsizes: 3 2\nkinds: RGB-color domain\n";
+        let bytes = nrrd_bytes(fields, &payload, 3);
+
+        let nrrd = Nrrd::parse_bytes(&bytes).unwrap();
+        let view = nrrd.image_view::<Rgb<u8>, 1>().unwrap();
+        assert_eq!(view[[1]], Rgb { r: 4, g: 5, b: 6 });
+    }
+
+    #[test]
+    fn image_view_rejects_unaligned_payload() {
+        let payload = [0; 12];
+        let bytes = nrrd_bytes("type: float\ndimension: 1\nsizes: 3\n", &payload, 2);
+
+        let nrrd = Nrrd::parse_bytes(&bytes).unwrap();
+        let err = nrrd.image_view::<f32, 1>().unwrap_err();
+        assert!(matches!(err, ImageFromNrrdErr::UnalignedBuffer));
+    }
+
+    #[test]
+    fn image_view_rejects_short_payload() {
+        let payload = [0; 4];
+        let bytes = nrrd_bytes("type: uint16\ndimension: 1\nsizes: 3\n", &payload, 0);
+
+        let nrrd = Nrrd::parse_bytes(&bytes).unwrap();
+        assert!(nrrd.image_view::<u16, 1>().is_err());
+    }
+
+    #[test]
+    fn parse_bytes_borrows_header_and_payload() {
+        let bytes = b"NRRD0004\r\n# comment\r\ntype: short\r\nDimension: 1\r\nsizes: 2\r\n\
+            endian: big\r\nencoding: raw\r\nspace units:=mm\r\n\r\n\x01\x02\x03\x04";
+
+        let nrrd = Nrrd::parse_bytes(bytes).unwrap();
+        assert_eq!(nrrd.pixel_type(), PixelType::Int16);
+        assert_eq!(nrrd.sizes(), &[2]);
+        assert_eq!(nrrd.field("dimension"), Some("1"));
+        assert_eq!(nrrd.fields()[1].identifier, "Dimension");
+        assert_eq!(nrrd.key_values()[0].value, "mm");
+
+        let payload = nrrd.payload();
+        assert_eq!(payload, &[1, 2, 3, 4]);
+        assert!(std::ptr::eq(payload, &bytes[bytes.len() - 4..]));
+
+        let owned = nrrd.to_nrrd().unwrap();
+        let image = Image::<i16, 1>::try_from(&owned).unwrap();
+        assert_eq!(image.pixels(), &[0x0102, 0x0304]);
+    }
+
+    #[test]
+    fn to_nrrd_decodes_payload() {
+        let image = Image::<u16, 2>::from_fn([40, 30], |[x, y]| (x * y) as u16);
+        let mut nrrd = Nrrd::from(&image);
+        nrrd.set_encoding(Encoding::GZip);
+        let mut bytes = Vec::new();
+        write_nrrd(&nrrd, &mut bytes).unwrap();
+
+        let parsed = Nrrd::parse_bytes(&bytes).unwrap();
+        assert!(matches!(
+            parsed.image_view::<u16, 2>(),
+            Err(ImageFromNrrdErr::UnsupportedEncoding)
+        ));
+        assert_eq!(
+            parsed.to_nrrd().unwrap().buffer(),
+            Nrrd::from(&image).buffer()
+        );
+    }
+
+    #[test]
+    fn parse_bytes_rejects_malformed_headers() {
+        let detached =
+            b"NRRD0004\ntype: uint8\ndimension: 1\nsizes: 2\nendian: little\nencoding: raw\ndata file: a.raw\n";
+        let nrrd = Nrrd::parse_bytes(detached).unwrap();
+        assert!(nrrd.payload().is_empty());
+
+        let err = Nrrd::parse_bytes(b"NRRD0004\ntype: \xff\n\n").unwrap_err();
+        assert!(matches!(err, ReadNrrdErr::Malformed(_)));
+
+        let err = Nrrd::parse_bytes(b"NRRD0004\ntype: uint8\ndimension: 1\n\n").unwrap_err();
+        assert!(matches!(err, ReadNrrdErr::Malformed(_)));
+    }
+}
@@ -0,0 +1,236 @@
+use std::{
+    error::Error,
+    fmt,
+    io::{self, Read, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+/// Called with the number of bytes processed so far and the total number of bytes
+pub type ProgressCallback = Arc<dyn Fn(u64, u64) + Send + Sync>;
+
+/// Callback is invoked at most once per this many bytes, and when processing completes
+const REPORT_INTERVAL: u64 = 1024 * 1024;
+
+/// Cancels reading or writing from another thread
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Error inside `std::io::Error` that readers and writers turn into their `Cancelled` variants
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Operation cancelled")
+    }
+}
+
+impl Error for Cancelled {}
+
+impl Cancelled {
+    /// Returns `true` if the error was caused by cancellation
+    pub fn is(err: &io::Error) -> bool {
+        err.get_ref().is_some_and(|e| e.is::<Cancelled>())
+    }
+}
+
+/// Progress reporting and cancellation of pixel data processing
+#[derive(Clone, Default)]
+pub struct Progress {
+    pub callback: Option<ProgressCallback>,
+    pub cancellation: Option<CancellationToken>,
+}
+
+impl fmt::Debug for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Progress")
+            .field("callback", &self.callback.as_ref().map(|_| ".."))
+            .field("cancellation", &self.cancellation)
+            .finish()
+    }
+}
+
+/// Counts bytes passing through a reader or writer
+pub(crate) struct Tracked<'a, T> {
+    inner: T,
+    progress: &'a Progress,
+    processed: u64,
+    reported: u64,
+    total: u64,
+}
+
+impl<'a, T> Tracked<'a, T> {
+    pub(crate) fn new(inner: T, progress: &'a Progress, total: u64) -> Self {
+        Self {
+            inner,
+            progress,
+            processed: 0,
+            reported: 0,
+            total,
+        }
+    }
+
+    fn check_cancelled(&self) -> io::Result<()> {
+        match &self.progress.cancellation {
+            Some(token) if token.is_cancelled() => Err(io::Error::other(Cancelled)),
+            _ => Ok(()),
+        }
+    }
+
+    fn advance(&mut self, bytes: usize) {
+        self.processed += bytes as u64;
+
+        if let Some(callback) = &self.progress.callback {
+            if self.processed - self.reported >= REPORT_INTERVAL || self.processed == self.total {
+                self.reported = self.processed;
+                callback(self.processed, self.total);
+            }
+        }
+    }
+}
+
+impl<T: Read> Read for Tracked<'_, T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.check_cancelled()?;
+        let read = self.inner.read(buf)?;
+        self.advance(read);
+        Ok(read)
+    }
+}
+
+impl<T: Write> Write for Tracked<'_, T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.check_cancelled()?;
+        let written = self.inner.write(buf)?;
+        self.advance(written);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CancellationToken, Progress, REPORT_INTERVAL};
+    use crate::{
+        image::Image,
+        nrrd::{
+            reader::{read_nrrd_with_options, ReadNrrdErr, ReadOptions},
+            writer::{write_nrrd, write_nrrd_with_options, WriteNrrdErr, WriteOptions},
+            Encoding, Nrrd,
+        },
+    };
+    use std::sync::{Arc, Mutex};
+
+    /// Five and a half report intervals of pixel data
+    fn sample() -> Nrrd {
+        let count = (REPORT_INTERVAL as usize * 11 / 2) / 4;
+        let image = Image::<u32, 1>::from_fn([count], |[i]| i as u32);
+        Nrrd::from(&image)
+    }
+
+    fn recording(reports: &Arc<Mutex<Vec<(u64, u64)>>>) -> Progress {
+        let reports = reports.clone();
+        Progress {
+            callback: Some(Arc::new(move |done, total| {
+                reports.lock().unwrap().push((done, total))
+            })),
+            cancellation: None,
+        }
+    }
+
+    #[test]
+    fn reports_every_interval_and_completion() {
+        for encoding in [Encoding::Raw, Encoding::GZip] {
+            let mut nrrd = sample();
+            nrrd.set_encoding(encoding);
+            let total = nrrd.buffer().len() as u64;
+
+            let reports = Arc::new(Mutex::new(Vec::new()));
+            let options = WriteOptions {
+                progress: recording(&reports),
+                ..Default::default()
+            };
+            let mut file = Vec::new();
+            write_nrrd_with_options(&nrrd, &mut file, &options).unwrap();
+            assert_eq!(reports.lock().unwrap().last(), Some(&(total, total)));
+
+            reports.lock().unwrap().clear();
+            let options = ReadOptions {
+                progress: recording(&reports),
+            };
+            read_nrrd_with_options(file.as_slice(), &options).unwrap();
+
+            let reports = reports.lock().unwrap();
+            assert_eq!(reports.len(), 6);
+            assert_eq!(reports.last(), Some(&(total, total)));
+            assert!(reports[..5]
+                .windows(2)
+                .all(|w| w[1].0 - w[0].0 >= REPORT_INTERVAL));
+        }
+    }
+
+    #[test]
+    fn cancels_mid_read() {
+        let nrrd = sample();
+        let mut file = Vec::new();
+        write_nrrd(&nrrd, &mut file).unwrap();
+
+        // Cancelled from the first report, before the data is complete
+        let token = CancellationToken::new();
+        let callback_token = token.clone();
+        let reports = Arc::new(Mutex::new(0));
+        let callback_reports = reports.clone();
+        let options = ReadOptions {
+            progress: Progress {
+                callback: Some(Arc::new(move |_, _| {
+                    *callback_reports.lock().unwrap() += 1;
+                    callback_token.cancel();
+                })),
+                cancellation: Some(token),
+            },
+        };
+
+        let err = read_nrrd_with_options(file.as_slice(), &options).unwrap_err();
+        assert!(matches!(err, ReadNrrdErr::Cancelled));
+        assert_eq!(*reports.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn cancels_write() {
+        let token = CancellationToken::new();
+        token.cancel();
+        let options = WriteOptions {
+            progress: Progress {
+                callback: None,
+                cancellation: Some(token),
+            },
+            ..Default::default()
+        };
+
+        let err = write_nrrd_with_options(&sample(), Vec::new(), &options).unwrap_err();
+        assert!(matches!(err, WriteNrrdErr::Cancelled));
+    }
+}
@@ -1,7 +1,13 @@
-use crate::nrrd::{Encoding, Endian, Field, KeyValue, Nrrd, PixelType, Version};
+use crate::nrrd::{
+    codec::{find_codec, Limited, Oversized},
+    progress::{Cancelled, Progress, Tracked},
+    Encoding, Endian, Field, KeyValue, Nrrd, PixelType, Version,
+};
 use std::{
     collections::HashSet,
-    io::{BufRead, BufReader, Read},
+    fs::File,
+    io::{self, BufRead, BufReader, Read},
+    path::{Path, PathBuf},
     str::FromStr,
 };
 
@@ -10,103 +16,294 @@ pub enum ReadNrrdErr {
     DuplicateField(String),
     UnknownVersion(String),
     Malformed(String),
+    UnsupportedEncoding(Encoding),
+    Cancelled,
     IOError(std::io::Error),
 }
 
 impl From<std::io::Error> for ReadNrrdErr {
     #[inline]
     fn from(err: std::io::Error) -> Self {
+        if Cancelled::is(&err) {
+            return ReadNrrdErr::Cancelled;
+        }
+
+        if Oversized::is(&err) {
+            return ReadNrrdErr::Malformed(err.to_string());
+        }
+
         ReadNrrdErr::IOError(err)
     }
 }
 
+#[derive(Debug, Clone, Default)]
+pub struct ReadOptions {
+    /// Reports decoded bytes out of the total buffer size
+    pub progress: Progress,
+}
+
 pub fn read_nrrd<T: Read>(reader: T) -> Result<Nrrd, ReadNrrdErr> {
+    read_nrrd_with_options(reader, &ReadOptions::default())
+}
+
+pub fn read_nrrd_with_options<T: Read>(
+    reader: T,
+    options: &ReadOptions,
+) -> Result<Nrrd, ReadNrrdErr> {
     let mut buf_reader = BufReader::new(reader);
 
     // let max_file_size = 1024 * 1024 * 1024 * 10; // 10 GB
     // let buf_reader.take(max_file_size);
 
     let mut nrrd = read_header(&mut buf_reader)?;
-    buf_reader.read_to_end(&mut nrrd.buffer)?;
+    read_buffer(&mut nrrd, &mut buf_reader, &options.progress)?;
 
-    if !validate_buffer_size(&nrrd) {
-        return Err(ReadNrrdErr::Malformed("Buffer size mismatch".to_string()));
-    }
+    Ok(nrrd)
+}
+
+/// Reads NRRD from a file, following `data file` for detached headers.
+/// Relative data file paths are resolved against the directory of the header.
+pub fn read_nrrd_file<P: AsRef<Path>>(path: P) -> Result<Nrrd, ReadNrrdErr> {
+    read_nrrd_file_with_options(path, &ReadOptions::default())
+}
+
+pub fn read_nrrd_file_with_options<P: AsRef<Path>>(
+    path: P,
+    options: &ReadOptions,
+) -> Result<Nrrd, ReadNrrdErr> {
+    let path = path.as_ref();
+    let mut buf_reader = BufReader::new(File::open(path)?);
+    let mut nrrd = read_header(&mut buf_reader)?;
+    let progress = &options.progress;
+
+    match data_file_path(&nrrd, path) {
+        Some(data_path) => {
+            let mut data_reader = BufReader::new(File::open(data_path)?);
+            read_buffer(&mut nrrd, &mut data_reader, progress)?
+        }
+        None => read_buffer(&mut nrrd, &mut buf_reader, progress)?,
+    };
 
     Ok(nrrd)
 }
 
-fn read_header<T: BufRead>(reader: &mut T) -> Result<Nrrd, ReadNrrdErr> {
+/// Path of the detached data file, resolved against the directory of the header
+pub(crate) fn data_file_path(nrrd: &Nrrd, header_path: &Path) -> Option<PathBuf> {
+    let data_file = nrrd
+        .fields
+        .iter()
+        .find(|f| f.identifier == "data file" || f.identifier == "datafile")?;
+    let header_dir = header_path.parent().unwrap_or(Path::new(""));
+
+    Some(header_dir.join(&data_file.descriptor))
+}
+
+/// Reads pixel data till the end of `reader`, decoding it with the codec registered for encoding
+pub(crate) fn read_buffer<T: Read>(
+    nrrd: &mut Nrrd,
+    reader: &mut T,
+    progress: &Progress,
+) -> Result<(), ReadNrrdErr> {
+    let codec = find_codec(&nrrd.encoding);
+    if codec.is_none() && nrrd.encoding != Encoding::Raw {
+        return Err(ReadNrrdErr::UnsupportedEncoding(nrrd.encoding.clone()));
+    }
+
+    let total = expected_buffer_size(nrrd)?;
+    let buffer = Tracked::new(&mut nrrd.buffer, progress, total as u64);
+    let mut buffer = Limited::new(buffer, total);
+
+    match codec {
+        Some(codec) => codec.decode(reader, &mut buffer, total)?,
+        None => {
+            io::copy(reader, &mut buffer)?;
+        }
+    };
+
+    validate_buffer_size(nrrd)
+}
+
+pub(crate) fn read_header<T: BufRead>(reader: &mut T) -> Result<Nrrd, ReadNrrdErr> {
     let mut line = String::new();
     reader.read_line(&mut line)?;
-    remove_trailing_new_line(&mut line);
-    let version = try_read_magic(&line)?;
-
-    let mut fields = HashSet::new();
-    let mut key_values = HashSet::new();
-    let mut required_fields = RequiredFields::default();
-    let mut line_num = 0;
+    let mut parser = HeaderParser::new(&mut line)?;
 
     loop {
         line.clear();
         reader.read_line(&mut line)?;
-        line_num += 1;
 
-        if line == "\n" || line == "\r\n" {
-            // End of header
+        if !parser.parse_line(&mut line)? {
             break;
         }
+    }
+
+    parser.finish()
+}
+
+/// Collects header into `Nrrd`, shared by sync and async readers
+pub(crate) struct HeaderParser {
+    lines: LineParser,
+    fields: HashSet<Field>,
+    key_values: HashSet<KeyValue>,
+}
+
+impl HeaderParser {
+    /// Starts parsing from the magic line
+    pub(crate) fn new(magic_line: &mut String) -> Result<Self, ReadNrrdErr> {
+        remove_trailing_new_line(magic_line);
+
+        Ok(Self {
+            lines: LineParser::new(magic_line)?,
+            fields: HashSet::new(),
+            key_values: HashSet::new(),
+        })
+    }
+
+    /// Parses next line including its line break, returns `false` once the header has ended
+    pub(crate) fn parse_line(&mut self, line: &mut String) -> Result<bool, ReadNrrdErr> {
+        remove_trailing_new_line(line);
+
+        match self.lines.parse_line(line)? {
+            HeaderLine::End => return Ok(false),
+            HeaderLine::Comment => (),
+            HeaderLine::Field {
+                identifier,
+                descriptor,
+            } => {
+                self.fields.insert(Field {
+                    identifier: identifier.to_lowercase(),
+                    descriptor: descriptor.into(),
+                });
+            }
+            HeaderLine::KeyValue { key, value } => {
+                self.key_values.insert(KeyValue {
+                    key: key.into(),
+                    value: value.into(),
+                });
+            }
+        };
+
+        Ok(true)
+    }
+
+    pub(crate) fn finish(self) -> Result<Nrrd, ReadNrrdErr> {
+        let header = self.lines.finish()?;
+
+        Ok(Nrrd {
+            version: header.version,
+            fields: self.fields,
+            key_values: self.key_values,
+            dimension: header.dimension,
+            sizes: header.sizes,
+            pixel_type: header.pixel_type,
+            encoding: header.encoding,
+            endian: header.endian,
+            buffer: Vec::new(),
+        })
+    }
+}
+
+/// Single line of header, borrowing from the line text
+pub(crate) enum HeaderLine<'l> {
+    End,
+    Comment,
+    Field {
+        identifier: &'l str, // As written, compare case-insensitively
+        descriptor: &'l str,
+    },
+    KeyValue {
+        key: &'l str,
+        value: &'l str,
+    },
+}
 
-        remove_trailing_new_line(&mut line);
+/// Header values required to interpret pixel data
+pub(crate) struct ParsedHeader {
+    pub version: Version,
+    pub dimension: i32,
+    pub sizes: Vec<i32>,
+    pub pixel_type: PixelType,
+    pub encoding: Encoding,
+    pub endian: Endian,
+}
+
+/// Validates header line by line independent of how lines are stored
+pub(crate) struct LineParser {
+    version: Version,
+    required_fields: RequiredFields,
+    identifiers: HashSet<String>,
+    line_num: usize,
+}
+
+impl LineParser {
+    /// Starts parsing from the magic line without line break
+    pub(crate) fn new(magic_line: &str) -> Result<Self, ReadNrrdErr> {
+        Ok(Self {
+            version: try_read_magic(magic_line)?,
+            required_fields: RequiredFields::default(),
+            identifiers: HashSet::new(),
+            line_num: 0,
+        })
+    }
+
+    /// Parses next line without line break, an empty line ends the header
+    pub(crate) fn parse_line<'l>(&mut self, line: &'l str) -> Result<HeaderLine<'l>, ReadNrrdErr> {
+        self.line_num += 1;
+
+        if line.is_empty() {
+            // End of header, detached headers may end with EOF
+            return Ok(HeaderLine::End);
+        }
 
         if line.starts_with('#') {
-            // Comment
-            continue;
+            return Ok(HeaderLine::Comment);
         }
 
-        if let Some(field) = try_read_field(&line) {
-            required_fields.parse(&field)?;
-            let exist = !fields.insert(field.clone());
+        if let Some((identifier, descriptor)) = try_read_field(line) {
+            let clean_ident = identifier.to_lowercase();
+            self.required_fields.parse(&clean_ident, descriptor)?;
 
-            if exist {
+            if !self.identifiers.insert(clean_ident) {
                 return Err(ReadNrrdErr::DuplicateField(format!(
                     "Duplicate field '{}' at line {}",
-                    field.identifier, line_num
+                    identifier, self.line_num
                 )));
             }
 
-            continue;
+            return Ok(HeaderLine::Field {
+                identifier,
+                descriptor,
+            });
         }
 
-        if version < Version::Nrrd2 {
+        if self.version < Version::Nrrd2 {
             return Err(ReadNrrdErr::Malformed(format!(
                 "Unexpected line at {}: '{}'",
-                line_num, line
+                self.line_num, line
             )));
         }
 
-        match try_read_key_value(&line) {
-            Some(kv) => key_values.insert(kv),
-            None => {
-                return Err(ReadNrrdErr::Malformed(format!(
-                    "Unexpected line at {}: '{}'",
-                    line_num, line
-                )))
-            }
-        };
+        match try_read_key_value(line) {
+            Some((key, value)) => Ok(HeaderLine::KeyValue { key, value }),
+            None => Err(ReadNrrdErr::Malformed(format!(
+                "Unexpected line at {}: '{}'",
+                self.line_num, line
+            ))),
+        }
     }
 
-    required_fields.validate().map(|required| Nrrd {
-        version,
-        fields,
-        key_values,
-        dimension: required.dimension.unwrap(),
-        sizes: required.sizes.unwrap(),
-        pixel_type: required.pixel_type.unwrap(),
-        encoding: required.encoding.unwrap(),
-        endian: required.endian.unwrap_or(Endian::Little),
-        buffer: Vec::new(),
-    })
+    pub(crate) fn finish(self) -> Result<ParsedHeader, ReadNrrdErr> {
+        let required = self.required_fields.validate()?;
+
+        Ok(ParsedHeader {
+            version: self.version,
+            dimension: required.dimension.unwrap(),
+            sizes: required.sizes.unwrap(),
+            pixel_type: required.pixel_type.unwrap(),
+            encoding: required.encoding.unwrap(),
+            endian: required.endian.unwrap_or(Endian::Little),
+        })
+    }
 }
 
 #[derive(Debug, Default)]
@@ -120,37 +317,37 @@ struct RequiredFields {
 }
 
 impl RequiredFields {
-    fn parse(&mut self, field: &Field) -> Result<(), ReadNrrdErr> {
-        match field.identifier.as_str() {
-            "dimension" => self.try_parse_dimension(field),
-            "sizes" => self.try_parse_sizes(field),
-            "type" => self.try_parse_type(field),
-            "encoding" => self.try_parse_encoding(field),
-            "block size" | "blocksize" => self.try_parse_block_size(field),
-            "endian" => self.try_parse_endian(field),
+    fn parse(&mut self, identifier: &str, descriptor: &str) -> Result<(), ReadNrrdErr> {
+        match identifier {
+            "dimension" => self.try_parse_dimension(descriptor),
+            "sizes" => self.try_parse_sizes(descriptor),
+            "type" => self.try_parse_type(descriptor),
+            "encoding" => self.try_parse_encoding(descriptor),
+            "block size" | "blocksize" => self.try_parse_block_size(descriptor),
+            "endian" => self.try_parse_endian(descriptor),
             _ => Ok(()),
         }
     }
 
-    fn try_parse_dimension(&mut self, field: &Field) -> Result<(), ReadNrrdErr> {
-        let dimension = field.descriptor.parse().map_err(|_| {
-            let err = format!("Invalid DIMENSION value");
+    fn try_parse_dimension(&mut self, descriptor: &str) -> Result<(), ReadNrrdErr> {
+        let dimension = descriptor.parse().map_err(|_| {
+            let err = "Invalid DIMENSION value".to_string();
             ReadNrrdErr::Malformed(err)
         })?;
         self.dimension = Some(dimension);
         Ok(())
     }
 
-    fn try_parse_sizes(&mut self, field: &Field) -> Result<(), ReadNrrdErr> {
+    fn try_parse_sizes(&mut self, descriptor: &str) -> Result<(), ReadNrrdErr> {
         let dimension = match self.dimension {
             Some(d) => d,
             None => {
-                let err = format!("Per-axis specification before DIMENSION");
+                let err = "Per-axis specification before DIMENSION".to_string();
                 return Err(ReadNrrdErr::Malformed(err));
             }
         };
 
-        let all_sizes = field.descriptor.split_whitespace();
+        let all_sizes = descriptor.split_whitespace();
         let mut vec = Vec::new();
 
         for size in all_sizes {
@@ -166,7 +363,7 @@ impl RequiredFields {
             .map_err(|_| ReadNrrdErr::Malformed("Too many SIZES".to_string()))?;
 
         if vec_len != dimension {
-            let err = format!("Mismatched DIMENSION and SIZES");
+            let err = "Mismatched DIMENSION and SIZES".to_string();
             return Err(ReadNrrdErr::Malformed(err));
         }
 
@@ -174,39 +371,39 @@ impl RequiredFields {
         Ok(())
     }
 
-    fn try_parse_type(&mut self, field: &Field) -> Result<(), ReadNrrdErr> {
-        let pixel_type = PixelType::from_str(&field.descriptor).map_err(|_| {
-            let err = format!("Invalid TYPE value");
+    fn try_parse_type(&mut self, descriptor: &str) -> Result<(), ReadNrrdErr> {
+        let pixel_type = PixelType::from_str(descriptor).map_err(|_| {
+            let err = "Invalid TYPE value".to_string();
             ReadNrrdErr::Malformed(err)
         })?;
         self.pixel_type = Some(pixel_type);
         Ok(())
     }
 
-    fn try_parse_encoding(&mut self, field: &Field) -> Result<(), ReadNrrdErr> {
-        let encoding = match field.descriptor.as_str() {
+    fn try_parse_encoding(&mut self, descriptor: &str) -> Result<(), ReadNrrdErr> {
+        let encoding = match descriptor {
             "raw" => Encoding::Raw,
             "ascii" | "text" | "txt" => Encoding::Ascii,
             "gzip" | "gz" => Encoding::GZip,
             "bzip2" | "bz2" => Encoding::BZip2,
-            _ => Encoding::Other(field.descriptor.clone()),
+            _ => Encoding::Other(descriptor.to_string()),
         };
 
         self.encoding = Some(encoding);
         Ok(())
     }
 
-    fn try_parse_block_size(&mut self, field: &Field) -> Result<(), ReadNrrdErr> {
-        let block_size = field.descriptor.parse().map_err(|_| {
-            let err = format!("Invalid BLOCK SIZE value");
+    fn try_parse_block_size(&mut self, descriptor: &str) -> Result<(), ReadNrrdErr> {
+        let block_size = descriptor.parse().map_err(|_| {
+            let err = "Invalid BLOCK SIZE value".to_string();
             ReadNrrdErr::Malformed(err)
         })?;
         self.block_size = Some(block_size);
         Ok(())
     }
 
-    fn try_parse_endian(&mut self, field: &Field) -> Result<(), ReadNrrdErr> {
-        let endian = match field.descriptor.as_str() {
+    fn try_parse_endian(&mut self, descriptor: &str) -> Result<(), ReadNrrdErr> {
+        let endian = match descriptor {
             "little" => Endian::Little,
             "big" => Endian::Big,
             _ => return Err(ReadNrrdErr::Malformed("Invalid ENDIAN value".to_string())),
@@ -278,37 +475,38 @@ fn try_read_magic(magic_line: &str) -> Result<Version, ReadNrrdErr> {
     }
 }
 
-fn try_read_field(line: &str) -> Option<Field> {
+/// Splits field into identifier and descriptor without trailing whitespace
+fn try_read_field(line: &str) -> Option<(&str, &str)> {
     let (ident, desc) = line.split_once(": ")?;
-    let clean_ident = ident.to_lowercase();
-    let clean_desc = desc.trim_end();
-
-    Some(Field {
-        identifier: clean_ident,
-        descriptor: clean_desc.into(),
-    })
+    Some((ident, desc.trim_end()))
 }
 
-fn try_read_key_value(line: &str) -> Option<KeyValue> {
+fn try_read_key_value(line: &str) -> Option<(&str, &str)> {
     let (key, value) = line.split_once(":=")?;
 
     if key.is_empty() {
         return None;
     }
 
-    Some(KeyValue {
-        key: key.into(),
-        value: value.into(),
-    })
+    Some((key, value))
 }
 
-fn validate_buffer_size(nrrd: &Nrrd) -> bool {
-    let buf_size = nrrd.buffer.len();
-    let pixel_size = nrrd.pixel_type.size();
-    let num_pixels = nrrd.sizes.iter().product::<i32>() as usize;
-    let expected_buf_size = pixel_size * num_pixels;
+pub(crate) fn validate_buffer_size(nrrd: &Nrrd) -> Result<(), ReadNrrdErr> {
+    if nrrd.buffer.len() != expected_buffer_size(nrrd)? {
+        return Err(ReadNrrdErr::Malformed("Buffer size mismatch".to_string()));
+    }
 
-    buf_size == expected_buf_size
+    Ok(())
+}
+
+/// Size of the decoded buffer in bytes, negative sizes and sizes that overflow `usize` are malformed
+pub(crate) fn expected_buffer_size(nrrd: &Nrrd) -> Result<usize, ReadNrrdErr> {
+    nrrd.sizes
+        .iter()
+        .try_fold(nrrd.pixel_type.size(), |size, &axis| {
+            size.checked_mul(usize::try_from(axis).ok()?)
+        })
+        .ok_or_else(|| ReadNrrdErr::Malformed("Invalid SIZES value".to_string()))
 }
 
 fn remove_trailing_new_line(line: &mut String) {
@@ -318,3 +516,32 @@ fn remove_trailing_new_line(line: &mut String) {
         line.pop();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{expected_buffer_size, read_header, read_nrrd, ReadNrrdErr};
+
+    fn header(fields: &str) -> String {
+        format!("NRRD0005\ntype: uint16\n{fields}endian: little\nencoding: raw\n\n")
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn buffer_size_of_large_volume() {
+        let header = header("dimension: 3\nsizes: 2048 2048 2048\n");
+        let mut reader = header.as_bytes();
+        let nrrd = read_header(&mut reader).unwrap();
+
+        assert_eq!(expected_buffer_size(&nrrd).unwrap(), 2 * 2048 * 2048 * 2048);
+    }
+
+    #[test]
+    fn overflowing_sizes_are_malformed() {
+        let sizes = "dimension: 4\nsizes: 2147483647 2147483647 2147483647 2147483647\n";
+        let err = read_nrrd(header(sizes).as_bytes()).unwrap_err();
+        assert!(matches!(err, ReadNrrdErr::Malformed(_)));
+
+        let err = read_nrrd(header("dimension: 2\nsizes: 2 -1\n").as_bytes()).unwrap_err();
+        assert!(matches!(err, ReadNrrdErr::Malformed(_)));
+    }
+}
@@ -0,0 +1,8 @@
+//! Helpers shared by tests of the file based readers and writers.
+
+use std::path::PathBuf;
+
+/// Path in the temporary directory, unique to this process
+pub(crate) fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("rusty_nrrd_{}_{name}", std::process::id()))
+}
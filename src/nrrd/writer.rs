@@ -1,33 +1,129 @@
 use super::Field;
-use crate::nrrd::Nrrd;
-use std::io::{BufWriter, Write};
+use crate::nrrd::{
+    codec::{find_codec, Codec},
+    progress::{Cancelled, Progress, Tracked},
+    Encoding, Nrrd,
+};
+use std::{
+    io::{self, BufWriter, Write},
+    sync::Arc,
+};
 
-pub fn write_nrrd<T: Write>(nrrd: &Nrrd, writer: T) -> Result<(), std::io::Error> {
+#[derive(Debug)]
+pub enum WriteNrrdErr {
+    /// No codec is registered for the encoding
+    UnsupportedEncoding(Encoding),
+    /// Encoding is not part of NRRD format and `allow_non_standard_encoding` is not set
+    NonStandardEncoding(Encoding),
+    Cancelled,
+    IOError(std::io::Error),
+}
+
+impl From<std::io::Error> for WriteNrrdErr {
+    #[inline]
+    fn from(err: std::io::Error) -> Self {
+        if Cancelled::is(&err) {
+            return WriteNrrdErr::Cancelled;
+        }
+
+        WriteNrrdErr::IOError(err)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct WriteOptions {
+    /// Allows encodings that other NRRD readers do not understand, such as zstd
+    pub allow_non_standard_encoding: bool,
+    /// Reports encoded bytes of the buffer out of its total size
+    pub progress: Progress,
+}
+
+pub fn write_nrrd<T: Write>(nrrd: &Nrrd, writer: T) -> Result<(), WriteNrrdErr> {
+    write_nrrd_with_options(nrrd, writer, &WriteOptions::default())
+}
+
+pub fn write_nrrd_with_options<T: Write>(
+    nrrd: &Nrrd,
+    writer: T,
+    options: &WriteOptions,
+) -> Result<(), WriteNrrdErr> {
+    let codec = codec_for_writing(nrrd, options)?;
     let mut buf_writer = BufWriter::new(writer);
 
+    write_header(nrrd, codec.as_deref(), &mut buf_writer)?;
+
+    // Write pixel data
+    let total = nrrd.buffer.len() as u64;
+    let mut buffer = Tracked::new(nrrd.buffer.as_slice(), &options.progress, total);
+
+    match codec {
+        Some(codec) => codec.encode(&mut buffer, &mut buf_writer)?,
+        None => {
+            io::copy(&mut buffer, &mut buf_writer)?;
+        }
+    };
+
+    Ok(buf_writer.flush()?)
+}
+
+/// Codec used to encode pixel data, `None` for raw encoding
+pub(crate) fn codec_for_writing(
+    nrrd: &Nrrd,
+    options: &WriteOptions,
+) -> Result<Option<Arc<dyn Codec>>, WriteNrrdErr> {
+    let codec = match find_codec(&nrrd.encoding) {
+        Some(codec) => codec,
+        None if nrrd.encoding == Encoding::Raw => return Ok(None),
+        None => return Err(WriteNrrdErr::UnsupportedEncoding(nrrd.encoding.clone())),
+    };
+
+    if !codec.is_standard() && !options.allow_non_standard_encoding {
+        return Err(WriteNrrdErr::NonStandardEncoding(nrrd.encoding.clone()));
+    }
+
+    Ok(Some(codec))
+}
+
+/// Writes everything up to and including the empty line that separates header and buffer.
+/// Returns the offset of the `sizes` descriptor from the start of the header.
+/// `codec` is the one that encodes pixel data, as returned by `codec_for_writing`.
+pub(crate) fn write_header<T: Write>(
+    nrrd: &Nrrd,
+    codec: Option<&dyn Codec>,
+    writer: &mut T,
+) -> Result<u64, std::io::Error> {
     // Write NRRD version
-    writeln!(buf_writer, "NRRD0005")?;
+    let mut header = String::from("NRRD0005\n");
+
+    if codec.is_some_and(|c| !c.is_standard()) {
+        header += &format!(
+            "# encoding '{}' is not part of NRRD format\n",
+            nrrd.encoding
+        );
+    }
 
     // Write fields in a specific order
     let mut ordered_fields = nrrd.fields.iter().collect::<Vec<_>>();
     ordered_fields.sort_by_key(|f| field_order(f));
 
+    let mut sizes_offset = 0;
     for field in ordered_fields {
-        writeln!(buf_writer, "{}: {}", field.identifier, field.descriptor)?;
+        if field.identifier == "sizes" {
+            sizes_offset = header.len() + "sizes: ".len();
+        }
+        header += &format!("{}: {}\n", field.identifier, field.descriptor);
     }
 
     // Write key-value pairs
     for key_value in &nrrd.key_values {
-        writeln!(buf_writer, "{}:={}", key_value.key, key_value.value)?;
+        header += &format!("{}:={}\n", key_value.key, key_value.value);
     }
 
     // Empty line between header and buffer
-    writeln!(buf_writer)?;
-
-    // Write pixel data
-    buf_writer.write_all(&nrrd.buffer)?;
+    header.push('\n');
 
-    Ok(())
+    writer.write_all(header.as_bytes())?;
+    Ok(sizes_offset as u64)
 }
 
 fn field_order(f: &Field) -> usize {
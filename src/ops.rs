@@ -0,0 +1,235 @@
+//! Arithmetic between images of equal size and between images and scalars.
+//!
+//! Integer pixels saturate at the bounds of their type instead of overflowing,
+//! division by zero panics as it does for the pixel type itself.
+//! Images with different sizes panic. Views take part like images, `ImageViewMut` is updated in place.
+
+use crate::{
+    image::Image,
+    pixel::NumericPixel,
+    storage::{Storage, StorageMut},
+    view::{ImageView, ImageViewMut},
+};
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Sub, SubAssign};
+
+macro_rules! impl_image_op {
+    ($op: ident, $method: ident, $op_assign: ident, $method_assign: ident, $saturating: ident) => {
+        impl<T: NumericPixel, const D: usize, S: Storage<T>, S2: Storage<T>> $op<&Image<T, D, S2>>
+            for &Image<T, D, S>
+        {
+            type Output = Image<T, D>;
+
+            #[inline]
+            fn $method(self, rhs: &Image<T, D, S2>) -> Self::Output {
+                self.zip_map(rhs, |&a, &b| a.$saturating(b))
+            }
+        }
+
+        impl<T: NumericPixel, const D: usize, S: StorageMut<T>, S2: Storage<T>>
+            $op<&Image<T, D, S2>> for Image<T, D, S>
+        {
+            type Output = Image<T, D, S>;
+
+            #[inline]
+            fn $method(mut self, rhs: &Image<T, D, S2>) -> Self::Output {
+                self.$method_assign(rhs);
+                self
+            }
+        }
+
+        impl<T: NumericPixel, const D: usize, S: Storage<T>> $op<T> for &Image<T, D, S> {
+            type Output = Image<T, D>;
+
+            #[inline]
+            fn $method(self, rhs: T) -> Self::Output {
+                self.map(|&a| a.$saturating(rhs))
+            }
+        }
+
+        impl<T: NumericPixel, const D: usize, S: StorageMut<T>> $op<T> for Image<T, D, S> {
+            type Output = Image<T, D, S>;
+
+            #[inline]
+            fn $method(mut self, rhs: T) -> Self::Output {
+                self.$method_assign(rhs);
+                self
+            }
+        }
+
+        impl<T: NumericPixel, const D: usize, S: StorageMut<T>, S2: Storage<T>>
+            $op_assign<&Image<T, D, S2>> for Image<T, D, S>
+        {
+            #[inline]
+            fn $method_assign(&mut self, rhs: &Image<T, D, S2>) {
+                self.zip_map_in_place(rhs, |&a, &b| a.$saturating(b));
+            }
+        }
+
+        impl<T: NumericPixel, const D: usize, S: StorageMut<T>> $op_assign<T> for Image<T, D, S> {
+            #[inline]
+            fn $method_assign(&mut self, rhs: T) {
+                self.map_in_place(|&a| a.$saturating(rhs));
+            }
+        }
+
+        impl<T: NumericPixel, const D: usize, S: Storage<T>> $op<ImageView<'_, T, D>>
+            for &Image<T, D, S>
+        {
+            type Output = Image<T, D>;
+
+            #[inline]
+            fn $method(self, rhs: ImageView<'_, T, D>) -> Self::Output {
+                self.zip_map(rhs, |&a, &b| a.$saturating(b))
+            }
+        }
+
+        impl<T: NumericPixel, const D: usize, S: StorageMut<T>> $op<ImageView<'_, T, D>>
+            for Image<T, D, S>
+        {
+            type Output = Image<T, D, S>;
+
+            #[inline]
+            fn $method(mut self, rhs: ImageView<'_, T, D>) -> Self::Output {
+                self.$method_assign(rhs);
+                self
+            }
+        }
+
+        impl<T: NumericPixel, const D: usize, S: StorageMut<T>> $op_assign<ImageView<'_, T, D>>
+            for Image<T, D, S>
+        {
+            #[inline]
+            fn $method_assign(&mut self, rhs: ImageView<'_, T, D>) {
+                self.zip_map_in_place(rhs, |&a, &b| a.$saturating(b));
+            }
+        }
+
+        impl<T: NumericPixel, const D: usize, S: Storage<T>> $op<&Image<T, D, S>>
+            for ImageView<'_, T, D>
+        {
+            type Output = Image<T, D>;
+
+            #[inline]
+            fn $method(self, rhs: &Image<T, D, S>) -> Self::Output {
+                self.zip_map(rhs, |&a, &b| a.$saturating(b))
+            }
+        }
+
+        impl<T: NumericPixel, const D: usize> $op<ImageView<'_, T, D>> for ImageView<'_, T, D> {
+            type Output = Image<T, D>;
+
+            #[inline]
+            fn $method(self, rhs: ImageView<'_, T, D>) -> Self::Output {
+                self.zip_map(rhs, |&a, &b| a.$saturating(b))
+            }
+        }
+
+        impl<T: NumericPixel, const D: usize> $op<T> for ImageView<'_, T, D> {
+            type Output = Image<T, D>;
+
+            #[inline]
+            fn $method(self, rhs: T) -> Self::Output {
+                self.map(|&a| a.$saturating(rhs))
+            }
+        }
+
+        impl<T: NumericPixel, const D: usize, S: Storage<T>> $op_assign<&Image<T, D, S>>
+            for ImageViewMut<'_, T, D>
+        {
+            #[inline]
+            fn $method_assign(&mut self, rhs: &Image<T, D, S>) {
+                self.zip_map_in_place(rhs, |&a, &b| a.$saturating(b));
+            }
+        }
+
+        impl<T: NumericPixel, const D: usize> $op_assign<ImageView<'_, T, D>>
+            for ImageViewMut<'_, T, D>
+        {
+            #[inline]
+            fn $method_assign(&mut self, rhs: ImageView<'_, T, D>) {
+                self.zip_map_in_place(rhs, |&a, &b| a.$saturating(b));
+            }
+        }
+
+        impl<T: NumericPixel, const D: usize> $op_assign<T> for ImageViewMut<'_, T, D> {
+            #[inline]
+            fn $method_assign(&mut self, rhs: T) {
+                self.map_in_place(|&a| a.$saturating(rhs));
+            }
+        }
+    };
+}
+
+impl_image_op!(Add, add, AddAssign, add_assign, saturating_add);
+impl_image_op!(Sub, sub, SubAssign, sub_assign, saturating_sub);
+impl_image_op!(Mul, mul, MulAssign, mul_assign, saturating_mul);
+impl_image_op!(Div, div, DivAssign, div_assign, saturating_div);
+
+#[cfg(test)]
+mod tests {
+    use crate::{image::Image, pixel::NumericPixel};
+
+    fn image<T: NumericPixel>(pixels: &[T]) -> Image<T, 1> {
+        Image::from_vec([pixels.len()], pixels.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn integers_saturate() {
+        let a = image::<u8>(&[250, 3, 100, 0]);
+        let b = image::<u8>(&[10, 5, 3, 0]);
+
+        assert_eq!((&a + &b).pixels(), &[255, 8, 103, 0]);
+        assert_eq!((&a - &b).pixels(), &[240, 0, 97, 0]);
+        assert_eq!((&a * &b).pixels(), &[255, 15, 255, 0]);
+        assert_eq!((&a / 2).pixels(), &[125, 1, 50, 0]);
+
+        let signed = image::<i8>(&[i8::MIN, -100, 100]);
+        assert_eq!((&signed / -1).pixels(), &[i8::MAX, 100, -100]);
+        assert_eq!((&signed - 100).pixels(), &[i8::MIN, i8::MIN, 0]);
+        assert_eq!((&signed * 2).pixels(), &[i8::MIN, i8::MIN, i8::MAX]);
+
+        let mut sum = image::<i64>(&[i64::MAX, i64::MIN]);
+        sum += 1;
+        sum -= &image(&[0, 1]);
+        assert_eq!(sum.pixels(), &[i64::MAX, i64::MIN]);
+    }
+
+    #[test]
+    fn floats_follow_ieee() {
+        let a = image::<f32>(&[f32::MAX, 1.0, -1.0]);
+        assert_eq!((&a * 2.0).pixels(), &[f32::INFINITY, 2.0, -2.0]);
+
+        let quotient = &a / 0.0;
+        assert_eq!(quotient[[1]], f32::INFINITY);
+        assert_eq!(quotient[[2]], f32::NEG_INFINITY);
+        assert!((&image::<f64>(&[0.0]) / 0.0)[[0]].is_nan());
+    }
+
+    #[test]
+    #[should_panic]
+    fn integer_division_by_zero_panics() {
+        let _ = &image::<u16>(&[1, 2]) / 0;
+    }
+
+    #[test]
+    #[should_panic]
+    fn different_sizes_panic() {
+        let _ = &image::<u8>(&[1, 2]) + &image(&[1, 2, 3]);
+    }
+
+    #[test]
+    fn views_take_part_in_place() {
+        let mut image = Image::<u8, 2>::from_fn([3, 2], |[x, y]| (x + 3 * y) as u8 * 50);
+        let ones = Image::<u8, 2>::new(1, [2, 2]);
+
+        let flipped = image.view().flip(0);
+        assert_eq!((flipped + 10).pixels(), &[110, 60, 10, 255, 210, 160]);
+        let region = image.view().region([1, 0], [2, 2]).unwrap();
+        assert_eq!((region - &ones).pixels(), &[49, 99, 199, 249]);
+
+        let mut view = image.view_mut().into_region([0, 1], [2, 1]).unwrap();
+        view *= 2;
+        view += ones.view().region([0, 0], [2, 1]).unwrap();
+        assert_eq!(image.pixels(), &[0, 50, 100, 255, 255, 250]);
+    }
+}
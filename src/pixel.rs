@@ -1,11 +1,87 @@
-use crate::nrrd::{Endian, PixelType};
+use crate::{
+    convert::ConversionPolicy,
+    nrrd::{Endian, PixelType},
+};
+use std::ops::{Add, Div, Mul, Sub};
 
 pub trait PixelValue: Sized + Default + Clone {
     fn from_bytes(buffer: &[u8], endian: Endian) -> Self;
     fn to_bytes(&self, buffer: &mut [u8], endian: Endian);
+    /// Type of a single component
     fn pixel_type() -> PixelType;
+
+    /// Number of components, stored along an extra fastest axis for non-scalar pixels
+    #[inline]
+    fn components() -> usize {
+        1
+    }
+
+    /// `kinds` entry written for the component axis, `None` for scalars which have no such axis
+    #[inline]
+    fn kind() -> Option<&'static str> {
+        None
+    }
+
+    /// Returns `true` if a component axis of `kind` can be folded into this pixel
+    #[inline]
+    fn accepts_kind(kind: &str) -> bool {
+        Self::kind().is_some_and(|k| k.eq_ignore_ascii_case(kind))
+    }
+
+    /// Size of the whole pixel in bytes
+    #[inline]
+    fn pixel_size() -> usize {
+        Self::pixel_type().size() * Self::components()
+    }
+}
+
+/// Pixel whose in-memory representation is its native endian byte layout,
+/// so that raw buffers can be viewed as slices of it without copying.
+///
+/// # Safety
+/// Type must have no padding, its size must equal `pixel_size()`, components must be laid out in order
+/// and every bit pattern of its size must be a valid value.
+pub unsafe trait PodPixel: PixelValue + Copy {}
+
+/// Scalar pixel that supports arithmetic and conversion between numeric types
+pub trait NumericPixel:
+    PixelValue
+    + Copy
+    + PartialOrd
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+{
+    const ZERO: Self;
+    const ONE: Self;
+    /// Smallest finite value
+    const MIN: Self;
+    /// Largest finite value
+    const MAX: Self;
+
+    /// Integers clamp at `MIN` and `MAX`, floats follow IEEE rules
+    fn saturating_add(self, rhs: Self) -> Self;
+    fn saturating_sub(self, rhs: Self) -> Self;
+    fn saturating_mul(self, rhs: Self) -> Self;
+    /// Panics on integer division by zero
+    fn saturating_div(self, rhs: Self) -> Self;
+
+    fn to_f64(self) -> f64;
+
+    /// Exact value for integer types, `None` for floating point ones
+    fn to_i128(self) -> Option<i128>;
+
+    /// Returns `None` if the value cannot be represented under `policy`
+    fn from_f64(value: f64, policy: ConversionPolicy) -> Option<Self>;
+
+    /// Returns `None` if the value cannot be represented under `policy`
+    fn from_i128(value: i128, policy: ConversionPolicy) -> Option<Self>;
 }
 
+/// Integer pixel, whose values are evenly spaced between `MIN` and `MAX`
+pub trait IntegerPixel: NumericPixel {}
+
 macro_rules! impl_pixel_value {
     ($type: ty, $pixel_type: expr) => {
         impl PixelValue for $type {
@@ -31,6 +107,8 @@ macro_rules! impl_pixel_value {
                 $pixel_type
             }
         }
+
+        unsafe impl PodPixel for $type {}
     };
 }
 
@@ -44,3 +122,144 @@ impl_pixel_value!(i64, PixelType::Int64);
 impl_pixel_value!(u64, PixelType::UInt64);
 impl_pixel_value!(f32, PixelType::Float32);
 impl_pixel_value!(f64, PixelType::Float64);
+
+macro_rules! impl_numeric_int {
+    ($type: ty) => {
+        impl NumericPixel for $type {
+            const ZERO: Self = 0;
+            const ONE: Self = 1;
+            const MIN: Self = <$type>::MIN;
+            const MAX: Self = <$type>::MAX;
+
+            #[inline]
+            fn saturating_add(self, rhs: Self) -> Self {
+                <$type>::saturating_add(self, rhs)
+            }
+
+            #[inline]
+            fn saturating_sub(self, rhs: Self) -> Self {
+                <$type>::saturating_sub(self, rhs)
+            }
+
+            #[inline]
+            fn saturating_mul(self, rhs: Self) -> Self {
+                <$type>::saturating_mul(self, rhs)
+            }
+
+            #[inline]
+            fn saturating_div(self, rhs: Self) -> Self {
+                <$type>::saturating_div(self, rhs)
+            }
+
+            #[inline]
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+
+            #[inline]
+            fn to_i128(self) -> Option<i128> {
+                Some(self as i128)
+            }
+
+            fn from_f64(value: f64, policy: ConversionPolicy) -> Option<Self> {
+                match policy {
+                    ConversionPolicy::Exact if value.fract() != 0.0 || !value.is_finite() => None,
+                    ConversionPolicy::Exact => Self::from_i128(value as i128, policy),
+                    // `as` saturates and maps NaN to 0
+                    ConversionPolicy::Saturating => Some(value as $type),
+                    ConversionPolicy::Rounding => Some(value.round() as $type),
+                }
+            }
+
+            fn from_i128(value: i128, policy: ConversionPolicy) -> Option<Self> {
+                match policy {
+                    ConversionPolicy::Exact => <$type>::try_from(value).ok(),
+                    ConversionPolicy::Saturating | ConversionPolicy::Rounding => {
+                        Some(value.clamp(<$type>::MIN as i128, <$type>::MAX as i128) as $type)
+                    }
+                }
+            }
+        }
+
+        impl IntegerPixel for $type {}
+    };
+}
+
+macro_rules! impl_numeric_float {
+    ($type: ty) => {
+        impl NumericPixel for $type {
+            const ZERO: Self = 0.0;
+            const ONE: Self = 1.0;
+            const MIN: Self = <$type>::MIN;
+            const MAX: Self = <$type>::MAX;
+
+            #[inline]
+            fn saturating_add(self, rhs: Self) -> Self {
+                self + rhs
+            }
+
+            #[inline]
+            fn saturating_sub(self, rhs: Self) -> Self {
+                self - rhs
+            }
+
+            #[inline]
+            fn saturating_mul(self, rhs: Self) -> Self {
+                self * rhs
+            }
+
+            #[inline]
+            fn saturating_div(self, rhs: Self) -> Self {
+                self / rhs
+            }
+
+            #[inline]
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+
+            #[inline]
+            fn to_i128(self) -> Option<i128> {
+                None
+            }
+
+            fn from_f64(value: f64, policy: ConversionPolicy) -> Option<Self> {
+                let converted = match policy {
+                    ConversionPolicy::Exact => value as $type,
+                    _ if value.is_finite() => {
+                        value.clamp(<$type>::MIN as f64, <$type>::MAX as f64) as $type
+                    }
+                    _ => value as $type,
+                };
+
+                if policy == ConversionPolicy::Exact && converted as f64 != value && !value.is_nan()
+                {
+                    return None;
+                }
+
+                Some(converted)
+            }
+
+            fn from_i128(value: i128, policy: ConversionPolicy) -> Option<Self> {
+                let converted = value as $type;
+
+                if policy == ConversionPolicy::Exact && converted as i128 != value {
+                    return None;
+                }
+
+                Some(converted)
+            }
+        }
+    };
+}
+
+impl_numeric_int!(i8);
+impl_numeric_int!(u8);
+impl_numeric_int!(i16);
+impl_numeric_int!(u16);
+impl_numeric_int!(i32);
+impl_numeric_int!(u32);
+impl_numeric_int!(i64);
+impl_numeric_int!(u64);
+impl_numeric_float!(f32);
+impl_numeric_float!(f64);
@@ -0,0 +1,692 @@
+//! Resampling onto a new grid in the spirit of `unu resample`.
+//!
+//! Axes are resampled one after another with a separable `Kernel`. Sizes and spacings given as
+//! the target keep the centre of the image in place; a reference `Grid` places pixels exactly.
+//! `Nrrd::resample` also updates `space directions`, `space origin`, `spacings` and axis bounds.
+//! `Grid::crop` and `Grid::pad` place images cropped or padded with `Image::crop` and `Image::pad`.
+
+use crate::{
+    axes::{AxisErr, AxisFields},
+    convert::ConversionPolicy,
+    image::{Image, ImageFromNrrdErr},
+    neighbourhood::Boundary,
+    nrrd::Nrrd,
+    pixel::NumericPixel,
+    storage::Storage,
+    view::ImageView,
+};
+use std::f64::consts::PI;
+
+#[derive(Debug)]
+pub enum ResampleErr {
+    DimensionsDoNotMatch,
+    SizesDoNotMatch,
+    InvalidSpacing,
+    InvalidKernel,
+    EmptyImage,
+    CannotReadImage(ImageFromNrrdErr),
+    /// Sizes of the header do not match its buffer
+    InvalidAxes(AxisErr),
+}
+
+impl From<ImageFromNrrdErr> for ResampleErr {
+    #[inline]
+    fn from(value: ImageFromNrrdErr) -> Self {
+        Self::CannotReadImage(value)
+    }
+}
+
+impl From<AxisErr> for ResampleErr {
+    #[inline]
+    fn from(value: AxisErr) -> Self {
+        Self::InvalidAxes(value)
+    }
+}
+
+/// Weights given to pixels around a sampled position
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kernel {
+    /// Value of the closest pixel, never mixes values, so labels stay valid
+    Nearest,
+    /// Linear interpolation between the two closest pixels
+    Linear,
+    /// Cubic B-spline, smooth but blurs slightly as it does not pass through the pixels
+    CubicBSpline,
+    /// sinc windowed by a sinc stretched to reach `radius` pixels to both sides
+    Lanczos(usize),
+    /// sinc windowed by a Hann window reaching `radius` pixels to both sides
+    WindowedSinc(usize),
+}
+
+impl Kernel {
+    /// Pixels reached to both sides of a sampled position
+    #[inline]
+    fn radius(&self) -> f64 {
+        match self {
+            Self::Nearest => 0.5,
+            Self::Linear => 1.0,
+            Self::CubicBSpline => 2.0,
+            Self::Lanczos(radius) | Self::WindowedSinc(radius) => *radius as f64,
+        }
+    }
+
+    /// Weight of a pixel at distance `x`
+    fn weight(&self, x: f64) -> f64 {
+        let x = x.abs();
+        if x >= self.radius() {
+            return 0.0;
+        }
+
+        match self {
+            Self::Nearest => 1.0,
+            Self::Linear => 1.0 - x,
+            Self::CubicBSpline if x < 1.0 => (4.0 - 6.0 * x * x + 3.0 * x * x * x) / 6.0,
+            Self::CubicBSpline => (2.0 - x).powi(3) / 6.0,
+            Self::Lanczos(radius) => sinc(x) * sinc(x / *radius as f64),
+            Self::WindowedSinc(radius) => sinc(x) * (0.5 + 0.5 * (PI * x / *radius as f64).cos()),
+        }
+    }
+}
+
+#[inline]
+fn sinc(x: f64) -> f64 {
+    match x == 0.0 {
+        true => 1.0,
+        false => (PI * x).sin() / (PI * x),
+    }
+}
+
+/// Placement of pixels along each axis: pixel `i` lies at `origin + i * spacing`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Grid<const D: usize> {
+    pub sizes: [usize; D],
+    pub spacings: [f64; D],
+    pub origin: [f64; D],
+}
+
+impl<const D: usize> Grid<D> {
+    /// Grid where positions are pixel indices
+    #[inline]
+    pub fn new(sizes: [usize; D]) -> Self {
+        Self {
+            sizes,
+            spacings: [1.0; D],
+            origin: [0.0; D],
+        }
+    }
+
+    /// Placement of the region of `sizes` pixels starting at `origin`, as returned by `Image::crop`
+    pub fn crop(&self, origin: [usize; D], sizes: [usize; D]) -> Self {
+        let mut cropped = *self;
+        cropped.sizes = sizes;
+        for ((o, &start), &spacing) in cropped.origin.iter_mut().zip(&origin).zip(&self.spacings) {
+            *o += start as f64 * spacing;
+        }
+        cropped
+    }
+
+    /// Placement of the image grown by `lower` and `upper` pixels, as returned by `Image::pad`
+    pub fn pad(&self, lower: [usize; D], upper: [usize; D]) -> Self {
+        let mut padded = *self;
+        for axis in 0..D {
+            padded.sizes[axis] += lower[axis] + upper[axis];
+            padded.origin[axis] -= lower[axis] as f64 * self.spacings[axis];
+        }
+        padded
+    }
+}
+
+/// Where pixels of a resampled image lie
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResampleTarget<const D: usize> {
+    /// Number of pixels along each axis, spacings change so that the extent stays the same
+    Sizes([usize; D]),
+    /// Distance between pixels along each axis, sizes are rounded to keep the extent
+    Spacings([f64; D]),
+    /// Grid of a reference image
+    Grid(Grid<D>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResampleOptions {
+    pub kernel: Kernel,
+    /// Widens the kernel by the downsampling factor so that fine detail is averaged
+    /// instead of aliased. Has no effect on `Kernel::Nearest`.
+    pub anti_alias: bool,
+    /// How pixels outside of the source are read
+    pub boundary: Boundary<f64>,
+}
+
+impl Default for ResampleOptions {
+    fn default() -> Self {
+        Self {
+            kernel: Kernel::Linear,
+            anti_alias: true,
+            boundary: Boundary::Clamp,
+        }
+    }
+}
+
+/// Positions sampled along one axis, in source pixels
+#[derive(Debug, Clone, Copy)]
+struct AxisSampling {
+    first: f64,
+    step: f64,
+    size: usize,
+}
+
+/// Source pixels and weights contributing to one target pixel, `None` reads the boundary constant
+type Taps = Vec<(Option<usize>, f64)>;
+
+impl<T: NumericPixel, const D: usize, S: Storage<T>> Image<T, D, S> {
+    /// Same as `ImageView::resample`
+    #[inline]
+    pub fn resample(
+        &self,
+        grid: &Grid<D>,
+        target: &ResampleTarget<D>,
+        options: &ResampleOptions,
+    ) -> Result<(Image<T, D>, Grid<D>), ResampleErr> {
+        self.view().resample(grid, target, options)
+    }
+}
+
+impl<T: NumericPixel, const D: usize> ImageView<'_, T, D> {
+    /// Resamples view whose pixels lie on `grid` onto `target`, returns the result and its grid
+    pub fn resample(
+        &self,
+        grid: &Grid<D>,
+        target: &ResampleTarget<D>,
+        options: &ResampleOptions,
+    ) -> Result<(Image<T, D>, Grid<D>), ResampleErr> {
+        if grid.sizes != *self.sizes() {
+            return Err(ResampleErr::SizesDoNotMatch);
+        }
+
+        let samplings = samplings(grid, [false; D], target)?;
+        let image = self.resample_with(&samplings, options)?;
+
+        let mut resampled = Grid::new(*image.sizes());
+        for (axis, sampling) in samplings.iter().enumerate() {
+            resampled.spacings[axis] = sampling.step * grid.spacings[axis];
+            resampled.origin[axis] = grid.origin[axis] + sampling.first * grid.spacings[axis];
+        }
+
+        Ok((image, resampled))
+    }
+
+    fn resample_with(
+        &self,
+        samplings: &[AxisSampling; D],
+        options: &ResampleOptions,
+    ) -> Result<Image<T, D>, ResampleErr> {
+        if let Kernel::Lanczos(0) | Kernel::WindowedSinc(0) = options.kernel {
+            return Err(ResampleErr::InvalidKernel);
+        }
+        if self.sizes().contains(&0) && !matches!(options.boundary, Boundary::Constant(_)) {
+            return Err(ResampleErr::EmptyImage);
+        }
+
+        let constant = match options.boundary {
+            Boundary::Constant(value) => value,
+            _ => 0.0,
+        };
+
+        if options.kernel == Kernel::Nearest {
+            return Ok(self.resample_nearest(samplings, &options.boundary, constant));
+        }
+
+        let mut sizes = *self.sizes();
+        let mut values = self.iter().map(|v| v.to_f64()).collect::<Vec<_>>();
+        for (axis, sampling) in samplings.iter().enumerate() {
+            let taps = axis_taps(sizes[axis], sampling, options);
+            values = resample_axis(&values, &sizes, axis, &taps, constant);
+            sizes[axis] = sampling.size;
+        }
+
+        let buffer = values
+            .into_iter()
+            .map(|v| {
+                T::from_f64(v, ConversionPolicy::Rounding)
+                    .expect("rounding conversion always succeeds")
+            })
+            .collect();
+        Ok(Image::from_parts(buffer, sizes))
+    }
+
+    /// Copies closest pixels without mixing values
+    fn resample_nearest(
+        &self,
+        samplings: &[AxisSampling; D],
+        boundary: &Boundary<f64>,
+        constant: f64,
+    ) -> Image<T, D> {
+        let sources = std::array::from_fn::<_, D, _>(|axis| {
+            let sampling = samplings[axis];
+            (0..sampling.size)
+                .map(|i| {
+                    let position = sampling.first + i as f64 * sampling.step;
+                    let closest = (position + 0.5).floor() as isize;
+                    boundary.resolve(closest, self.sizes()[axis]).ok()
+                })
+                .collect::<Vec<_>>()
+        });
+
+        let constant = T::from_f64(constant, ConversionPolicy::Rounding)
+            .expect("rounding conversion always succeeds");
+        let sizes = samplings.map(|s| s.size);
+
+        Image::from_fn(sizes, |index| {
+            let mut source = [0; D];
+            for (axis, (s, &i)) in source.iter_mut().zip(&index).enumerate() {
+                match sources[axis][i] {
+                    Some(resolved) => *s = resolved,
+                    None => return constant,
+                }
+            }
+            *self.get(&source)
+        })
+    }
+}
+
+impl Nrrd {
+    /// Placement of pixels along each axis, measured along the axis.
+    /// Taken from `space directions` and `space origin`, which are assumed orthogonal,
+    /// or else from `spacings` and axis bounds; unknown axes have unit spacing and start at zero.
+    /// Fails with `InvalidAxes` if sizes do not match the buffer.
+    pub fn grid<const D: usize>(&self) -> Result<Grid<D>, ResampleErr> {
+        let sizes = self.checked_sizes()?;
+        if sizes.len() != D {
+            return Err(ResampleErr::DimensionsDoNotMatch);
+        }
+
+        let fields = AxisFields::read(self);
+        let mut grid = Grid::new(std::array::from_fn(|axis| sizes[axis]));
+        for axis in 0..D {
+            let (first, step) = fields.placement(axis, grid.sizes[axis]);
+            grid.origin[axis] = first;
+            grid.spacings[axis] = step;
+        }
+
+        Ok(grid)
+    }
+
+    /// Resamples pixels of type `T` onto `target`, updating geometry of the header
+    pub fn resample<T: NumericPixel, const D: usize>(
+        &self,
+        target: &ResampleTarget<D>,
+        options: &ResampleOptions,
+    ) -> Result<Nrrd, ResampleErr> {
+        let image = Image::<T, D>::try_from(self)?;
+        let grid = self.grid::<D>()?;
+        let mut fields = AxisFields::read(self);
+        let is_node = std::array::from_fn(|axis| fields.is_node(axis));
+        let samplings = samplings(&grid, is_node, target)?;
+        let image = image.view().resample_with(&samplings, options)?;
+
+        let pixel_size = T::pixel_size();
+        let mut buffer = vec![0; image.pixels_count() * pixel_size];
+        for (bytes, pixel) in buffer.chunks_exact_mut(pixel_size).zip(image.pixels()) {
+            pixel.to_bytes(bytes, self.endian());
+        }
+
+        for (axis, sampling) in samplings.iter().enumerate() {
+            fields.resample(
+                axis,
+                grid.sizes[axis],
+                sampling.first,
+                sampling.step,
+                sampling.size,
+            );
+        }
+
+        Ok(fields.apply(self.with_axes(image.sizes(), buffer)))
+    }
+}
+
+/// Positions to sample along each axis of an image placed on `source`.
+/// Sizes and spacings span the same extent as the source: from the first to the last pixel
+/// on axes where `is_node`, from edge to edge of the pixels otherwise.
+fn samplings<const D: usize>(
+    source: &Grid<D>,
+    is_node: [bool; D],
+    target: &ResampleTarget<D>,
+) -> Result<[AxisSampling; D], ResampleErr> {
+    let is_valid = |spacing: &f64| spacing.is_finite() && *spacing > 0.0;
+    if !source.spacings.iter().all(is_valid) {
+        return Err(ResampleErr::InvalidSpacing);
+    }
+
+    // Centres of source and target stay in place
+    let centred = |axis: usize, step: f64, size: usize| {
+        let old_size = source.sizes[axis] as f64;
+        AxisSampling {
+            first: (old_size - 1.0) / 2.0 - (size as f64 - 1.0) * step / 2.0,
+            step,
+            size,
+        }
+    };
+
+    let samplings = match target {
+        ResampleTarget::Sizes(sizes) => std::array::from_fn(|axis| {
+            let old_size = source.sizes[axis] as f64;
+            let step = match (sizes[axis], is_node[axis]) {
+                (0, _) | (1, true) => 1.0,
+                (size, true) => (old_size - 1.0).max(0.0) / (size - 1) as f64,
+                (size, false) => old_size / size as f64,
+            };
+            centred(axis, step, sizes[axis])
+        }),
+        ResampleTarget::Spacings(spacings) => {
+            if !spacings.iter().all(is_valid) {
+                return Err(ResampleErr::InvalidSpacing);
+            }
+            std::array::from_fn(|axis| {
+                let step = spacings[axis] / source.spacings[axis];
+                let old_size = source.sizes[axis] as f64;
+                let size = match is_node[axis] {
+                    true => ((old_size - 1.0).max(0.0) / step).round() + 1.0,
+                    false => (old_size / step).round().max(1.0),
+                };
+                centred(axis, step, size as usize)
+            })
+        }
+        ResampleTarget::Grid(grid) => {
+            if !grid.spacings.iter().all(is_valid) || !grid.origin.iter().all(|o| o.is_finite()) {
+                return Err(ResampleErr::InvalidSpacing);
+            }
+            std::array::from_fn(|axis| AxisSampling {
+                first: (grid.origin[axis] - source.origin[axis]) / source.spacings[axis],
+                step: grid.spacings[axis] / source.spacings[axis],
+                size: grid.sizes[axis],
+            })
+        }
+    };
+
+    Ok(samplings)
+}
+
+/// Normalised weights of source pixels for every target pixel along an axis of `old_size` pixels
+fn axis_taps(old_size: usize, sampling: &AxisSampling, options: &ResampleOptions) -> Vec<Taps> {
+    let stretch = match options.anti_alias && sampling.step > 1.0 {
+        true => sampling.step,
+        false => 1.0,
+    };
+    let reach = options.kernel.radius() * stretch;
+
+    (0..sampling.size)
+        .map(|j| {
+            let position = sampling.first + j as f64 * sampling.step;
+            let lowest = (position - reach).ceil() as isize;
+            let highest = (position + reach).floor() as isize;
+
+            let mut taps = (lowest..=highest)
+                .filter_map(|i| {
+                    let weight = options.kernel.weight((i as f64 - position) / stretch);
+                    (weight != 0.0).then(|| (options.boundary.resolve(i, old_size).ok(), weight))
+                })
+                .collect::<Taps>();
+
+            let total = taps.iter().map(|(_, w)| w).sum::<f64>();
+            if total != 0.0 {
+                taps.iter_mut().for_each(|(_, w)| *w /= total);
+            }
+            taps
+        })
+        .collect()
+}
+
+/// Applies `taps` along `axis` of `values`, which are laid out with the first axis fastest
+fn resample_axis(
+    values: &[f64],
+    sizes: &[usize],
+    axis: usize,
+    taps: &[Taps],
+    constant: f64,
+) -> Vec<f64> {
+    let inner = sizes[..axis].iter().product::<usize>();
+    let outer = sizes[axis + 1..].iter().product::<usize>();
+    let old_size = sizes[axis];
+
+    let mut resampled = Vec::with_capacity(inner * taps.len() * outer);
+    for o in 0..outer {
+        for pixel_taps in taps {
+            for k in 0..inner {
+                let value = pixel_taps
+                    .iter()
+                    .map(|&(source, weight)| match source {
+                        Some(i) => weight * values[(o * old_size + i) * inner + k],
+                        None => weight * constant,
+                    })
+                    .sum();
+                resampled.push(value);
+            }
+        }
+    }
+
+    resampled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Grid, Kernel, ResampleErr, ResampleOptions, ResampleTarget};
+    use crate::{axes::AxisErr, image::Image, neighbourhood::Boundary, nrrd::Nrrd};
+
+    const KERNELS: [Kernel; 5] = [
+        Kernel::Nearest,
+        Kernel::Linear,
+        Kernel::CubicBSpline,
+        Kernel::Lanczos(3),
+        Kernel::WindowedSinc(3),
+    ];
+
+    fn options(kernel: Kernel) -> ResampleOptions {
+        ResampleOptions {
+            kernel,
+            ..Default::default()
+        }
+    }
+
+    fn ramp() -> Image<f64, 1> {
+        Image::from_fn([16], |[i]| 2.0 * i as f64 + 1.0)
+    }
+
+    #[test]
+    fn every_kernel_keeps_constants() {
+        let image = Image::<f64, 2>::new(7.5, [9, 6]);
+        let grid = Grid::new(*image.sizes());
+
+        for kernel in KERNELS {
+            for sizes in [[20, 13], [4, 2]] {
+                let target = ResampleTarget::Sizes(sizes);
+                let (resampled, _) = image.resample(&grid, &target, &options(kernel)).unwrap();
+                assert_eq!(resampled.sizes(), &sizes);
+                assert!(
+                    resampled.pixels().iter().all(|&v| (v - 7.5).abs() < 1e-9),
+                    "{kernel:?} to {sizes:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn every_kernel_follows_a_linear_ramp() {
+        let image = ramp();
+        // Whole and half pixel positions away from the edges, where every kernel fits
+        let target = ResampleTarget::Grid(Grid {
+            sizes: [9],
+            spacings: [0.5],
+            origin: [4.0],
+        });
+
+        for kernel in KERNELS {
+            let (resampled, grid) = image
+                .resample(&Grid::new([16]), &target, &options(kernel))
+                .unwrap();
+            assert_eq!(grid.origin, [4.0]);
+            assert_eq!(grid.spacings, [0.5]);
+
+            for (j, &value) in resampled.pixels().iter().enumerate() {
+                let position = 4.0 + 0.5 * j as f64;
+                let expected = match kernel {
+                    Kernel::Nearest => 2.0 * (position + 0.5).floor() + 1.0,
+                    _ => 2.0 * position + 1.0,
+                };
+                assert!((value - expected).abs() < 1e-9, "{kernel:?} at {position}");
+            }
+        }
+    }
+
+    #[test]
+    fn grid_target_round_trip() {
+        let mut nrrd = Nrrd::from(&ramp());
+        nrrd.set_field("space origin", "(10)".to_string());
+        nrrd.set_field("space directions", "(2)".to_string());
+        let grid = nrrd.grid::<1>().unwrap();
+        assert_eq!(grid.origin, [10.0]);
+        assert_eq!(grid.spacings, [2.0]);
+
+        let target = ResampleTarget::Grid(grid);
+        let same = nrrd
+            .resample::<f64, 1>(&target, &ResampleOptions::default())
+            .unwrap();
+        assert_eq!(same.buffer(), nrrd.buffer());
+        assert_eq!(same.field("space origin"), Some("(10)"));
+
+        let options = ResampleOptions {
+            anti_alias: false,
+            ..options(Kernel::Linear)
+        };
+        let fine = Grid {
+            sizes: [31],
+            spacings: [1.0],
+            origin: [10.0],
+        };
+        let (upsampled, _) = ramp()
+            .resample(&grid, &ResampleTarget::Grid(fine), &options)
+            .unwrap();
+        assert_eq!(upsampled[[1]], 2.0);
+        let (back, back_grid) = upsampled
+            .resample(&fine, &ResampleTarget::Grid(grid), &options)
+            .unwrap();
+        assert_eq!(back_grid, grid);
+        assert_eq!(back.pixels(), ramp().pixels());
+    }
+
+    #[test]
+    fn node_centred_axes_keep_their_end_samples() {
+        let mut nrrd = Nrrd::from(&Image::<f64, 1>::from_fn([5], |[i]| 10.0 * i as f64));
+        nrrd.set_field("centers", "node".to_string());
+        nrrd.set_field("axis mins", "0".to_string());
+        nrrd.set_field("axis maxs", "4".to_string());
+        let options = ResampleOptions {
+            anti_alias: false,
+            ..options(Kernel::Linear)
+        };
+
+        let upsampled = nrrd
+            .resample::<f64, 1>(&ResampleTarget::Sizes([9]), &options)
+            .unwrap();
+        let pixels = Image::<f64, 1>::try_from(&upsampled).unwrap().into_vec();
+        assert_eq!(pixels, (0..9).map(|i| 5.0 * i as f64).collect::<Vec<_>>());
+        assert_eq!(upsampled.field("axis mins"), Some("0"));
+        assert_eq!(upsampled.field("axis maxs"), Some("4"));
+
+        let coarse = nrrd
+            .resample::<f64, 1>(&ResampleTarget::Spacings([2.0]), &options)
+            .unwrap();
+        let pixels = Image::<f64, 1>::try_from(&coarse).unwrap().into_vec();
+        assert_eq!(pixels, &[0.0, 20.0, 40.0]);
+        assert_eq!(coarse.field("axis mins"), Some("0"));
+        assert_eq!(coarse.field("axis maxs"), Some("4"));
+    }
+
+    #[test]
+    fn resamples_views_like_images() {
+        let image = Image::<f64, 2>::from_fn([6, 4], |[x, y]| (x * y) as f64);
+        let view = image.view().flip(1).region([1, 0], [4, 3]).unwrap();
+        let grid = Grid::new(*view.sizes());
+        let target = ResampleTarget::Sizes([7, 5]);
+
+        let (from_view, view_grid) = view
+            .resample(&grid, &target, &options(Kernel::CubicBSpline))
+            .unwrap();
+        let (from_image, image_grid) = view
+            .to_image()
+            .resample(&grid, &target, &options(Kernel::CubicBSpline))
+            .unwrap();
+        assert_eq!(from_view.pixels(), from_image.pixels());
+        assert_eq!(view_grid, image_grid);
+    }
+
+    #[test]
+    fn grid_follows_crop_and_pad() {
+        let grid = Grid {
+            sizes: [4, 5],
+            spacings: [2.0, 0.5],
+            origin: [10.0, -1.0],
+        };
+
+        let cropped = grid.crop([1, 2], [3, 2]);
+        assert_eq!(cropped.sizes, [3, 2]);
+        assert_eq!(cropped.spacings, grid.spacings);
+        assert_eq!(cropped.origin, [12.0, 0.0]);
+
+        let padded = grid.pad([2, 0], [1, 3]);
+        assert_eq!(padded.sizes, [7, 8]);
+        assert_eq!(padded.spacings, grid.spacings);
+        assert_eq!(padded.origin, [6.0, -1.0]);
+
+        // Pixel kept by the crop lies where it was
+        let image = Image::<u8, 2>::from_fn(grid.sizes, |[x, y]| (x + 4 * y) as u8);
+        let region = image.crop([1, 2], [3, 2]).unwrap();
+        assert_eq!(region[[0, 0]], image[[1, 2]]);
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        let image = ramp();
+        let grid = Grid::new([16]);
+        let target = ResampleTarget::Sizes([8]);
+
+        assert!(matches!(
+            image.resample(&grid, &target, &options(Kernel::Lanczos(0))),
+            Err(ResampleErr::InvalidKernel)
+        ));
+        assert!(matches!(
+            image.resample(&Grid::new([15]), &target, &ResampleOptions::default()),
+            Err(ResampleErr::SizesDoNotMatch)
+        ));
+        assert!(matches!(
+            image.resample(
+                &grid,
+                &ResampleTarget::Spacings([0.0]),
+                &ResampleOptions::default()
+            ),
+            Err(ResampleErr::InvalidSpacing)
+        ));
+
+        let empty = Image::<f64, 1>::new(0.0, [0]);
+        assert!(matches!(
+            empty.resample(&Grid::new([0]), &target, &ResampleOptions::default()),
+            Err(ResampleErr::EmptyImage)
+        ));
+        let options = ResampleOptions {
+            boundary: Boundary::Constant(3.0),
+            ..Default::default()
+        };
+        let (filled, _) = empty.resample(&Grid::new([0]), &target, &options).unwrap();
+        assert_eq!(filled.pixels(), &[3.0; 8]);
+
+        let mut nrrd = Nrrd::from(&image);
+        assert!(matches!(
+            nrrd.grid::<2>(),
+            Err(ResampleErr::DimensionsDoNotMatch)
+        ));
+        nrrd.set_buffer(vec![0; 3]);
+        assert!(matches!(
+            nrrd.grid::<1>(),
+            Err(ResampleErr::InvalidAxes(AxisErr::BufferSizeDoesNotMatch))
+        ));
+    }
+}
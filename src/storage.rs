@@ -0,0 +1,339 @@
+//! Containers that can hold the pixels of an `Image`.
+//!
+//! `Image` is generic over its storage, which defaults to `Vec`.
+//! Read-only storages give access to pixels, `StorageMut` ones also allow changing them.
+
+use std::{
+    alloc::{self, Layout},
+    fmt,
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+    slice,
+    sync::Arc,
+};
+
+/// Contiguous pixels, first axis is the fastest
+pub trait Storage<T> {
+    fn as_slice(&self) -> &[T];
+}
+
+pub trait StorageMut<T>: Storage<T> {
+    fn as_mut_slice(&mut self) -> &mut [T];
+}
+
+impl<T> Storage<T> for Vec<T> {
+    #[inline]
+    fn as_slice(&self) -> &[T] {
+        self
+    }
+}
+
+impl<T> StorageMut<T> for Vec<T> {
+    #[inline]
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        self
+    }
+}
+
+impl<T> Storage<T> for Box<[T]> {
+    #[inline]
+    fn as_slice(&self) -> &[T] {
+        self
+    }
+}
+
+impl<T> StorageMut<T> for Box<[T]> {
+    #[inline]
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        self
+    }
+}
+
+/// Cheap to clone, pixels are copied on the first write while they are shared
+impl<T> Storage<T> for Arc<[T]> {
+    #[inline]
+    fn as_slice(&self) -> &[T] {
+        self
+    }
+}
+
+impl<T: Clone> StorageMut<T> for Arc<[T]> {
+    #[inline]
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        Arc::make_mut(self)
+    }
+}
+
+impl<T> Storage<T> for &[T] {
+    #[inline]
+    fn as_slice(&self) -> &[T] {
+        self
+    }
+}
+
+impl<T> Storage<T> for &mut [T] {
+    #[inline]
+    fn as_slice(&self) -> &[T] {
+        self
+    }
+}
+
+impl<T> StorageMut<T> for &mut [T] {
+    #[inline]
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        self
+    }
+}
+
+/// Heap buffer with alignment chosen by the caller, e.g. to suit SIMD loads
+pub struct AlignedBuffer<T> {
+    ptr: NonNull<T>,
+    len: usize,
+    layout: Layout,
+}
+
+// SAFETY: buffer owns its pixels like `Vec` does
+unsafe impl<T: Send> Send for AlignedBuffer<T> {}
+unsafe impl<T: Sync> Sync for AlignedBuffer<T> {}
+
+impl<T> AlignedBuffer<T> {
+    /// Allocates `len` pixels starting at a multiple of `alignment` and fills them from `pixels`.
+    /// Length is raised only after a pixel is written, so if the iterator panics
+    /// only the pixels written so far are dropped, along with the allocation.
+    fn from_iter_aligned(pixels: impl Iterator<Item = T>, len: usize, alignment: usize) -> Self {
+        assert!(
+            alignment.is_power_of_two() && alignment >= align_of::<T>(),
+            "invalid alignment"
+        );
+
+        let layout = Layout::array::<T>(len)
+            .and_then(|l| l.align_to(alignment))
+            .expect("buffer is too large");
+
+        let ptr = match layout.size() {
+            0 => NonNull::dangling(),
+            // SAFETY: layout has non-zero size
+            _ => match NonNull::new(unsafe { alloc::alloc(layout) }.cast::<T>()) {
+                Some(ptr) => ptr,
+                None => alloc::handle_alloc_error(layout),
+            },
+        };
+
+        let mut buffer = Self {
+            ptr,
+            len: 0,
+            layout,
+        };
+        for pixel in pixels.take(len) {
+            // SAFETY: `buffer.len < len`, so the pixel is within the allocation
+            unsafe { ptr.as_ptr().add(buffer.len).write(pixel) };
+            buffer.len += 1;
+        }
+        assert_eq!(buffer.len, len, "not enough pixels");
+
+        buffer
+    }
+
+    /// Alignment the buffer was allocated with
+    #[inline]
+    pub fn alignment(&self) -> usize {
+        self.layout.align()
+    }
+}
+
+impl<T: Clone> AlignedBuffer<T> {
+    /// Allocates `len` copies of `value` starting at a multiple of `alignment`.
+    /// Panics if `alignment` is not a power of two or is smaller than the alignment of `T`.
+    pub fn new(value: T, len: usize, alignment: usize) -> Self {
+        Self::from_iter_aligned(std::iter::repeat_n(value, len), len, alignment)
+    }
+
+    /// Copies `pixels` into a buffer starting at a multiple of `alignment`, panics like `new`
+    pub fn from_slice(pixels: &[T], alignment: usize) -> Self {
+        Self::from_iter_aligned(pixels.iter().cloned(), pixels.len(), alignment)
+    }
+}
+
+impl<T> Deref for AlignedBuffer<T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &[T] {
+        // SAFETY: first `len` pixels are initialized
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T> DerefMut for AlignedBuffer<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut [T] {
+        // SAFETY: first `len` pixels are initialized and borrowed exclusively
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T> Drop for AlignedBuffer<T> {
+    fn drop(&mut self) {
+        // SAFETY: pixels are initialized and memory was allocated with `layout`
+        unsafe {
+            std::ptr::drop_in_place(&mut **self as *mut [T]);
+            if self.layout.size() != 0 {
+                alloc::dealloc(self.ptr.as_ptr().cast(), self.layout);
+            }
+        }
+    }
+}
+
+impl<T: Clone> Clone for AlignedBuffer<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self::from_slice(self, self.alignment())
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for AlignedBuffer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T> Storage<T> for AlignedBuffer<T> {
+    #[inline]
+    fn as_slice(&self) -> &[T] {
+        self
+    }
+}
+
+impl<T> StorageMut<T> for AlignedBuffer<T> {
+    #[inline]
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        self
+    }
+}
+
+#[cfg(feature = "mmap")]
+pub use mmap::MmapStorage;
+
+#[cfg(feature = "mmap")]
+mod mmap {
+    use super::Storage;
+    use crate::pixel::PodPixel;
+    use std::{fmt, marker::PhantomData, slice};
+
+    /// Raw pixels of a memory mapped file, created by `NrrdFile::map_image`
+    pub struct MmapStorage<T: PodPixel> {
+        map: memmap2::Mmap,
+        offset: usize,
+        len: usize,
+        pixel: PhantomData<T>,
+    }
+
+    impl<T: PodPixel> MmapStorage<T> {
+        /// Caller must check that `len` pixels starting at `offset` are within the map and aligned for `T`
+        pub(crate) unsafe fn new(map: memmap2::Mmap, offset: usize, len: usize) -> Self {
+            Self {
+                map,
+                offset,
+                len,
+                pixel: PhantomData,
+            }
+        }
+    }
+
+    impl<T: PodPixel + fmt::Debug> fmt::Debug for MmapStorage<T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_list().entries(self.as_slice()).finish()
+        }
+    }
+
+    impl<T: PodPixel> Storage<T> for MmapStorage<T> {
+        #[inline]
+        fn as_slice(&self) -> &[T] {
+            // SAFETY: bounds and alignment were checked on creation, any bytes are valid for `T`
+            unsafe { slice::from_raw_parts(self.map.as_ptr().add(self.offset).cast(), self.len) }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AlignedBuffer;
+    use crate::image::Image;
+    use std::sync::Arc;
+
+    #[test]
+    fn shared_pixels_are_copied_on_write() {
+        let pixels: Arc<[u16]> = (0..6).collect();
+        let mut image = Image::<u16, 2, Arc<[u16]>>::from_storage(pixels, [3, 2]).unwrap();
+        let copy = image.clone();
+        assert!(Arc::ptr_eq(image.storage(), copy.storage()));
+
+        image[[2, 1]] = 100;
+        assert!(!Arc::ptr_eq(image.storage(), copy.storage()));
+        assert_eq!(copy[[2, 1]], 5);
+        assert_eq!(image[[2, 1]], 100);
+
+        // Unshared pixels are changed where they are
+        let before = image.storage().as_ptr();
+        image.pixels_mut()[0] = 7;
+        assert_eq!(image.storage().as_ptr(), before);
+        assert_eq!(image.to_owned_image().pixels(), &[7, 1, 2, 3, 4, 100]);
+    }
+
+    #[test]
+    fn aligned_buffers_keep_alignment() {
+        let buffer = AlignedBuffer::from_slice(&[1f32, 2.0, 3.0, 4.0], 64);
+        assert_eq!(buffer.alignment(), 64);
+        assert_eq!(buffer.as_ptr() as usize % 64, 0);
+
+        let mut image = Image::<f32, 1, _>::from_storage(buffer, [4]).unwrap();
+        image[[3]] = 8.0;
+        let copy = image.clone();
+        image[[0]] = -1.0;
+        assert_eq!(copy.storage().as_ptr() as usize % 64, 0);
+        assert_eq!(&copy.storage()[..], &[1.0, 2.0, 3.0, 8.0]);
+        assert_eq!(image.pixels(), &[-1.0, 2.0, 3.0, 8.0]);
+
+        let empty = AlignedBuffer::new(0u64, 0, 4096);
+        assert!(empty.is_empty());
+        assert_eq!(AlignedBuffer::new(String::from("a"), 3, 32).concat(), "aaa");
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid alignment")]
+    fn rejects_alignment_below_the_pixel_type() {
+        AlignedBuffer::new(0u64, 4, 2);
+    }
+
+    #[test]
+    fn borrowed_and_boxed_pixels() {
+        let mut pixels = vec![1u8, 2, 3, 4];
+        assert!(Image::<u8, 2, &[u8]>::from_storage(&pixels, [3, 1]).is_none());
+
+        let mut image = Image::<u8, 2, &mut [u8]>::from_storage(&mut pixels, [2, 2]).unwrap();
+        image[[1, 1]] = 40;
+        assert_eq!(pixels, [1, 2, 3, 40]);
+
+        let boxed = Image::<u8, 1, Box<[u8]>>::from_storage(pixels.into(), [4]).unwrap();
+        assert_eq!(boxed.into_storage().len(), 4);
+    }
+
+    #[test]
+    #[cfg(feature = "mmap")]
+    fn mapped_pixels_match_the_file() {
+        use crate::nrrd::{file::NrrdFile, writer::write_nrrd, Nrrd};
+
+        let image = Image::<u8, 2>::from_fn([5, 3], |[x, y]| (x * 3 + y) as u8);
+        let path = std::env::temp_dir().join(format!("rusty_nrrd_{}_mmap", std::process::id()));
+        write_nrrd(&Nrrd::from(&image), std::fs::File::create(&path).unwrap()).unwrap();
+
+        let file = NrrdFile::open(&path).unwrap();
+        // SAFETY: the file is not modified while mapped
+        let mapped = unsafe { file.map_image::<u8, 2>() }.unwrap();
+        assert_eq!(mapped.pixels(), image.pixels());
+        assert_eq!(mapped.to_owned_image()[[4, 2]], 14);
+
+        drop(mapped);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,564 @@
+//! Borrowed images with arbitrary strides.
+//!
+//! Views start out covering a contiguous buffer and can then be narrowed to a region,
+//! flipped, permuted or stepped along an axis without copying.
+//! Each of these keeps distinct indices on distinct pixels, which `ImageViewMut` relies on.
+
+use crate::{
+    image::Image,
+    iter::{IndexedIter, IndexedIterMut},
+    pixel::PixelValue,
+    storage::{Storage, StorageMut},
+};
+use std::ops::{Index, IndexMut};
+
+/// Mapping from pixel index to buffer offset
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Layout<const D: usize> {
+    pub(crate) start: usize,
+    pub(crate) strides: [isize; D],
+    pub(crate) sizes: [usize; D],
+}
+
+impl<const D: usize> Layout<D> {
+    /// First axis is the fastest
+    fn contiguous(sizes: [usize; D]) -> Self {
+        let mut strides = [0; D];
+        let mut stride = 1;
+
+        for (s, &size) in strides.iter_mut().zip(&sizes) {
+            *s = stride as isize;
+            stride *= size;
+        }
+
+        Self {
+            start: 0,
+            strides,
+            sizes,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn pixels_count(&self) -> usize {
+        self.sizes.iter().product()
+    }
+
+    /// Panics if index is out of bounds on any axis
+    #[inline]
+    pub(crate) fn offset(&self, index: &[usize; D]) -> usize {
+        self.try_offset(index).expect("index out of bounds")
+    }
+
+    #[inline]
+    fn try_offset(&self, index: &[usize; D]) -> Option<usize> {
+        let mut offset = self.start as isize;
+
+        for ((&i, &size), &stride) in index.iter().zip(&self.sizes).zip(&self.strides) {
+            if i >= size {
+                return None;
+            }
+            offset += i as isize * stride;
+        }
+
+        Some(offset as usize)
+    }
+
+    fn region(&self, origin: [usize; D], sizes: [usize; D]) -> Option<Self> {
+        let fits = (0..D).all(|axis| {
+            origin[axis]
+                .checked_add(sizes[axis])
+                .is_some_and(|end| end <= self.sizes[axis])
+        });
+        if !fits {
+            return None;
+        }
+
+        // Empty regions keep the start, there is nothing to address
+        let start = match sizes.contains(&0) {
+            true => self.start,
+            false => self.offset(&origin),
+        };
+
+        Some(Self {
+            start,
+            strides: self.strides,
+            sizes,
+        })
+    }
+
+    fn flip(&self, axis: usize) -> Self {
+        let mut flipped = *self;
+
+        if self.sizes[axis] > 0 {
+            let last = (self.sizes[axis] - 1) as isize;
+            flipped.start = (self.start as isize + last * self.strides[axis]) as usize;
+        }
+        flipped.strides[axis] = -self.strides[axis];
+
+        flipped
+    }
+
+    fn permute(&self, axes: [usize; D]) -> Self {
+        let mut seen = [false; D];
+        for &axis in &axes {
+            assert!(axis < D && !seen[axis], "axes are not a permutation");
+            seen[axis] = true;
+        }
+
+        Self {
+            start: self.start,
+            strides: axes.map(|axis| self.strides[axis]),
+            sizes: axes.map(|axis| self.sizes[axis]),
+        }
+    }
+
+    fn step(&self, axis: usize, step: usize) -> Self {
+        assert!(step > 0, "step must be positive");
+
+        let mut stepped = *self;
+        stepped.sizes[axis] = self.sizes[axis].div_ceil(step);
+        stepped.strides[axis] = self.strides[axis] * step as isize;
+
+        stepped
+    }
+
+    fn is_contiguous(&self) -> bool {
+        let contiguous = Self::contiguous(self.sizes);
+        self.pixels_count() <= 1
+            || (0..D)
+                .all(|axis| self.sizes[axis] <= 1 || self.strides[axis] == contiguous.strides[axis])
+    }
+
+    /// Index of the `n`-th pixel, first axis is the fastest.
+    /// `n` must be less than the number of pixels.
+    #[inline]
+    pub(crate) fn unravel(&self, mut n: usize) -> [usize; D] {
+        let mut index = [0; D];
+
+        for (i, &size) in index.iter_mut().zip(&self.sizes) {
+            *i = n % size;
+            n /= size;
+        }
+
+        index
+    }
+
+    /// Index following `index`, first axis is the fastest
+    #[inline]
+    pub(crate) fn advance(&self, index: &mut [usize; D]) {
+        for (i, &size) in index.iter_mut().zip(&self.sizes) {
+            *i += 1;
+            if *i < size {
+                return;
+            }
+            *i = 0;
+        }
+    }
+}
+
+/// Borrowed image with arbitrary strides
+#[derive(Debug)]
+pub struct ImageView<'a, TPixel: PixelValue, const D: usize> {
+    pub(crate) pixels: &'a [TPixel],
+    pub(crate) layout: Layout<D>,
+}
+
+// Derives would require `T: Copy`, but only a reference is copied
+impl<T: PixelValue, const D: usize> Clone for ImageView<'_, T, D> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: PixelValue, const D: usize> Copy for ImageView<'_, T, D> {}
+
+impl<'a, T: PixelValue, const D: usize> ImageView<'a, T, D> {
+    /// View over contiguous pixels, returns `None` if their number does not match `sizes`
+    pub fn new(pixels: &'a [T], sizes: [usize; D]) -> Option<Self> {
+        if pixels.len() != sizes.iter().product::<usize>() {
+            return None;
+        }
+
+        Some(Self {
+            pixels,
+            layout: Layout::contiguous(sizes),
+        })
+    }
+
+    #[inline]
+    pub fn pixels_count(&self) -> usize {
+        self.layout.pixels_count()
+    }
+
+    #[inline]
+    pub fn get(&self, index: &[usize; D]) -> &'a T {
+        &self.pixels[self.layout.offset(index)]
+    }
+
+    /// Returns `None` if index is out of bounds
+    #[inline]
+    pub fn try_get(&self, index: &[usize; D]) -> Option<&'a T> {
+        self.layout.try_offset(index).map(|o| &self.pixels[o])
+    }
+
+    #[inline]
+    pub fn sizes(&self) -> &[usize; D] {
+        &self.layout.sizes
+    }
+
+    /// Distance in pixels between neighbours along each axis
+    #[inline]
+    pub fn strides(&self) -> &[isize; D] {
+        &self.layout.strides
+    }
+
+    /// Pixels as a slice, if they are stored contiguously with the first axis being the fastest
+    pub fn as_slice(&self) -> Option<&'a [T]> {
+        match self.layout.is_contiguous() {
+            true => Some(&self.pixels[self.layout.start..self.layout.start + self.pixels_count()]),
+            false => None,
+        }
+    }
+
+    /// Pixels in index order, first axis is the fastest
+    pub fn iter(&self) -> ViewIter<'a, T, D> {
+        match self.as_slice() {
+            Some(pixels) => ViewIter(Pixels::Contiguous(pixels.iter())),
+            None => ViewIter(Pixels::Strided(self.indexed_iter())),
+        }
+    }
+
+    /// Sub-region starting at `origin`, returns `None` if it does not fit
+    pub fn region(&self, origin: [usize; D], sizes: [usize; D]) -> Option<Self> {
+        Some(Self {
+            pixels: self.pixels,
+            layout: self.layout.region(origin, sizes)?,
+        })
+    }
+
+    /// Reverses the order of pixels along `axis`
+    pub fn flip(&self, axis: usize) -> Self {
+        Self {
+            pixels: self.pixels,
+            layout: self.layout.flip(axis),
+        }
+    }
+
+    /// Axis `i` of the result is axis `axes[i]` of this view, panics if `axes` is not a permutation
+    pub fn permute(&self, axes: [usize; D]) -> Self {
+        Self {
+            pixels: self.pixels,
+            layout: self.layout.permute(axes),
+        }
+    }
+
+    /// Keeps every `step`-th pixel along `axis`, starting with the first
+    pub fn step(&self, axis: usize, step: usize) -> Self {
+        Self {
+            pixels: self.pixels,
+            layout: self.layout.step(axis, step),
+        }
+    }
+
+    /// Copies pixels into an owned image
+    pub fn to_image(&self) -> Image<T, D> {
+        Image::from_parts(self.iter().cloned().collect(), self.layout.sizes)
+    }
+
+    /// Applies `f` to every pixel
+    pub fn map<U: PixelValue>(&self, f: impl FnMut(&T) -> U) -> Image<U, D> {
+        Image::from_parts(self.iter().map(f).collect(), self.layout.sizes)
+    }
+
+    /// Applies `f` to pixels at the same index of both images, panics if sizes differ
+    pub fn zip_map<'b, U: PixelValue + 'b, V: PixelValue>(
+        &self,
+        other: impl Into<ImageView<'b, U, D>>,
+        mut f: impl FnMut(&T, &U) -> V,
+    ) -> Image<V, D> {
+        let other = other.into();
+        assert_eq!(self.sizes(), other.sizes(), "image sizes do not match");
+
+        let buffer = self
+            .iter()
+            .zip(other.iter())
+            .map(|(a, b)| f(a, b))
+            .collect();
+        Image::from_parts(buffer, self.layout.sizes)
+    }
+}
+
+impl<'a, T: PixelValue, const D: usize, S: Storage<T>> From<&'a Image<T, D, S>>
+    for ImageView<'a, T, D>
+{
+    #[inline]
+    fn from(image: &'a Image<T, D, S>) -> Self {
+        Self {
+            pixels: image.pixels(),
+            layout: Layout::contiguous(*image.sizes()),
+        }
+    }
+}
+
+impl<'a, T: PixelValue, const D: usize> From<&'a ImageViewMut<'_, T, D>> for ImageView<'a, T, D> {
+    #[inline]
+    fn from(view: &'a ImageViewMut<'_, T, D>) -> Self {
+        view.as_view()
+    }
+}
+
+impl<T: PixelValue, const D: usize> Index<&[usize; D]> for ImageView<'_, T, D> {
+    type Output = T;
+
+    #[inline]
+    fn index(&self, index: &[usize; D]) -> &Self::Output {
+        self.get(index)
+    }
+}
+
+impl<T: PixelValue, const D: usize> Index<[usize; D]> for ImageView<'_, T, D> {
+    type Output = T;
+
+    #[inline]
+    fn index(&self, index: [usize; D]) -> &Self::Output {
+        self.get(&index)
+    }
+}
+
+/// Mutably borrowed image with arbitrary strides
+#[derive(Debug)]
+pub struct ImageViewMut<'a, TPixel: PixelValue, const D: usize> {
+    pub(crate) pixels: &'a mut [TPixel],
+    pub(crate) layout: Layout<D>,
+}
+
+impl<'a, T: PixelValue, const D: usize> ImageViewMut<'a, T, D> {
+    /// View over contiguous pixels, returns `None` if their number does not match `sizes`
+    pub fn new(pixels: &'a mut [T], sizes: [usize; D]) -> Option<Self> {
+        if pixels.len() != sizes.iter().product::<usize>() {
+            return None;
+        }
+
+        Some(Self {
+            pixels,
+            layout: Layout::contiguous(sizes),
+        })
+    }
+
+    #[inline]
+    pub fn pixels_count(&self) -> usize {
+        self.layout.pixels_count()
+    }
+
+    #[inline]
+    pub fn get(&self, index: &[usize; D]) -> &T {
+        &self.pixels[self.layout.offset(index)]
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, index: &[usize; D]) -> &mut T {
+        &mut self.pixels[self.layout.offset(index)]
+    }
+
+    /// Returns `None` if index is out of bounds
+    #[inline]
+    pub fn try_get_mut(&mut self, index: &[usize; D]) -> Option<&mut T> {
+        self.layout.try_offset(index).map(|o| &mut self.pixels[o])
+    }
+
+    #[inline]
+    pub fn sizes(&self) -> &[usize; D] {
+        &self.layout.sizes
+    }
+
+    /// Distance in pixels between neighbours along each axis
+    #[inline]
+    pub fn strides(&self) -> &[isize; D] {
+        &self.layout.strides
+    }
+
+    /// Read-only view of the same pixels
+    #[inline]
+    pub fn as_view(&self) -> ImageView<'_, T, D> {
+        ImageView {
+            pixels: self.pixels,
+            layout: self.layout,
+        }
+    }
+
+    /// Shorter lived view of the same pixels, so that this one can be used again afterwards
+    #[inline]
+    pub fn reborrow(&mut self) -> ImageViewMut<'_, T, D> {
+        ImageViewMut {
+            pixels: self.pixels,
+            layout: self.layout,
+        }
+    }
+
+    /// Pixels in index order, first axis is the fastest
+    pub fn iter_mut(&mut self) -> ViewIterMut<'_, T, D> {
+        ViewIterMut(self.indexed_iter_mut())
+    }
+
+    /// Sets every pixel to `value`
+    pub fn fill(&mut self, value: T) {
+        for pixel in self.iter_mut() {
+            *pixel = value.clone();
+        }
+    }
+
+    /// Replaces every pixel with the result of `f`
+    pub fn map_in_place(&mut self, mut f: impl FnMut(&T) -> T) {
+        for pixel in self.iter_mut() {
+            *pixel = f(pixel);
+        }
+    }
+
+    /// Replaces every pixel with the result of `f` applied to it and the pixel of `other`
+    /// at the same index, panics if sizes differ
+    pub fn zip_map_in_place<'b, U: PixelValue + 'b>(
+        &mut self,
+        other: impl Into<ImageView<'b, U, D>>,
+        mut f: impl FnMut(&T, &U) -> T,
+    ) {
+        let other = other.into();
+        assert_eq!(self.sizes(), other.sizes(), "image sizes do not match");
+
+        for (a, b) in self.iter_mut().zip(other.iter()) {
+            *a = f(a, b);
+        }
+    }
+
+    /// Copies pixels from a view of the same sizes, panics if sizes differ
+    pub fn copy_from(&mut self, source: &ImageView<'_, T, D>) {
+        assert_eq!(self.sizes(), source.sizes(), "view sizes do not match");
+
+        for (pixel, value) in self.iter_mut().zip(source.iter()) {
+            *pixel = value.clone();
+        }
+    }
+
+    /// Sub-region starting at `origin`, returns `None` if it does not fit
+    pub fn into_region(self, origin: [usize; D], sizes: [usize; D]) -> Option<Self> {
+        Some(Self {
+            layout: self.layout.region(origin, sizes)?,
+            pixels: self.pixels,
+        })
+    }
+
+    /// Reverses the order of pixels along `axis`
+    pub fn into_flipped(self, axis: usize) -> Self {
+        Self {
+            layout: self.layout.flip(axis),
+            pixels: self.pixels,
+        }
+    }
+
+    /// Axis `i` of the result is axis `axes[i]` of this view, panics if `axes` is not a permutation
+    pub fn into_permuted(self, axes: [usize; D]) -> Self {
+        Self {
+            layout: self.layout.permute(axes),
+            pixels: self.pixels,
+        }
+    }
+
+    /// Keeps every `step`-th pixel along `axis`, starting with the first
+    pub fn into_stepped(self, axis: usize, step: usize) -> Self {
+        Self {
+            layout: self.layout.step(axis, step),
+            pixels: self.pixels,
+        }
+    }
+}
+
+impl<'a, T: PixelValue, const D: usize, S: StorageMut<T>> From<&'a mut Image<T, D, S>>
+    for ImageViewMut<'a, T, D>
+{
+    #[inline]
+    fn from(image: &'a mut Image<T, D, S>) -> Self {
+        image.view_mut()
+    }
+}
+
+impl<T: PixelValue, const D: usize> Index<&[usize; D]> for ImageViewMut<'_, T, D> {
+    type Output = T;
+
+    #[inline]
+    fn index(&self, index: &[usize; D]) -> &Self::Output {
+        self.get(index)
+    }
+}
+
+impl<T: PixelValue, const D: usize> Index<[usize; D]> for ImageViewMut<'_, T, D> {
+    type Output = T;
+
+    #[inline]
+    fn index(&self, index: [usize; D]) -> &Self::Output {
+        self.get(&index)
+    }
+}
+
+impl<T: PixelValue, const D: usize> IndexMut<&[usize; D]> for ImageViewMut<'_, T, D> {
+    #[inline]
+    fn index_mut(&mut self, index: &[usize; D]) -> &mut Self::Output {
+        self.get_mut(index)
+    }
+}
+
+impl<T: PixelValue, const D: usize> IndexMut<[usize; D]> for ImageViewMut<'_, T, D> {
+    #[inline]
+    fn index_mut(&mut self, index: [usize; D]) -> &mut Self::Output {
+        self.get_mut(&index)
+    }
+}
+
+/// Iterator returned by `ImageView::iter`
+pub struct ViewIter<'a, T, const D: usize>(Pixels<'a, T, D>);
+
+/// Contiguous views are walked as a slice, without computing offsets
+enum Pixels<'a, T, const D: usize> {
+    Contiguous(std::slice::Iter<'a, T>),
+    Strided(IndexedIter<'a, T, D>),
+}
+
+impl<'a, T, const D: usize> Iterator for ViewIter<'a, T, D> {
+    type Item = &'a T;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.0 {
+            Pixels::Contiguous(pixels) => pixels.next(),
+            Pixels::Strided(pixels) => pixels.next().map(|(_, pixel)| pixel),
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.0 {
+            Pixels::Contiguous(pixels) => pixels.size_hint(),
+            Pixels::Strided(pixels) => pixels.size_hint(),
+        }
+    }
+}
+
+impl<T, const D: usize> ExactSizeIterator for ViewIter<'_, T, D> {}
+
+/// Iterator returned by `ImageViewMut::iter_mut`
+pub struct ViewIterMut<'a, T, const D: usize>(IndexedIterMut<'a, T, D>);
+
+impl<'a, T, const D: usize> Iterator for ViewIterMut<'a, T, D> {
+    type Item = &'a mut T;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(_, pixel)| pixel)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<T, const D: usize> ExactSizeIterator for ViewIterMut<'_, T, D> {}
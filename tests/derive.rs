@@ -0,0 +1,116 @@
+#![cfg(feature = "derive")]
+
+use rusty_nrrd::{read_nrrd, write_nrrd, Image, Nrrd, PixelType, PixelValue};
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, PixelValue)]
+#[nrrd(kind = "RGB-color")]
+struct Color {
+    r: u16,
+    g: u16,
+    b: u16,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, PixelValue)]
+struct Sample(f32, f32);
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, PixelValue)]
+struct Record {
+    id: u8,
+    value: i32,
+}
+
+fn round_trip<T: PixelValue + PartialEq + std::fmt::Debug>(image: &Image<T, 1>) -> Nrrd {
+    let mut file = Vec::new();
+    write_nrrd(&Nrrd::from(image), &mut file).unwrap();
+    let nrrd = read_nrrd(file.as_slice()).unwrap();
+
+    let read = Image::<T, 1>::try_from(&nrrd).unwrap();
+    assert_eq!(read.pixels(), image.pixels());
+    nrrd
+}
+
+#[test]
+fn components_use_the_given_kind() {
+    assert_eq!(Color::pixel_type(), PixelType::UInt16);
+    assert_eq!(Color::components(), 3);
+    assert!(!Color::accepts_kind("vector"));
+
+    let image = Image::<Color, 1>::from_fn([2], |[i]| Color {
+        r: i as u16,
+        g: 1000,
+        b: u16::MAX,
+    });
+    let nrrd = round_trip(&image);
+    assert_eq!(nrrd.sizes(), &[3, 2]);
+    assert_eq!(nrrd.field("kinds"), Some("RGB-color domain"));
+
+    let rgb = Image::<rusty_nrrd::Rgb<u16>, 1>::try_from(&nrrd).unwrap();
+    assert_eq!(rgb[[1]].r, 1);
+}
+
+#[test]
+fn unnamed_fields_default_to_vector() {
+    assert_eq!(Sample::kind(), Some("vector"));
+    assert!(Sample::accepts_kind("complex"));
+
+    let image = Image::<Sample, 1>::from_fn([3], |[i]| Sample(i as f32, -0.5));
+    let nrrd = round_trip(&image);
+    assert_eq!(nrrd.field("kinds"), Some("vector domain"));
+}
+
+#[test]
+fn mixed_fields_become_blocks() {
+    assert_eq!(Record::pixel_type(), PixelType::Block(5));
+    assert_eq!(Record::pixel_size(), 5);
+
+    let image = Image::<Record, 1>::from_fn([2], |[i]| Record {
+        id: i as u8,
+        value: -70000,
+    });
+    let nrrd = round_trip(&image);
+    assert_eq!(nrrd.sizes(), &[2]);
+    assert_eq!(nrrd.field("block size"), Some("5"));
+}
+
+#[test]
+fn block_fields_ignore_the_file_endian() {
+    let mut file = b"NRRD0005\ntype: block\nblock size: 5\ndimension: 1\nsizes: 1\n\
+        encoding: raw\nendian: big\n\n"
+        .to_vec();
+    file.extend_from_slice(&[7, 0x04, 0x03, 0x02, 0x01]);
+
+    let nrrd = read_nrrd(file.as_slice()).unwrap();
+    let image = Image::<Record, 1>::try_from(&nrrd).unwrap();
+    let record = Record {
+        id: 7,
+        value: 0x01020304,
+    };
+    assert_eq!(image.pixels(), &[record]);
+
+    let mut written = Vec::new();
+    write_nrrd(&nrrd, &mut written).unwrap();
+    assert!(written.ends_with(&[7, 0x04, 0x03, 0x02, 0x01]));
+    let read = Image::<Record, 1>::try_read_nrrd(written.as_slice()).unwrap();
+    assert_eq!(read.pixels(), &[record]);
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, PixelValue)]
+struct Tagged<T> {
+    tag: u8,
+    value: T,
+}
+
+#[test]
+fn generic_fields_are_bounded() {
+    assert_eq!(Tagged::<i16>::pixel_type(), PixelType::Block(3));
+
+    let image = Image::<Tagged<i16>, 1>::from_fn([2], |[i]| Tagged {
+        tag: i as u8,
+        value: -300,
+    });
+    round_trip(&image);
+}